@@ -0,0 +1,73 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use thoth_cli::markdown_renderer::{BlockRenderMemo, MarkdownRenderer};
+
+const WIDTH: usize = 100;
+const SECTIONS: usize = 200;
+
+/// A long block mixing prose, lists and fenced code, roughly 5k lines.
+fn large_block() -> Vec<String> {
+    let mut lines = Vec::new();
+    for i in 0..SECTIONS {
+        lines.push(format!("## Section {}", i));
+        lines.push(String::new());
+        lines.push(format!(
+            "Some *emphasis* and `inline code` in paragraph {} with enough text to wrap.",
+            i
+        ));
+        lines.push(format!("- [ ] task {}", i));
+        lines.push("- plain item".to_string());
+        lines.push("```rust".to_string());
+        for j in 0..15 {
+            lines.push(format!("    let value_{} = compute({}, \"{}\");", j, i, j));
+        }
+        lines.push("```".to_string());
+        lines.push(String::new());
+    }
+    lines
+}
+
+fn bench_render(c: &mut Criterion) {
+    let renderer = MarkdownRenderer::new();
+    let lines = large_block();
+    let mut edited = lines.clone();
+    let middle = edited.len() / 2;
+    edited[middle].push_str(" // edited");
+
+    c.bench_function("full render", |b| {
+        b.iter(|| {
+            renderer
+                .render_markdown(black_box(lines.join("\n")), WIDTH)
+                .unwrap()
+        })
+    });
+
+    c.bench_function("incremental render after one-line edit", |b| {
+        b.iter_batched(
+            || {
+                let mut memo = BlockRenderMemo::default();
+                renderer
+                    .render_incremental(&mut memo, &lines, WIDTH, usize::MAX)
+                    .unwrap();
+                memo
+            },
+            |mut memo| {
+                renderer
+                    .render_incremental(&mut memo, black_box(&edited), WIDTH, usize::MAX)
+                    .unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    c.bench_function("render first screen of a cold block", |b| {
+        b.iter(|| {
+            let mut memo = BlockRenderMemo::default();
+            renderer
+                .render_incremental(&mut memo, black_box(&lines), WIDTH, 60)
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_render);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+use crate::wiki_links::Backlink;
+
+pub struct BacklinksPopup {
+    /// Title of the block the links point to
+    pub target: String,
+    pub entries: Vec<Backlink>,
+    pub selected_index: usize,
+    pub visible: bool,
+}
+
+impl BacklinksPopup {
+    pub fn new() -> Self {
+        BacklinksPopup {
+            target: String::new(),
+            entries: Vec::new(),
+            selected_index: 0,
+            visible: false,
+        }
+    }
+
+    pub fn open(&mut self, target: String, entries: Vec<Backlink>) {
+        self.target = target;
+        self.entries = entries;
+        self.selected_index = 0;
+        self.visible = true;
+    }
+
+    pub fn selected_entry(&self) -> Option<&Backlink> {
+        self.entries.get(self.selected_index)
+    }
+}
+
+impl Default for BacklinksPopup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,233 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+
+/// A finished command and everything it printed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub command: Vec<String>,
+    /// Exit code, `None` when the command was ended by a signal
+    pub code: Option<i32>,
+    /// How the command ended, e.g. `exit status 0` or `killed by signal 9`
+    pub status: String,
+    pub duration: Duration,
+    /// Stdout and stderr in the order they were written
+    pub output: String,
+}
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Runs `command` with stdout and stderr captured, also copying them to the
+/// terminal as they arrive when `tee` is set. The command reads thoth's stdin.
+pub fn run_capture(command: &[String], tee: bool) -> Result<Capture> {
+    let Some((program, args)) = command.split_first() else {
+        bail!("Nothing to run. Pass the command after `--`");
+    };
+    let started = Instant::now();
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", program))?;
+
+    // Both pipes are drained on their own threads so neither can fill up and block
+    // the command, and chunks are kept in the order they came in
+    let (sender, receiver) = mpsc::channel();
+    let readers = [
+        forward_chunks(
+            child.stdout.take().expect("stdout is piped"),
+            Stream::Stdout,
+            sender.clone(),
+        ),
+        forward_chunks(
+            child.stderr.take().expect("stderr is piped"),
+            Stream::Stderr,
+            sender,
+        ),
+    ];
+    let mut output = Vec::new();
+    for (stream, chunk) in receiver {
+        if tee {
+            // The capture matters more than the live copy, so write errors are ignored
+            let _ = match stream {
+                Stream::Stdout => io::stdout().write_all(&chunk),
+                Stream::Stderr => io::stderr().write_all(&chunk),
+            };
+        }
+        output.extend_from_slice(&chunk);
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to wait for '{}'", program))?;
+
+    Ok(Capture {
+        command: command.to_vec(),
+        code: status.code(),
+        status: describe_status(status),
+        duration: started.elapsed(),
+        output: String::from_utf8_lossy(&output).into_owned(),
+    })
+}
+
+fn forward_chunks<R: Read + Send + 'static>(
+    mut reader: R,
+    stream: Stream,
+    sender: mpsc::Sender<(Stream, Vec<u8>)>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buffer = [0; 8192];
+        while let Ok(read @ 1..) = reader.read(&mut buffer) {
+            if sender.send((stream, buffer[..read].to_vec())).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+fn describe_status(status: ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exit status {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("killed by signal {}", signal);
+        }
+    }
+    status.to_string()
+}
+
+impl Capture {
+    /// The capture as a fenced `console` section: the command line, its output and
+    /// a last line with how it ended and how long it took.
+    pub fn to_markdown(&self) -> String {
+        let output = self.output.trim_end();
+        let fence = "`".repeat(longest_backtick_run(output).max(2) + 1);
+        let mut markdown = format!("{}console\n$ {}\n", fence, shell_words(&self.command));
+        if !output.is_empty() {
+            markdown.push_str(output);
+            markdown.push('\n');
+        }
+        markdown.push_str(&format!(
+            "[{} after {}]\n{}",
+            self.status,
+            format_duration(self.duration),
+            fence
+        ));
+        markdown
+    }
+}
+
+/// Longest run of backticks starting a line, so the fence can be made longer.
+fn longest_backtick_run(text: &str) -> usize {
+    text.lines()
+        .map(|line| line.trim_start().chars().take_while(|&c| c == '`').count())
+        .max()
+        .unwrap_or(0)
+}
+
+/// `command` as it would be typed in a shell, quoting words that need it.
+fn shell_words(command: &[String]) -> String {
+    command
+        .iter()
+        .map(|word| {
+            let plain = !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+            if plain {
+                word.clone()
+            } else {
+                format!("'{}'", word.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 60.0 {
+        format!("{:.2}s", secs)
+    } else {
+        format!("{}m {}s", duration.as_secs() / 60, duration.as_secs() % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notebook::{blocks_to_markdown, parse_blocks, Block};
+
+    fn words(command: &[&str]) -> Vec<String> {
+        command.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn test_markdown_section() {
+        let capture = Capture {
+            command: words(&["grep", "-r", "two words", "it's"]),
+            code: Some(1),
+            status: "exit status 1".to_string(),
+            duration: Duration::from_millis(1250),
+            output: "```\nfenced\n```\n\n".to_string(),
+        };
+        assert_eq!(
+            capture.to_markdown(),
+            "````console\n$ grep -r 'two words' 'it'\\''s'\n```\nfenced\n```\n[exit status 1 after 1.25s]\n````"
+        );
+
+        let quiet = Capture {
+            output: String::new(),
+            duration: Duration::from_secs(125),
+            ..capture
+        };
+        assert!(quiet
+            .to_markdown()
+            .ends_with("'\\''s'\n[exit status 1 after 2m 5s]\n```"));
+    }
+
+    #[test]
+    fn test_fenced_output_survives_the_notes_file() {
+        let capture = Capture {
+            command: words(&["cat", "notes.md"]),
+            code: Some(0),
+            status: "exit status 0".to_string(),
+            duration: Duration::from_millis(10),
+            output: "# Todo\n```sh\n# comment\n```\n# Done\n".to_string(),
+        };
+        let block = Block::new("log", format!("{}\n# after", capture.to_markdown()));
+        let text = blocks_to_markdown(std::slice::from_ref(&block));
+        assert_eq!(parse_blocks(&text), [block]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_runs_and_captures_both_streams() {
+        let capture = run_capture(
+            &words(&["sh", "-c", "echo out; sleep 0.1; echo err >&2; exit 3"]),
+            false,
+        )
+        .unwrap();
+        assert_eq!(capture.output, "out\nerr\n");
+        assert_eq!(capture.code, Some(3));
+        assert_eq!(capture.status, "exit status 3");
+
+        assert!(run_capture(&words(&["thoth-no-such-command"]), false).is_err());
+        assert!(run_capture(&[], false).is_err());
+    }
+}
@@ -1,15 +1,20 @@
-use crate::EditorClipboard;
-use anyhow::{bail, Result};
-use std::{
-    fs::File,
-    io::{BufRead, BufReader, Write},
-};
+use crate::capture::run_capture;
+use crate::clip_history::{entry_preview, ClipHistory};
+use crate::config::ThothConfig;
+use crate::control::{send_request, ControlRequest, ControlResponse, SharedNotes};
+use crate::json_tools::{query_json, JsonOperation};
+use crate::server::{ServeConfig, Server};
+use crate::task_list::collect_tasks;
+use crate::{Block, EditorClipboard, FormatterRegistry, Notebook, NotebookError};
+use anyhow::{anyhow, bail, Result};
 
 use std::env;
+use std::net::{IpAddr, SocketAddr};
 
 use clap::{Parser, Subcommand};
 
-use crate::get_save_file_path;
+use crate::syntaxes::{load_syntax_set_from, recognized_syntaxes};
+use crate::{get_cache_dir, get_save_file_path, get_syntaxes_dir};
 #[derive(Parser)]
 #[command(author = env!("CARGO_PKG_AUTHORS"), version = env!("CARGO_PKG_VERSION"), about, long_about = None)]
 pub struct Cli {
@@ -26,6 +31,13 @@ pub enum Commands {
         /// Contents to be associated with the named block
         content: Option<String>,
     },
+    /// Add to the end of a block, from the arguments or STDIN
+    Append {
+        /// Name of the block to be added to
+        name: String,
+        /// Contents to be added to the end of the block
+        content: Option<String>,
+    },
     /// List all of the blocks within your thoth scratchpad
     List,
     /// Delete a block by name
@@ -43,170 +55,615 @@ pub enum Commands {
         /// The name of the block to be used
         name: String,
     },
+    /// Create a block from the system clipboard, or append to one with --append
+    Paste {
+        /// The name of the block to be created or appended to
+        name: String,
+        /// Add to the end of an existing block instead of creating one
+        #[arg(short, long)]
+        append: bool,
+        /// Format the contents first, e.g. json or markdown
+        #[arg(short, long)]
+        format: Option<String>,
+    },
+    /// Run a command and keep its command line, exit status, duration and output in a block
+    #[command(alias = "run")]
+    Capture {
+        /// The name of the block to be created or appended to
+        name: String,
+        /// Add to the end of an existing block instead of creating one
+        #[arg(short, long)]
+        append: bool,
+        /// Also show the output in the terminal while the command runs
+        #[arg(short, long)]
+        tee: bool,
+        /// The command and its arguments, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// List all open task items (`- [ ]`) across blocks
+    Todo,
+    /// Format a block in place, detecting its format unless one is given
+    Fmt {
+        /// The name of the block to be formatted
+        name: String,
+        /// Formatter to use, e.g. json, yaml, sql or a command from the config
+        #[arg(short, long)]
+        format: Option<String>,
+    },
+    /// Transform or query a JSON block
+    Json {
+        #[command(subcommand)]
+        command: JsonCommands,
+    },
+    /// Browse the history of everything copied from thoth
+    Clip {
+        #[command(subcommand)]
+        command: ClipCommands,
+    },
+    /// Serve the blocks over a JSON API on localhost for editors and scripts
+    Serve {
+        /// Address to listen on. Anything but loopback requires a token
+        #[arg(long, default_value = "127.0.0.1")]
+        host: IpAddr,
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 7870)]
+        port: u16,
+        /// Require `Authorization: Bearer <token>`, also read from THOTH_SERVE_TOKEN
+        #[arg(short, long)]
+        token: Option<String>,
+    },
+    /// Inspect the syntax definitions used for code block highlighting
+    Syntaxes {
+        #[command(subcommand)]
+        command: SyntaxesCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SyntaxesCommands {
+    /// List every syntax and the code fence tokens that select it
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum ClipCommands {
+    /// List the clipboard history, most recent first
+    List,
+    /// Print (STDOUT) an entry of the clipboard history
+    Paste {
+        /// Position in `thoth clip list`, 0 being the most recent copy
+        #[arg(default_value_t = 0)]
+        index: usize,
+    },
+    /// Forget every entry of the clipboard history
+    Clear,
+}
+
+#[derive(Subcommand)]
+pub enum JsonCommands {
+    /// Pretty print a JSON block in place
+    Pretty {
+        /// The name of the block to be used
+        name: String,
+    },
+    /// Minify a JSON block in place
+    Minify {
+        /// The name of the block to be used
+        name: String,
+    },
+    /// Sort the keys of every object in a JSON block
+    SortKeys {
+        /// The name of the block to be used
+        name: String,
+    },
+    /// Convert a JSON block to YAML
+    ToYaml {
+        /// The name of the block to be used
+        name: String,
+    },
+    /// Convert a YAML block to JSON
+    FromYaml {
+        /// The name of the block to be used
+        name: String,
+    },
+    /// Convert a JSON block to TOML
+    ToToml {
+        /// The name of the block to be used
+        name: String,
+    },
+    /// Convert a TOML block to JSON
+    FromToml {
+        /// The name of the block to be used
+        name: String,
+    },
+    /// Replace a block with its contents quoted as a JSON string
+    Escape {
+        /// The name of the block to be used
+        name: String,
+    },
+    /// Decode a block holding a JSON string
+    Unescape {
+        /// The name of the block to be used
+        name: String,
+    },
+    /// Print (STDOUT) the result of a jq-like path query such as `.items[].name`
+    Query {
+        /// The name of the block to be used
+        name: String,
+        /// Path to select
+        path: String,
+    },
 }
 
 pub fn add_block(name: &str, content: &str) -> Result<()> {
-    let mut file = std::fs::OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(get_save_file_path())?;
+    let request = ControlRequest::Add {
+        name: name.to_string(),
+        content: content.to_string(),
+    };
+    if send_to_running_tui(&request)? {
+        return Ok(());
+    }
 
-    writeln!(file, "# {}", name)?;
-    writeln!(file, "{}", content)?;
-    writeln!(file)?;
+    let mut notebook = Notebook::open(get_save_file_path())?;
+    match notebook.insert(Block::new(name, content.trim_end())) {
+        Err(NotebookError::DuplicateTitle(_)) => bail!(
+            "Block '{}' already exists. Use `thoth append {}` to add to it",
+            name,
+            name
+        ),
+        result => result?,
+    };
+    notebook.save()?;
 
     println!("Block '{}' added successfully.", name);
     Ok(())
 }
 
-pub fn list_blocks() -> Result<()> {
-    let file = File::open(get_save_file_path())?;
-    let reader = BufReader::new(file);
+pub fn append_block(name: &str, content: &str) -> Result<()> {
+    let request = ControlRequest::Append {
+        name: name.to_string(),
+        content: content.to_string(),
+    };
+    if send_to_running_tui(&request)? {
+        return Ok(());
+    }
 
-    for line in reader.lines() {
-        let line = line?;
+    let mut notebook = Notebook::open(get_save_file_path())?;
+    let lines = block_lines(find_block(&notebook, name)?);
+    notebook.update(name, appended_lines(&lines, content).join("\n"))?;
+    notebook.save()?;
+    println!(
+        "Appended {} lines to block '{}'.",
+        content.lines().count(),
+        name
+    );
+    Ok(())
+}
 
-        if let Some(strip) = line.strip_prefix("# ") {
-            println!("{}", strip);
+pub fn list_blocks() -> Result<()> {
+    for block in Notebook::open(get_save_file_path())?.blocks() {
+        println!("{}", block.title);
+    }
+    Ok(())
+}
+
+pub fn list_todos() -> Result<()> {
+    let mut found = false;
+    for block in Notebook::open(get_save_file_path())?.blocks() {
+        let open_tasks: Vec<_> = collect_tasks(&block_lines(block))
+            .into_iter()
+            .filter(|task| !task.checked)
+            .collect();
+        if open_tasks.is_empty() {
+            continue;
+        }
+        found = true;
+        println!("{}", block.title);
+        for task in open_tasks {
+            println!("  - [ ] {}", task.text);
         }
     }
 
+    if !found {
+        println!("No open tasks found.");
+    }
     Ok(())
 }
 
-pub fn view_block(name: &str) -> Result<()> {
-    let file = File::open(get_save_file_path())?;
-    let reader = BufReader::new(file);
-    let mut blocks = Vec::new();
-    let mut current_block = Vec::new();
-    let mut current_name = String::new();
-
-    for line in reader.lines() {
-        let line = line?;
-        if let Some(strip) = line.strip_prefix("# ") {
-            if !current_name.is_empty() {
-                blocks.push((current_name, current_block));
-                current_block = Vec::new();
+pub fn clip_history(command: &ClipCommands) -> Result<()> {
+    let mut history = ClipHistory::load()?;
+    match command {
+        ClipCommands::List => {
+            if history.entries().is_empty() {
+                println!("The clipboard history is empty.");
             }
-            current_name = strip.to_string();
-        } else {
-            current_block.push(line);
+            for (index, entry) in history.entries().iter().enumerate() {
+                println!("{:>3}: {}", index, entry_preview(entry, 72));
+            }
+        }
+        ClipCommands::Paste { index } => match history.get(*index) {
+            Some(entry) => println!("{}", entry),
+            None => bail!(
+                "No clipboard history entry {}. Use `thoth clip list` to see them",
+                index
+            ),
+        },
+        ClipCommands::Clear => {
+            history.clear();
+            history.save()?;
+            println!("Cleared the clipboard history.");
         }
     }
+    Ok(())
+}
 
-    if !current_name.is_empty() {
-        blocks.push((current_name, current_block));
+pub fn paste_block(name: &str, append: bool, format: Option<&str>) -> Result<()> {
+    let mut clipboard =
+        EditorClipboard::new().map_err(|e| anyhow!("Failed to create clipboard context: {}", e))?;
+    let content = clipboard
+        .get_content()
+        .map_err(|e| anyhow!("Failed to read the clipboard: {}", e))?;
+    if content.trim().is_empty() {
+        bail!(
+            "Couldn't paste into '{}' because the clipboard is empty",
+            name
+        );
     }
 
-    for (block_name, block_content) in blocks {
-        if block_name == name {
-            for line in block_content {
-                println!("{}", line);
+    let formatters = FormatterRegistry::from_config(&ThothConfig::load()?)?;
+    let (content, language) = clipboard_block(&content, &formatters, format)?;
+
+    let mut notes = SharedNotes::open(&get_save_file_path())?;
+    match notes.notebook.get(name) {
+        Some(block) if append => {
+            let mut lines = appended_lines(&block_lines(block), "");
+            if !lines.is_empty() {
+                lines.push(String::new());
             }
+            lines.extend(content.lines().map(str::to_string));
+            let content = lines.join("\n");
+            notes.notebook.update(name, content.as_str())?;
+            notes.commit(|version| update_content(version, name, content))?;
+        }
+        Some(_) => bail!(
+            "Block '{}' already exists. Use `thoth paste {} --append` to add to it",
+            name,
+            name
+        ),
+        None if append => bail!(
+            "Block '{}' not found. You can use `thoth list` to find the name of all blocks",
+            name
+        ),
+        None => {
+            let block = notes
+                .notebook
+                .insert(Block::new(name, content.as_str()))?
+                .clone();
+            notes.commit(|version| ControlRequest::Insert { version, block })?;
         }
     }
+
+    let detected = language
+        .map(|language| format!(" as {}", language))
+        .unwrap_or_default();
+    let action = if append { "Appended" } else { "Pasted" };
+    println!(
+        "{} {} lines from the clipboard to block '{}'{}.",
+        action,
+        content.lines().count(),
+        name,
+        detected
+    );
     Ok(())
 }
 
-pub fn copy_block(name: &str) -> Result<()> {
-    let file = File::open(get_save_file_path())?;
-    let reader = BufReader::new(file);
-    let mut blocks = Vec::new();
-    let mut current_block = Vec::new();
-    let mut current_name = String::new();
-    let mut matched_name: Option<String> = None;
-
-    for line in reader.lines() {
-        let line = line?;
-        if let Some(strip) = line.strip_prefix("# ") {
-            if !current_name.is_empty() {
-                blocks.push((current_name, current_block));
-                current_block = Vec::new();
-            }
-            current_name = strip.to_string();
-        } else {
-            current_block.push(line);
-        }
+/// Runs `command` and keeps it with its output in block `name`, returning the
+/// command's exit code.
+pub fn capture_block(name: &str, append: bool, tee: bool, command: &[String]) -> Result<i32> {
+    // Checked first so the command does not run only for its output to be thrown away
+    match (block_exists(name)?, append) {
+        (true, false) => bail!(
+            "Block '{}' already exists. Use `thoth capture {} --append` to add to it",
+            name,
+            name
+        ),
+        (false, true) => bail!(
+            "Block '{}' not found. You can use `thoth list` to find the name of all blocks",
+            name
+        ),
+        _ => {}
     }
 
-    if !current_name.is_empty() {
-        blocks.push((current_name, current_block));
+    let capture = run_capture(command, tee)?;
+    let section = capture.to_markdown();
+    if append {
+        append_block(name, &format!("\n{}", section))?;
+    } else {
+        add_block(name, &section)?;
     }
+    Ok(capture.code.unwrap_or(1))
+}
 
-    for (block_name, block_content) in blocks {
-        if block_name == name {
-            let result_ctx = EditorClipboard::new();
+/// Whether block `name` exists, asking the running TUI first as it may not be saved yet.
+fn block_exists(name: &str) -> Result<bool> {
+    let request = ControlRequest::View {
+        name: name.to_string(),
+    };
+    match send_request(&get_save_file_path(), &request)? {
+        Some(ControlResponse::Ok(_)) => Ok(true),
+        Some(_) => Ok(false),
+        None => Ok(Notebook::open(get_save_file_path())?.get(name).is_some()),
+    }
+}
 
-            if result_ctx.is_err() {
-                bail!("Failed to create clipboard context for copy block");
-            }
+/// Block contents for pasted `content`, formatted with `format` when given, and its
+/// language. Code is fenced with its language so it gets highlighted, except for
+/// JSON, which whole blocks are already shown and transformed as.
+fn clipboard_block(
+    content: &str,
+    formatters: &FormatterRegistry,
+    format: Option<&str>,
+) -> Result<(String, Option<String>)> {
+    let content = content.trim_end();
+    let (content, formatter) = match format {
+        Some(format) => {
+            let formatter = formatters.get(format).ok_or_else(|| {
+                anyhow!(
+                    "Unknown format '{}'. Available formats: {}",
+                    format,
+                    formatters.names().join(", ")
+                )
+            })?;
+            (formatter.format(content)?, Some(formatter))
+        }
+        None => (content.to_string(), formatters.detect(content)),
+    };
+    let language = formatter.map(|f| f.name().to_string());
+    let content = match language.as_deref() {
+        None | Some("json") | Some("markdown") => content,
+        Some(language) => format!("```{}\n{}\n```", language, content.trim_end()),
+    };
+    Ok((content, language))
+}
 
-            let mut ctx = result_ctx.unwrap();
+pub fn format_block(name: &str, format: Option<&str>) -> Result<()> {
+    let mut notes = SharedNotes::open(&get_save_file_path())?;
+    let content = find_block(&notes.notebook, name)?.content.clone();
 
-            let is_success = ctx.set_contents(block_content.join("\n"));
+    let formatters = FormatterRegistry::from_config(&ThothConfig::load()?)?;
+    let formatter = match format {
+        Some(format) => match formatters.get(format) {
+            Some(formatter) => formatter,
+            None => bail!(
+                "Unknown format '{}'. Available formats: {}",
+                format,
+                formatters.names().join(", ")
+            ),
+        },
+        None => match formatters.detect(&content) {
+            Some(formatter) => formatter,
+            None => bail!(
+                "Couldn't detect the format of block '{}'. Pass one with --format",
+                name
+            ),
+        },
+    };
 
-            if is_success.is_err() {
-                bail!(format!(
-                    "Failed to copy contents of block {} to system clipboard",
-                    block_name
-                ));
-            }
-            matched_name = Some(block_name);
-            break;
+    let formatted = formatter.format(content.trim_end())?.trim_end().to_string();
+    notes.notebook.update(name, formatted.as_str())?;
+    notes.commit(|version| update_content(version, name, formatted))?;
+    println!("Block '{}' formatted as {}.", name, formatter.name());
+    Ok(())
+}
+
+pub fn json_block(command: &JsonCommands) -> Result<()> {
+    let (name, operation) = match command {
+        JsonCommands::Pretty { name } => (name, JsonOperation::Pretty),
+        JsonCommands::Minify { name } => (name, JsonOperation::Minify),
+        JsonCommands::SortKeys { name } => (name, JsonOperation::SortKeys),
+        JsonCommands::ToYaml { name } => (name, JsonOperation::ToYaml),
+        JsonCommands::FromYaml { name } => (name, JsonOperation::FromYaml),
+        JsonCommands::ToToml { name } => (name, JsonOperation::ToToml),
+        JsonCommands::FromToml { name } => (name, JsonOperation::FromToml),
+        JsonCommands::Escape { name } => (name, JsonOperation::Escape),
+        JsonCommands::Unescape { name } => (name, JsonOperation::Unescape),
+        JsonCommands::Query { name, path } => {
+            let notes = SharedNotes::open(&get_save_file_path())?;
+            println!(
+                "{}",
+                query_json(&find_block(&notes.notebook, name)?.content, path)?
+            );
+            return Ok(());
         }
-    }
-    match matched_name {
-        Some(name) => println!("Successfully copied contents from block {}", name),
-        None => println!("Didn't find the block. Please try again. You can use `thoth list` to find the name of all blocks")
     };
 
+    let mut notes = SharedNotes::open(&get_save_file_path())?;
+    let content = find_block(&notes.notebook, name)?.content.clone();
+    let output = operation.apply(content.trim_end())?.trim_end().to_string();
+    notes.notebook.update(name, output.as_str())?;
+    notes.commit(|version| update_content(version, name, output))?;
+    println!("Ran '{}' on block '{}'.", operation.label(), name);
     Ok(())
 }
 
-pub fn delete_block(name: &str) -> Result<()> {
-    let file = File::open(get_save_file_path())?;
-    let reader = BufReader::new(file);
-    let mut blocks = Vec::new();
-    let mut current_block = Vec::new();
-    let mut current_name = String::new();
-
-    for line in reader.lines() {
-        let line = line?;
-        if let Some(strip) = line.strip_prefix("# ") {
-            if !current_name.is_empty() {
-                blocks.push((current_name, current_block));
-                current_block = Vec::new();
-            }
-            current_name = strip.to_string();
-        } else {
-            current_block.push(line);
-        }
-    }
+fn find_block<'a>(notebook: &'a Notebook, name: &str) -> Result<&'a Block> {
+    notebook.get(name).ok_or_else(|| {
+        anyhow!(
+            "Block '{}' not found. You can use `thoth list` to find the name of all blocks",
+            name
+        )
+    })
+}
 
-    if !current_name.is_empty() {
-        blocks.push((current_name, current_block));
+/// Has block `name` hold `content`, as seen at `version` of the running TUI.
+fn update_content(version: u64, name: &str, content: String) -> ControlRequest {
+    ControlRequest::Update {
+        version,
+        name: name.to_string(),
+        new_name: None,
+        content: Some(content),
     }
+}
 
-    let mut file = File::create(get_save_file_path())?;
-    let mut deleted = false;
+fn block_lines(block: &Block) -> Vec<String> {
+    block.content.lines().map(str::to_string).collect()
+}
 
-    for (block_name, block_content) in blocks {
-        if block_name != name {
-            writeln!(file, "# {}", block_name)?;
-            for line in block_content {
-                writeln!(file, "{}", line)?;
-            }
-            writeln!(file)?;
-        } else {
-            deleted = true;
+/// `lines` of a block with `content` added after its last non-blank line.
+pub(crate) fn appended_lines(lines: &[String], content: &str) -> Vec<String> {
+    let mut lines = lines.to_vec();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    lines.extend(content.lines().map(str::to_string));
+    lines
+}
+
+/// Has the TUI open on the notes file carry out `request` and prints its answer.
+/// Returns false when no TUI is running.
+fn send_to_running_tui(request: &ControlRequest) -> Result<bool> {
+    match send_request(&get_save_file_path(), request)? {
+        Some(ControlResponse::Ok(output)) => {
+            println!("{}", output);
+            Ok(true)
+        }
+        Some(ControlResponse::Error(message)) => bail!(message),
+        Some(ControlResponse::Blocks { .. }) => {
+            bail!("The running thoth sent an unexpected answer")
         }
+        None => Ok(false),
     }
+}
 
-    if deleted {
-        println!("Block '{}' deleted successfully.", name);
+pub fn serve(host: IpAddr, port: u16, token: Option<&str>) -> Result<()> {
+    let token = token
+        .map(str::to_string)
+        .or_else(|| env::var("THOTH_SERVE_TOKEN").ok())
+        .filter(|token| !token.is_empty());
+    let server = Server::bind(ServeConfig {
+        host,
+        port,
+        token: token.clone(),
+        notes_path: get_save_file_path(),
+    })?;
+    let auth = if token.is_some() {
+        " (token required)"
     } else {
-        println!("Block '{}' not found.", name);
+        ""
+    };
+    println!(
+        "Serving {} on http://{}{}",
+        get_save_file_path().display(),
+        SocketAddr::new(host, server.port()),
+        auth
+    );
+    server.run();
+    Ok(())
+}
+
+pub fn list_syntaxes() -> Result<()> {
+    let syntaxes_dir = get_syntaxes_dir();
+    let syntax_set = load_syntax_set_from(&syntaxes_dir, &get_cache_dir())?;
+    for (name, tokens) in recognized_syntaxes(&syntax_set) {
+        let mut all_tokens = vec![name.to_lowercase()];
+        all_tokens.extend(tokens);
+        all_tokens.sort_unstable();
+        all_tokens.dedup();
+        println!("{}: {}", name, all_tokens.join(", "));
+    }
+    println!(
+        "\nAdd .sublime-syntax files to {} for more languages.",
+        syntaxes_dir.display()
+    );
+    Ok(())
+}
+
+pub fn view_block(name: &str) -> Result<()> {
+    // The open TUI may have edits that are not saved yet
+    let request = ControlRequest::View {
+        name: name.to_string(),
+    };
+    if send_to_running_tui(&request)? {
+        return Ok(());
     }
 
+    let notebook = Notebook::open(get_save_file_path())?;
+    let block = find_block(&notebook, name)?;
+    if !block.content.is_empty() {
+        println!("{}", block.content);
+    }
+    Ok(())
+}
+
+pub fn copy_block(name: &str) -> Result<()> {
+    let notebook = Notebook::open(get_save_file_path())?;
+    let block = find_block(&notebook, name)?;
+    let mut ctx = EditorClipboard::new()
+        .map_err(|e| anyhow!("Failed to create clipboard context for copy block: {}", e))?;
+    ctx.set_contents(block.content.clone()).map_err(|e| {
+        anyhow!(
+            "Failed to copy contents of block {} to system clipboard: {:#}",
+            block.title,
+            e
+        )
+    })?;
+    println!("Successfully copied contents from block {}", block.title);
     Ok(())
 }
+
+pub fn delete_block(name: &str) -> Result<()> {
+    let mut notes = SharedNotes::open(&get_save_file_path())?;
+    notes.notebook.remove(name)?;
+    notes.commit(|version| ControlRequest::Delete {
+        version,
+        name: name.to_string(),
+    })?;
+    println!("Block '{}' deleted successfully.", name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{block_content, block_to_textarea};
+
+    #[test]
+    fn test_run_is_capture() {
+        let cli = Cli::try_parse_from(["thoth", "run", "log", "--", "ls", "-l"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Capture { name, command, .. }) if name == "log" && command == ["ls", "-l"]
+        ));
+    }
+
+    #[test]
+    fn test_clipboard_block_detects_and_formats() {
+        let formatters = FormatterRegistry::with_builtins();
+
+        let (block, language) = clipboard_block("SELECT 1;\n", &formatters, None).unwrap();
+        assert_eq!(block, "```sql\nSELECT 1;\n```");
+        assert_eq!(language.as_deref(), Some("sql"));
+
+        let (block, language) = clipboard_block("{\"a\":1}", &formatters, Some("json")).unwrap();
+        assert_eq!(block, "{\n  \"a\": 1\n}");
+        assert_eq!(language.as_deref(), Some("json"));
+
+        let (block, language) = clipboard_block("just text", &formatters, None).unwrap();
+        assert_eq!(block, "just text");
+        assert_eq!(language, None);
+
+        assert!(clipboard_block("x", &formatters, Some("cobol")).is_err());
+    }
+
+    #[test]
+    fn test_appended_lines_and_block_content() {
+        let lines: Vec<String> = ["log", "- first", "", "  "].map(String::from).to_vec();
+        let appended = appended_lines(&lines, "- second\n- third\n");
+        assert_eq!(appended, ["log", "- first", "- second", "- third"]);
+
+        let textarea = block_to_textarea(&Block::new("log", appended.join("\n")));
+        assert_eq!(textarea.lines().last().map(String::as_str), Some(""));
+        assert_eq!(block_content(textarea.lines()), appended.join("\n"));
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::ThothConfig;
+use crate::get_clip_history_file_path;
+
+pub const DEFAULT_MAX_ENTRIES: usize = 50;
+
+/// `[clipboard_history]` settings of the config file.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ClipHistoryConfig {
+    /// Oldest entries are dropped beyond this many, 0 turns the history off
+    pub max_entries: usize,
+    /// Also record what is pasted from the clipboard with Ctrl+V
+    pub record_pastes: bool,
+}
+
+impl Default for ClipHistoryConfig {
+    fn default() -> Self {
+        ClipHistoryConfig {
+            max_entries: DEFAULT_MAX_ENTRIES,
+            record_pastes: false,
+        }
+    }
+}
+
+/// Everything copied from thoth, most recent first, kept in a JSON file between runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipHistory {
+    path: PathBuf,
+    entries: Vec<String>,
+    max_entries: usize,
+}
+
+impl ClipHistory {
+    /// Loads the history file with the size limit from the config file.
+    pub fn load() -> Result<Self> {
+        let config = ThothConfig::load()?.clipboard_history;
+        Self::load_from(get_clip_history_file_path(), config.max_entries)
+    }
+
+    pub fn load_from(path: impl AsRef<Path>, max_entries: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        Ok(ClipHistory {
+            path,
+            entries,
+            max_entries,
+        })
+    }
+
+    /// Picks up entries written by other thoth processes since it was loaded.
+    pub fn reload(&mut self) -> Result<()> {
+        *self = Self::load_from(&self.path, self.max_entries)?;
+        Ok(())
+    }
+
+    /// Replaces the history file in one step, as the TUI and the CLI both rewrite it.
+    /// Copies can hold passwords and tokens, so only the owner can read it.
+    pub fn save(&self) -> Result<()> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(dir)?;
+        // Created with mode 0600 on Unix
+        let mut file = tempfile::NamedTempFile::new_in(dir)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        file.write_all(serde_json::to_string_pretty(&self.entries)?.as_bytes())
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        file.persist(&self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Records `text` as the most recent entry, moving it up when it is already there.
+    pub fn push(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.entries.retain(|entry| *entry != text);
+        self.entries.insert(0, text);
+        self.entries.truncate(self.max_entries);
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Entry `index`, counting from 0 for the most recent one.
+    pub fn get(&self, index: usize) -> Option<&String> {
+        self.entries.get(index)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// First line of `entry` for lists, with a count of the lines left out.
+pub fn entry_preview(entry: &str, max_chars: usize) -> String {
+    let mut lines = entry.lines();
+    let first = lines.next().unwrap_or("").trim();
+    let mut preview: String = first.chars().take(max_chars).collect();
+    if first.chars().count() > max_chars {
+        preview.push('…');
+    }
+    let rest = lines.count();
+    if rest > 0 {
+        preview.push_str(&format!(" (+{} lines)", rest));
+    }
+    preview
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_dedupes_and_truncates() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = ClipHistory::load_from(dir.path().join("history.json"), 3).unwrap();
+        for text in ["a", "b", "c", "a", "", "d"] {
+            history.push(text.to_string());
+        }
+        assert_eq!(history.entries(), ["d", "a", "c"]);
+        assert_eq!(history.get(1).map(String::as_str), Some("a"));
+        assert_eq!(history.get(3), None);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("history.json");
+        let mut history = ClipHistory::load_from(&path, 10).unwrap();
+        history.push("line 1\nline 2".to_string());
+        history.save().unwrap();
+
+        let loaded = ClipHistory::load_from(&path, 10).unwrap();
+        assert_eq!(loaded.entries(), ["line 1\nline 2"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_saved_history_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.json");
+        // Written by an older thoth
+        fs::write(&path, "[]").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut history = ClipHistory::load_from(&path, 10).unwrap();
+        history.push("secret".to_string());
+        history.save().unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_entry_preview() {
+        assert_eq!(entry_preview("  hello  ", 10), "hello");
+        assert_eq!(entry_preview("abcdef\nx\ny", 3), "abc… (+2 lines)");
+        assert_eq!(entry_preview("", 3), "");
+    }
+}
@@ -0,0 +1,32 @@
+pub struct ClipHistoryPopup {
+    /// Clipboard history entries, most recent first
+    pub entries: Vec<String>,
+    pub selected_index: usize,
+    pub visible: bool,
+}
+
+impl ClipHistoryPopup {
+    pub fn new() -> Self {
+        ClipHistoryPopup {
+            entries: Vec::new(),
+            selected_index: 0,
+            visible: false,
+        }
+    }
+
+    pub fn open(&mut self, entries: Vec<String>) {
+        self.entries = entries;
+        self.selected_index = 0;
+        self.visible = true;
+    }
+
+    pub fn selected_entry(&self) -> Option<&String> {
+        self.entries.get(self.selected_index)
+    }
+}
+
+impl Default for ClipHistoryPopup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,50 +1,289 @@
-use anyhow::Result;
-use arboard::{Clipboard, Error};
+use anyhow::{anyhow, bail, Context, Result};
+use arboard::Clipboard;
+use serde::Deserialize;
+use std::cell::RefCell;
 use std::env;
-use std::process;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 #[cfg(target_os = "linux")]
-use arboard::SetExtLinux;
+use arboard::{GetExtLinux, LinuxClipboardKind, SetExtLinux};
+#[cfg(target_os = "linux")]
+use std::io::BufReader;
+#[cfg(target_os = "linux")]
+use std::process::{ChildStdin, ChildStdout};
 
+use crate::clip_history::ClipHistory;
+use crate::config::ThothConfig;
+use crate::get_clip_history_file_path;
+use crate::osc52::{copy_osc52, has_display};
 use crate::DAEMONIZE_ARG;
 
-pub struct EditorClipboard {
+/// Which clipboard thoth copies to, set with `clipboard = "..."` in the config.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardMode {
+    /// Picked from the platform, the display server and the tools installed
+    #[default]
+    Auto,
+    /// The system clipboard through arboard
+    System,
+    /// Escape sequences the terminal turns into a copy, which also work over SSH
+    Osc52,
+    WlClipboard,
+    Xclip,
+    Xsel,
+    Pbcopy,
+    /// tmux paste buffers
+    Tmux,
+}
+
+/// A place text can be copied to and pasted from.
+pub trait ClipboardProvider {
+    fn name(&self) -> &str;
+    fn set_contents(&mut self, content: String) -> Result<()>;
+    fn get_contents(&mut self) -> Result<String>;
+
+    /// Copies to the X11/Wayland primary selection, the one middle-click pastes.
+    fn set_primary(&mut self, _content: String) -> Result<()> {
+        bail!("The {} clipboard has no primary selection", self.name())
+    }
+
+    fn get_primary(&mut self) -> Result<String> {
+        bail!("The {} clipboard has no primary selection", self.name())
+    }
+}
+
+/// The system clipboard through arboard.
+pub struct ArboardProvider {
     clipboard: Arc<Mutex<Clipboard>>,
+    /// Serves the primary selection, started by the first `set_primary`
+    #[cfg(target_os = "linux")]
+    primary_daemon: Option<PrimaryDaemon>,
 }
 
-impl EditorClipboard {
-    pub fn new() -> Result<EditorClipboard, Error> {
-        Clipboard::new().map(|c| EditorClipboard {
-            clipboard: Arc::new(Mutex::new(c)),
+/// Pipes to a daemon that takes every primary selection of this thoth, one after
+/// the other, and keeps the last one after thoth exits.
+#[cfg(target_os = "linux")]
+struct PrimaryDaemon {
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl ArboardProvider {
+    pub fn new() -> Result<Self> {
+        Ok(ArboardProvider {
+            clipboard: Arc::new(Mutex::new(Clipboard::new()?)),
+            #[cfg(target_os = "linux")]
+            primary_daemon: None,
         })
     }
 
-    pub fn try_new() -> Option<EditorClipboard> {
-        Self::new().ok()
+    /// Runs the clipboard daemon when thoth was started as one by `set_contents`.
+    /// Does not return in that case.
+    #[cfg(target_os = "linux")]
+    pub fn handle_daemon_args() -> Result<()> {
+        let mut args = env::args().skip(1);
+        if args.next().as_deref() == Some(DAEMONIZE_ARG) {
+            let result = match args.next().as_deref() {
+                Some(DAEMON_PRIMARY_ARG) => run_primary_daemon(),
+                _ => run_daemon(),
+            };
+            let code = match result {
+                Ok(()) => 0,
+                Err(e) => {
+                    // The parent may already be gone after a successful copy
+                    let _ = writeln!(std::io::stdout(), "{}{:#}", DAEMON_ERROR, e);
+                    1
+                }
+            };
+            std::process::exit(code);
+        }
+        Ok(())
     }
 
-    pub fn set_contents(&mut self, content: String) -> Result<(), Error> {
-        #[cfg(target_os = "linux")]
-        {
-            if env::args().nth(1).as_deref() == Some(DAEMONIZE_ARG) {
-                let mut clipboard = self
-                    .clipboard
-                    .lock()
-                    .map_err(|_e| arboard::Error::ContentNotAvailable)?;
-                clipboard.set().wait().text(content)?;
-            } else {
-                process::Command::new(env::current_exe().unwrap())
-                    .arg(DAEMONIZE_ARG)
-                    .arg(content)
-                    .stdin(process::Stdio::null())
-                    .stdout(process::Stdio::null())
-                    .stderr(process::Stdio::null())
-                    .current_dir("/")
-                    .spawn()
-                    .map_err(|_e| arboard::Error::ContentNotAvailable)?;
+    /// Starts a detached copy of thoth that takes the contents on stdin and owns the
+    /// clipboard until another application takes it over. Waits for it to report
+    /// that the contents are on the clipboard.
+    #[cfg(target_os = "linux")]
+    fn spawn_daemon(content: &str) -> Result<()> {
+        let (mut stdin, stdout) = Self::start_daemon(&[])?;
+        // Dropping stdin after writing tells the daemon the contents are complete
+        stdin
+            .write_all(content.as_bytes())
+            .context("Failed to send the contents to the clipboard daemon")?;
+        drop(stdin);
+        read_daemon_report(BufReader::new(stdout))
+    }
+
+    /// Sends `content` to the primary selection daemon, starting it when it is not
+    /// running yet or has exited.
+    #[cfg(target_os = "linux")]
+    fn send_to_primary_daemon(&mut self, content: &str) -> Result<()> {
+        let daemon = match &mut self.primary_daemon {
+            Some(daemon) => daemon,
+            None => {
+                let (stdin, stdout) = Self::start_daemon(&[DAEMON_PRIMARY_ARG])?;
+                self.primary_daemon.insert(PrimaryDaemon {
+                    stdin,
+                    stdout: BufReader::new(stdout),
+                })
             }
+        };
+        let result = write_frame(&mut daemon.stdin, content)
+            .context("Failed to send the contents to the clipboard daemon")
+            .and_then(|()| read_daemon_report(&mut daemon.stdout));
+        if result.is_err() {
+            // Started again on the next selection
+            self.primary_daemon = None;
         }
+        result
+    }
+
+    #[cfg(target_os = "linux")]
+    fn start_daemon(args: &[&str]) -> Result<(ChildStdin, ChildStdout)> {
+        let mut child = Command::new(env::current_exe()?)
+            .arg(DAEMONIZE_ARG)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .current_dir("/")
+            .spawn()
+            .context("Failed to start the clipboard daemon")?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Clipboard daemon has no stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Clipboard daemon has no stdout"))?;
+        // Reap the daemon whenever it exits so it does not linger as a zombie
+        std::thread::spawn(move || child.wait());
+        Ok((stdin, stdout))
+    }
+}
+
+/// First line the daemon writes once the contents are on the clipboard.
+#[cfg(any(target_os = "linux", test))]
+const DAEMON_OK: &str = "ok";
+/// Prefix of the line the daemon writes when it could not copy.
+#[cfg(any(target_os = "linux", test))]
+const DAEMON_ERROR: &str = "error: ";
+/// Argument after `DAEMONIZE_ARG` that makes the daemon own the primary selection.
+#[cfg(target_os = "linux")]
+const DAEMON_PRIMARY_ARG: &str = "primary";
+
+#[cfg(target_os = "linux")]
+fn run_daemon() -> Result<()> {
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut content)?;
+    let mut clipboard = Clipboard::new()?;
+    clipboard.set().text(content.clone())?;
+
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "{}", DAEMON_OK)?;
+    stdout.flush()?;
+
+    // Keep serving the contents until another application owns the clipboard
+    clipboard.set().wait().text(content)?;
+    Ok(())
+}
+
+/// Takes each primary selection thoth sends until it exits, reporting on every
+/// one, then keeps serving the last until another application takes it over.
+#[cfg(target_os = "linux")]
+fn run_primary_daemon() -> Result<()> {
+    let mut clipboard = Clipboard::new()?;
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout();
+    let mut last = None;
+    // The contents stay on the primary selection as long as `clipboard` lives
+    while let Some(content) = read_frame(&mut stdin)? {
+        let primary = clipboard.set().clipboard(LinuxClipboardKind::Primary);
+        match primary.text(content.clone()) {
+            Ok(()) => {
+                writeln!(stdout, "{}", DAEMON_OK)?;
+                last = Some(content);
+            }
+            Err(e) => writeln!(stdout, "{}{}", DAEMON_ERROR, e)?,
+        }
+        stdout.flush()?;
+    }
+
+    let Some(content) = last else {
+        return Ok(());
+    };
+    // Only when nothing else selected text in the meantime
+    let current = clipboard
+        .get()
+        .clipboard(LinuxClipboardKind::Primary)
+        .text();
+    if current.ok().as_deref() == Some(content.as_str()) {
+        clipboard
+            .set()
+            .clipboard(LinuxClipboardKind::Primary)
+            .wait()
+            .text(content)?;
+    }
+    Ok(())
+}
+
+/// Writes `content` for a daemon that reads more than one, prefixed by its length.
+#[cfg(any(target_os = "linux", test))]
+fn write_frame(writer: &mut impl Write, content: &str) -> std::io::Result<()> {
+    writeln!(writer, "{}", content.len())?;
+    writer.write_all(content.as_bytes())?;
+    writer.flush()
+}
+
+/// The next contents written by `write_frame`, `None` once the writer is gone.
+#[cfg(any(target_os = "linux", test))]
+fn read_frame(reader: &mut impl std::io::BufRead) -> Result<Option<String>> {
+    let mut header = String::new();
+    if reader.read_line(&mut header)? == 0 {
+        return Ok(None);
+    }
+    let len = header
+        .trim_end()
+        .parse()
+        .context("Invalid length of the clipboard contents")?;
+    let mut content = vec![0; len];
+    reader.read_exact(&mut content)?;
+    Ok(Some(String::from_utf8(content)?))
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn read_daemon_report(mut reader: impl std::io::BufRead) -> Result<()> {
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .context("Failed to read the clipboard daemon status")?;
+    let line = line.trim_end();
+    if line == DAEMON_OK {
+        Ok(())
+    } else if let Some(message) = line.strip_prefix(DAEMON_ERROR) {
+        bail!("Clipboard daemon failed: {}", message)
+    } else {
+        bail!("Clipboard daemon exited without copying")
+    }
+}
+
+impl ClipboardProvider for ArboardProvider {
+    fn name(&self) -> &str {
+        "system"
+    }
+
+    fn set_contents(&mut self, content: String) -> Result<()> {
+        // X11 clipboards are owned by a process, so on Linux a detached copy of
+        // thoth keeps serving the contents after this one exits
+        #[cfg(target_os = "linux")]
+        Self::spawn_daemon(&content)?;
 
         #[cfg(not(target_os = "linux"))]
         {
@@ -55,20 +294,536 @@ impl EditorClipboard {
         Ok(())
     }
 
-    pub fn get_content(&mut self) -> Result<String, Error> {
-        let mut clipboard = self.clipboard.lock().unwrap();
-        clipboard.get_text()
+    fn get_contents(&mut self) -> Result<String> {
+        Ok(self.clipboard.lock().unwrap().get_text()?)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_primary(&mut self, content: String) -> Result<()> {
+        self.send_to_primary_daemon(&content)
     }
 
     #[cfg(target_os = "linux")]
-    pub fn handle_daemon_args() -> Result<(), Error> {
-        if let Some(content) = env::args().nth(2) {
-            if env::args().nth(1).as_deref() == Some(DAEMONIZE_ARG) {
-                let mut clipboard = Self::new()?;
-                clipboard.set_contents(content)?;
-                std::process::exit(0);
+    fn get_primary(&mut self) -> Result<String> {
+        let mut clipboard = self.clipboard.lock().unwrap();
+        Ok(clipboard
+            .get()
+            .clipboard(LinuxClipboardKind::Primary)
+            .text()?)
+    }
+}
+
+/// Copies and pastes by running external tools such as `wl-copy` or `xclip`.
+pub struct CommandProvider {
+    name: String,
+    copy: Vec<String>,
+    paste: Option<Vec<String>>,
+    /// Copy and paste commands for the primary selection
+    primary: Option<(Vec<String>, Vec<String>)>,
+}
+
+impl CommandProvider {
+    /// `copy` reads the contents on stdin, `paste` writes them to stdout.
+    pub fn new(name: &str, copy: &[&str], paste: Option<&[&str]>) -> Self {
+        let owned = |args: &[&str]| args.iter().map(|a| a.to_string()).collect();
+        CommandProvider {
+            name: name.to_string(),
+            copy: owned(copy),
+            paste: paste.map(owned),
+            primary: None,
+        }
+    }
+
+    /// Adds the commands that copy to and paste from the primary selection.
+    pub fn with_primary(mut self, copy: &[&str], paste: &[&str]) -> Self {
+        let owned = |args: &[&str]| args.iter().map(|a| a.to_string()).collect();
+        self.primary = Some((owned(copy), owned(paste)));
+        self
+    }
+
+    pub fn wl_clipboard() -> Self {
+        Self::new(
+            "wl-clipboard",
+            &["wl-copy"],
+            Some(&["wl-paste", "--no-newline"]),
+        )
+        .with_primary(
+            &["wl-copy", "--primary"],
+            &["wl-paste", "--primary", "--no-newline"],
+        )
+    }
+
+    pub fn xclip() -> Self {
+        Self::new(
+            "xclip",
+            &["xclip", "-selection", "clipboard"],
+            Some(&["xclip", "-selection", "clipboard", "-o"]),
+        )
+        .with_primary(
+            &["xclip", "-selection", "primary"],
+            &["xclip", "-selection", "primary", "-o"],
+        )
+    }
+
+    pub fn xsel() -> Self {
+        Self::new(
+            "xsel",
+            &["xsel", "--clipboard", "--input"],
+            Some(&["xsel", "--clipboard", "--output"]),
+        )
+        .with_primary(
+            &["xsel", "--primary", "--input"],
+            &["xsel", "--primary", "--output"],
+        )
+    }
+
+    pub fn pbcopy() -> Self {
+        Self::new("pbcopy", &["pbcopy"], Some(&["pbpaste"]))
+    }
+
+    /// `-w` also forwards the buffer to the outer terminal's clipboard when tmux allows it.
+    pub fn tmux() -> Self {
+        Self::new(
+            "tmux",
+            &["tmux", "load-buffer", "-w", "-"],
+            Some(&["tmux", "save-buffer", "-"]),
+        )
+    }
+
+    fn command(args: &[String]) -> Command {
+        let mut command = Command::new(&args[0]);
+        command.args(&args[1..]);
+        command
+    }
+
+    fn run_copy(args: &[String], content: &str) -> Result<()> {
+        // Tools like xclip stay around to own the selection, so their output
+        // is not captured to avoid waiting on it
+        let mut child = Self::command(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run '{}'", args[0]))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(content.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            bail!("'{}' exited with {}", args[0], status);
+        }
+        Ok(())
+    }
+
+    fn run_paste(args: &[String]) -> Result<String> {
+        let output = Self::command(args)
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run '{}'", args[0]))?;
+        if !output.status.success() {
+            bail!(
+                "'{}' exited with {}: {}",
+                args[0],
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    fn primary_commands(&self) -> Result<&(Vec<String>, Vec<String>)> {
+        self.primary
+            .as_ref()
+            .ok_or_else(|| anyhow!("The {} clipboard has no primary selection", self.name))
+    }
+}
+
+impl ClipboardProvider for CommandProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_contents(&mut self, content: String) -> Result<()> {
+        Self::run_copy(&self.copy, &content)
+    }
+
+    fn get_contents(&mut self) -> Result<String> {
+        let paste = self
+            .paste
+            .as_ref()
+            .ok_or_else(|| anyhow!("Pasting is not supported by {}", self.name))?;
+        Self::run_paste(paste)
+    }
+
+    fn set_primary(&mut self, content: String) -> Result<()> {
+        Self::run_copy(&self.primary_commands()?.0, &content)
+    }
+
+    fn get_primary(&mut self) -> Result<String> {
+        Self::run_paste(&self.primary_commands()?.1)
+    }
+}
+
+/// Copies through the terminal with OSC 52. Pasting is left to the terminal.
+pub struct Osc52Provider;
+
+impl ClipboardProvider for Osc52Provider {
+    fn name(&self) -> &str {
+        "osc52"
+    }
+
+    fn set_contents(&mut self, content: String) -> Result<()> {
+        copy_osc52(&content)
+    }
+
+    fn get_contents(&mut self) -> Result<String> {
+        bail!("Reading the clipboard is not supported over OSC 52")
+    }
+}
+
+/// Keeps the contents in memory. Clones share the same contents, so a test can
+/// keep one to look at what was copied.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryProvider {
+    contents: Rc<RefCell<String>>,
+    primary: Rc<RefCell<String>>,
+}
+
+impl MemoryProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contents(&self) -> String {
+        self.contents.borrow().clone()
+    }
+
+    pub fn primary_contents(&self) -> String {
+        self.primary.borrow().clone()
+    }
+}
+
+impl ClipboardProvider for MemoryProvider {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn set_contents(&mut self, content: String) -> Result<()> {
+        *self.contents.borrow_mut() = content;
+        Ok(())
+    }
+
+    fn get_contents(&mut self) -> Result<String> {
+        Ok(self.contents())
+    }
+
+    fn set_primary(&mut self, content: String) -> Result<()> {
+        *self.primary.borrow_mut() = content;
+        Ok(())
+    }
+
+    fn get_primary(&mut self) -> Result<String> {
+        Ok(self.primary_contents())
+    }
+}
+
+/// Keeps the contents in a file, which also survives between runs.
+pub struct FileProvider {
+    path: PathBuf,
+}
+
+impl FileProvider {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        FileProvider {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl ClipboardProvider for FileProvider {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn set_contents(&mut self, content: String) -> Result<()> {
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    fn get_contents(&mut self) -> Result<String> {
+        if !self.path.exists() {
+            return Ok(String::new());
+        }
+        fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))
+    }
+}
+
+/// Whether `program` is an executable on the `PATH`.
+fn command_exists(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// The provider `ClipboardMode::Auto` uses: the system clipboard where there is a
+/// display, `wl-copy` on Wayland, and OSC 52 without any display.
+fn detect_provider() -> Result<Box<dyn ClipboardProvider>> {
+    if cfg!(target_os = "linux")
+        && env::var_os("WAYLAND_DISPLAY").is_some_and(|v| !v.is_empty())
+        && command_exists("wl-copy")
+    {
+        return Ok(Box::new(CommandProvider::wl_clipboard()));
+    }
+    if !has_display() {
+        return Ok(Box::new(Osc52Provider));
+    }
+    Ok(Box::new(ArboardProvider::new()?))
+}
+
+pub struct EditorClipboard {
+    provider: Box<dyn ClipboardProvider>,
+    /// Where copies are recorded, when the history is on
+    history: Option<ClipHistory>,
+    record_pastes: bool,
+}
+
+impl EditorClipboard {
+    /// Opens the clipboard chosen in the config file, recording copies in the
+    /// clipboard history unless it is turned off.
+    pub fn new() -> Result<EditorClipboard> {
+        let config = ThothConfig::load().unwrap_or_default();
+        let clipboard = Self::with_mode(config.clipboard)?;
+        let history = &config.clipboard_history;
+        if history.max_entries == 0 {
+            return Ok(clipboard);
+        }
+        // A damaged history file should not stop copying
+        match ClipHistory::load_from(get_clip_history_file_path(), history.max_entries) {
+            Ok(loaded) => Ok(clipboard.with_history(loaded, history.record_pastes)),
+            Err(_) => Ok(clipboard),
+        }
+    }
+
+    pub fn with_mode(mode: ClipboardMode) -> Result<EditorClipboard> {
+        let provider: Box<dyn ClipboardProvider> = match mode {
+            ClipboardMode::Auto => detect_provider()?,
+            ClipboardMode::System => Box::new(ArboardProvider::new()?),
+            ClipboardMode::Osc52 => Box::new(Osc52Provider),
+            ClipboardMode::WlClipboard => Box::new(CommandProvider::wl_clipboard()),
+            ClipboardMode::Xclip => Box::new(CommandProvider::xclip()),
+            ClipboardMode::Xsel => Box::new(CommandProvider::xsel()),
+            ClipboardMode::Pbcopy => Box::new(CommandProvider::pbcopy()),
+            ClipboardMode::Tmux => Box::new(CommandProvider::tmux()),
+        };
+        Ok(Self::with_provider(provider))
+    }
+
+    pub fn with_provider(provider: Box<dyn ClipboardProvider>) -> EditorClipboard {
+        EditorClipboard {
+            provider,
+            history: None,
+            record_pastes: false,
+        }
+    }
+
+    pub fn with_history(mut self, history: ClipHistory, record_pastes: bool) -> EditorClipboard {
+        self.history = Some(history);
+        self.record_pastes = record_pastes;
+        self
+    }
+
+    /// Entries of the clipboard history, most recent first, including those
+    /// recorded by other thoth processes.
+    pub fn history_entries(&mut self) -> Result<Vec<String>> {
+        match self.history.as_mut() {
+            Some(history) => {
+                history.reload()?;
+                Ok(history.entries().to_vec())
             }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn record(&mut self, text: &str) -> Result<()> {
+        if let Some(history) = self.history.as_mut() {
+            history.reload()?;
+            history.push(text.to_string());
+            history.save()?;
         }
         Ok(())
     }
+
+    pub fn try_new() -> Option<EditorClipboard> {
+        Self::new().ok()
+    }
+
+    pub fn provider_name(&self) -> &str {
+        self.provider.name()
+    }
+
+    pub fn set_contents(&mut self, content: String) -> Result<()> {
+        self.provider.set_contents(content.clone())?;
+        self.record(&content)
+            .context("Copied, but failed to update the clipboard history")
+    }
+
+    pub fn get_content(&mut self) -> Result<String> {
+        let content = self.provider.get_contents()?;
+        if self.record_pastes {
+            self.record(&content)
+                .context("Failed to update the clipboard history")?;
+        }
+        Ok(content)
+    }
+
+    /// Copies to the primary selection. Selections change too often to be
+    /// worth recording in the history.
+    pub fn set_primary(&mut self, content: String) -> Result<()> {
+        self.provider.set_primary(content)
+    }
+
+    pub fn get_primary(&mut self) -> Result<String> {
+        self.provider.get_primary()
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn handle_daemon_args() -> Result<()> {
+        ArboardProvider::handle_daemon_args()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ThothConfig;
+
+    #[test]
+    fn test_memory_provider_shares_contents() {
+        let memory = MemoryProvider::new();
+        let mut clipboard = EditorClipboard::with_provider(Box::new(memory.clone()));
+        assert_eq!(clipboard.provider_name(), "memory");
+        clipboard.set_contents("copied".to_string()).unwrap();
+        assert_eq!(memory.contents(), "copied");
+        assert_eq!(clipboard.get_content().unwrap(), "copied");
+    }
+
+    #[test]
+    fn test_copies_and_pastes_are_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.json");
+        let memory = MemoryProvider::new();
+        let history = ClipHistory::load_from(&path, 10).unwrap();
+        let mut clipboard =
+            EditorClipboard::with_provider(Box::new(memory.clone())).with_history(history, false);
+        clipboard.set_contents("first".to_string()).unwrap();
+        clipboard.set_contents("second".to_string()).unwrap();
+        assert_eq!(clipboard.history_entries().unwrap(), ["second", "first"]);
+
+        // Another process copying in the meantime is not lost
+        let mut other = ClipHistory::load_from(&path, 10).unwrap();
+        other.push("from cli".to_string());
+        other.save().unwrap();
+        clipboard.set_contents("third".to_string()).unwrap();
+        assert_eq!(
+            ClipHistory::load_from(&path, 10).unwrap().entries(),
+            ["third", "from cli", "second", "first"]
+        );
+
+        memory.clone().set_contents("pasted".to_string()).unwrap();
+        clipboard.get_content().unwrap();
+        assert_eq!(clipboard.history_entries().unwrap()[0], "third");
+        clipboard.record_pastes = true;
+        clipboard.get_content().unwrap();
+        assert_eq!(clipboard.history_entries().unwrap()[0], "pasted");
+    }
+
+    #[test]
+    fn test_primary_selection_is_separate_and_not_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let memory = MemoryProvider::new();
+        let history = ClipHistory::load_from(dir.path().join("history.json"), 10).unwrap();
+        let mut clipboard =
+            EditorClipboard::with_provider(Box::new(memory.clone())).with_history(history, true);
+        clipboard.set_contents("copied".to_string()).unwrap();
+        clipboard.set_primary("selected".to_string()).unwrap();
+        assert_eq!(memory.contents(), "copied");
+        assert_eq!(memory.primary_contents(), "selected");
+        assert_eq!(clipboard.get_primary().unwrap(), "selected");
+        assert_eq!(clipboard.history_entries().unwrap(), ["copied"]);
+
+        let mut osc52 = EditorClipboard::with_provider(Box::new(Osc52Provider));
+        let error = osc52.set_primary("x".to_string()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The osc52 clipboard has no primary selection"
+        );
+    }
+
+    #[test]
+    fn test_file_provider_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut provider = FileProvider::new(dir.path().join("clipboard"));
+        assert_eq!(provider.get_contents().unwrap(), "");
+        provider.set_contents("line 1\nline 2".to_string()).unwrap();
+        assert_eq!(provider.get_contents().unwrap(), "line 1\nline 2");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_provider_pipes_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("buffer");
+        let file = file.to_str().unwrap();
+        let copy = format!("cat > '{}'", file);
+        let mut provider = CommandProvider::new("test", &["sh", "-c", &copy], Some(&["cat", file]));
+        provider.set_contents("über\n".to_string()).unwrap();
+        assert_eq!(provider.get_contents().unwrap(), "über\n");
+
+        let mut failing = CommandProvider::new("false", &["false"], None);
+        assert!(failing.set_contents(String::new()).is_err());
+        assert!(failing.get_contents().is_err());
+        assert!(failing.set_primary(String::new()).is_err());
+
+        let primary = format!("cat > '{}.primary'", file);
+        let primary_file = format!("{}.primary", file);
+        let mut provider = provider.with_primary(&["sh", "-c", &primary], &["cat", &primary_file]);
+        provider.set_primary("selected".to_string()).unwrap();
+        assert_eq!(provider.get_primary().unwrap(), "selected");
+        assert_eq!(provider.get_contents().unwrap(), "über\n");
+    }
+
+    #[test]
+    fn test_read_daemon_report() {
+        assert!(read_daemon_report("ok\n".as_bytes()).is_ok());
+        let error = read_daemon_report("error: no display\n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Clipboard daemon failed: no display");
+        assert!(read_daemon_report("".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_frames_round_trip() {
+        let mut written = Vec::new();
+        write_frame(&mut written, "first\nline").unwrap();
+        write_frame(&mut written, "").unwrap();
+        write_frame(&mut written, "ünïcode").unwrap();
+        let mut reader = std::io::Cursor::new(written);
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), "first\nline");
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), "");
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), "ünïcode");
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+        assert!(read_frame(&mut std::io::Cursor::new("x\n")).is_err());
+    }
+
+    #[test]
+    fn test_clipboard_mode_names() {
+        let mode = |name: &str| {
+            ThothConfig::parse(&format!("clipboard = \"{}\"", name))
+                .unwrap()
+                .clipboard
+        };
+        assert_eq!(mode("wl-clipboard"), ClipboardMode::WlClipboard);
+        assert_eq!(mode("xsel"), ClipboardMode::Xsel);
+        assert_eq!(mode("tmux"), ClipboardMode::Tmux);
+        assert_eq!(mode("osc52"), ClipboardMode::Osc52);
+    }
 }
@@ -0,0 +1,228 @@
+/// A fenced code block within the lines of a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeFence {
+    /// Line index of the opening fence
+    pub open: usize,
+    /// Line index of the closing fence, or the number of lines when the fence is never closed
+    pub close: usize,
+    /// First word of the info string, e.g. `json` for ```` ```json title="x" ````
+    pub language: Option<String>,
+    /// Indentation of the opening fence, removed from the code while it is formatted
+    pub indent: usize,
+}
+
+/// Returns the indent, marker character, marker length and info string of an opening fence.
+fn opening_fence(line: &str) -> Option<(usize, char, usize, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let rest = &line[indent..];
+    let marker = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let marker_len = rest.len() - rest.trim_start_matches(marker).len();
+    if marker_len < 3 {
+        return None;
+    }
+    let info = rest[marker_len..].trim();
+    if marker == '`' && info.contains('`') {
+        return None;
+    }
+    Some((indent, marker, marker_len, info))
+}
+
+fn is_closing_fence(line: &str, marker: char, marker_len: usize) -> bool {
+    let trimmed = line.trim_start_matches(' ');
+    let run = trimmed.len() - trimmed.trim_start_matches(marker).len();
+    run >= marker_len && trimmed[run..].trim().is_empty()
+}
+
+/// Length of the backtick fence opened by `line`. Notes files and the renderer only
+/// know backtick fences, which are closed by a bare run at least as long.
+pub fn backtick_fence_len(line: &str) -> Option<usize> {
+    match opening_fence(line) {
+        Some((_, '`', len, _)) => Some(len),
+        _ => None,
+    }
+}
+
+pub fn closes_backtick_fence(line: &str, len: usize) -> bool {
+    is_closing_fence(line, '`', len)
+}
+
+/// Length of the backtick fence open after `line`, given the one open before it.
+pub fn fence_after(open: Option<usize>, line: &str) -> Option<usize> {
+    match open {
+        Some(len) if closes_backtick_fence(line, len) => None,
+        Some(len) => Some(len),
+        None => backtick_fence_len(line),
+    }
+}
+
+/// Every fenced code block in `lines`, in order.
+pub fn code_fences<S: AsRef<str>>(lines: &[S]) -> Vec<CodeFence> {
+    let mut fences = Vec::new();
+    let mut row = 0;
+    while row < lines.len() {
+        let (indent, marker, marker_len, info) = match opening_fence(lines[row].as_ref()) {
+            Some(fence) => fence,
+            None => {
+                row += 1;
+                continue;
+            }
+        };
+        let close = (row + 1..lines.len())
+            .find(|&i| is_closing_fence(lines[i].as_ref(), marker, marker_len))
+            .unwrap_or(lines.len());
+        let language = info
+            .split_whitespace()
+            .next()
+            .map(|word| word.trim_start_matches('{').trim_start_matches('.'))
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase);
+        fences.push(CodeFence {
+            open: row,
+            close,
+            language,
+            indent,
+        });
+        row = close + 1;
+    }
+    fences
+}
+
+/// The fenced code block containing `row`, fence lines included.
+pub fn code_fence_at<S: AsRef<str>>(lines: &[S], row: usize) -> Option<CodeFence> {
+    code_fences(lines)
+        .into_iter()
+        .find(|fence| fence.open <= row && row <= fence.close)
+}
+
+impl CodeFence {
+    /// The code between the fences, with the fence indentation removed.
+    pub fn content<S: AsRef<str>>(&self, lines: &[S]) -> String {
+        lines[self.open + 1..self.close.min(lines.len())]
+            .iter()
+            .map(|line| strip_indent(line.as_ref(), self.indent))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// `lines` with the code between the fences replaced by `code`. Everything
+    /// outside the fences, the fence lines included, is left untouched.
+    pub fn splice<S: AsRef<str>>(&self, lines: &[S], code: &str) -> Vec<String> {
+        let indent = " ".repeat(self.indent);
+        let code_lines = code.lines().map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", indent, line)
+            }
+        });
+        lines[..=self.open]
+            .iter()
+            .map(|line| line.as_ref().to_string())
+            .chain(code_lines)
+            .chain(
+                lines[self.close.min(lines.len())..]
+                    .iter()
+                    .map(|line| line.as_ref().to_string()),
+            )
+            .collect()
+    }
+
+    /// Replaces the language of the opening fence, keeping the rest of the info string.
+    pub fn relabel(&self, lines: &mut [String], language: &str) {
+        let line = &lines[self.open];
+        let relabeled = match opening_fence(line) {
+            Some((indent, marker, marker_len, info)) => {
+                let rest = info.split_once(char::is_whitespace).map(|(_, rest)| rest);
+                format!(
+                    "{}{}{}{}",
+                    &line[..indent],
+                    marker.to_string().repeat(marker_len),
+                    language,
+                    rest.map(|rest| format!(" {}", rest)).unwrap_or_default()
+                )
+            }
+            None => return,
+        };
+        lines[self.open] = relabeled;
+    }
+}
+
+fn strip_indent(line: &str, indent: usize) -> &str {
+    let spaces = line.len() - line.trim_start_matches(' ').len();
+    &line[spaces.min(indent)..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_code_fences() {
+        let note = lines("intro\n```json\n{}\n```\n~~~~ sql extra\nselect 1\n~~~~\n```\nunclosed");
+        let fences = code_fences(&note);
+        assert_eq!(fences.len(), 3);
+        assert_eq!(fences[0].open, 1);
+        assert_eq!(fences[0].close, 3);
+        assert_eq!(fences[0].language.as_deref(), Some("json"));
+        assert_eq!(fences[1].language.as_deref(), Some("sql"));
+        assert_eq!(fences[2].close, note.len());
+        assert_eq!(fences[2].language, None);
+    }
+
+    #[test]
+    fn test_closing_fence_must_match_marker() {
+        let note = lines("````md\n```\nnested\n```\n````");
+        let fences = code_fences(&note);
+        assert_eq!(fences.len(), 1);
+        assert_eq!(fences[0].close, 4);
+        assert_eq!(fences[0].language.as_deref(), Some("md"));
+    }
+
+    #[test]
+    fn test_code_fence_at() {
+        let note = lines("a\n```\nb\n```\nc");
+        assert_eq!(code_fence_at(&note, 0), None);
+        assert_eq!(code_fence_at(&note, 1).unwrap().open, 1);
+        assert_eq!(code_fence_at(&note, 2).unwrap().open, 1);
+        assert_eq!(code_fence_at(&note, 3).unwrap().open, 1);
+        assert_eq!(code_fence_at(&note, 4), None);
+    }
+
+    #[test]
+    fn test_content_and_splice_keep_surroundings() {
+        let note = lines("- item\n  ```json\n  {\"a\":1}\n  ```\n  trailing  ");
+        let fence = code_fence_at(&note, 2).unwrap();
+        assert_eq!(fence.indent, 2);
+        assert_eq!(fence.content(&note), "{\"a\":1}");
+
+        let spliced = fence.splice(&note, "{\n  \"a\": 1\n}");
+        assert_eq!(
+            spliced,
+            lines("- item\n  ```json\n  {\n    \"a\": 1\n  }\n  ```\n  trailing  ")
+        );
+    }
+
+    #[test]
+    fn test_relabel_keeps_info_string() {
+        let mut note = lines("  ```json title=\"x\"\n  {}\n  ```\n~~~\n~~~");
+        let fences = code_fences(&note);
+        fences[0].relabel(&mut note, "yaml");
+        fences[1].relabel(&mut note, "toml");
+        assert_eq!(note[0], "  ```yaml title=\"x\"");
+        assert_eq!(note[3], "~~~toml");
+    }
+
+    #[test]
+    fn test_splice_unclosed_fence() {
+        let note = lines("```sql\nselect 1");
+        let fence = code_fence_at(&note, 1).unwrap();
+        assert_eq!(
+            fence.splice(&note, "SELECT\n  1"),
+            lines("```sql\nSELECT\n  1")
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::clip_history::ClipHistoryConfig;
+use crate::clipboard::ClipboardMode;
+use crate::external_formatter::ExternalFormatterConfig;
+use crate::get_config_file_path;
+
+/// User settings read from `config.toml` in the thoth config directory.
+/// Every field is optional so a missing or partial file falls back to defaults.
+#[derive(Debug, Default, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ThothConfig {
+    /// Name of a syntect theme, either built-in or a `.tmTheme` from the themes directory
+    pub theme: Option<String>,
+    /// Force the `dark` or `light` UI palette instead of deriving it from the theme
+    pub palette: Option<String>,
+    /// External formatter commands keyed by language, e.g. `[formatters.rust]`
+    pub formatters: BTreeMap<String, ExternalFormatterConfig>,
+    /// `auto`, `system`, `osc52` or one of the clipboard commands
+    pub clipboard: ClipboardMode,
+    /// Size of the clipboard history and whether pastes are recorded in it
+    pub clipboard_history: ClipHistoryConfig,
+    /// Copy edit mode selections to the X11/Wayland primary selection
+    pub primary_selection: bool,
+}
+
+impl ThothConfig {
+    pub fn load() -> Result<Self> {
+        let path = get_config_file_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_empty_config() {
+        assert_eq!(ThothConfig::parse("").unwrap(), ThothConfig::default());
+    }
+
+    #[test]
+    fn test_parse_theme_config() {
+        let config = ThothConfig::parse("theme = \"InspiredGitHub\"\npalette = \"light\"").unwrap();
+        assert_eq!(config.theme.as_deref(), Some("InspiredGitHub"));
+        assert_eq!(config.palette.as_deref(), Some("light"));
+    }
+
+    #[test]
+    fn test_parse_formatters_config() {
+        let config = ThothConfig::parse(
+            "[formatters.rust]\ncommand = [\"rustfmt\", \"--emit\", \"stdout\"]\naliases = [\"rs\"]\n\n[formatters.sh]\ncommand = [\"shfmt\"]\ntimeout_secs = 2",
+        )
+        .unwrap();
+        let rust = &config.formatters["rust"];
+        assert_eq!(rust.command, ["rustfmt", "--emit", "stdout"]);
+        assert_eq!(rust.aliases, ["rs"]);
+        assert_eq!(rust.timeout_secs, None);
+        assert_eq!(config.formatters["sh"].timeout_secs, Some(2));
+
+        assert!(ThothConfig::parse("[formatters.sh]\ntimeout_secs = 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_clipboard_config() {
+        assert_eq!(ThothConfig::default().clipboard, ClipboardMode::Auto);
+        let config = ThothConfig::parse("clipboard = \"osc52\"").unwrap();
+        assert_eq!(config.clipboard, ClipboardMode::Osc52);
+        assert!(ThothConfig::parse("clipboard = \"x11\"").is_err());
+        assert!(!config.primary_selection);
+        assert!(
+            ThothConfig::parse("primary_selection = true")
+                .unwrap()
+                .primary_selection
+        );
+    }
+
+    #[test]
+    fn test_parse_clipboard_history_config() {
+        let config = ThothConfig::parse("[clipboard_history]\nrecord_pastes = true").unwrap();
+        assert_eq!(config.clipboard_history.max_entries, 50);
+        assert!(config.clipboard_history.record_pastes);
+    }
+
+    #[test]
+    fn test_parse_invalid_config() {
+        assert!(ThothConfig::parse("theme = ").is_err());
+    }
+}
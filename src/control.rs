@@ -0,0 +1,425 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::get_runtime_dir;
+use crate::notebook::{blocks_to_markdown, Block, Notebook, NotebookError};
+use crate::utils::stable_hash;
+
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use std::time::Duration;
+
+/// How long either side waits on the other before giving up on a request.
+#[cfg(unix)]
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An operation a CLI command asks the running TUI to do instead of touching the
+/// notes file itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ControlRequest {
+    Add {
+        name: String,
+        content: String,
+    },
+    Append {
+        name: String,
+        content: String,
+    },
+    View {
+        name: String,
+    },
+    /// Every block, answered with `ControlResponse::Blocks`
+    Blocks,
+    /// The writes of `thoth serve`, which checks them against the blocks it got at
+    /// `version` and only wants them carried out when nothing changed since.
+    Insert {
+        version: u64,
+        block: Block,
+    },
+    Update {
+        version: u64,
+        name: String,
+        new_name: Option<String>,
+        content: Option<String>,
+    },
+    Delete {
+        version: u64,
+        name: String,
+    },
+}
+
+/// What the TUI answers: text for the CLI to print, or why it failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlResponse {
+    Ok(String),
+    Error(String),
+    Blocks { version: u64, blocks: Vec<Block> },
+}
+
+/// Identifies the state of `blocks`, so a write can be refused when they changed
+/// after the writer read them.
+pub fn blocks_version(blocks: &[Block]) -> u64 {
+    stable_hash(blocks_to_markdown(blocks).bytes())
+}
+
+/// The notes as a writer outside the TUI sees them: the blocks of the TUI editing
+/// them when one is running, so changes go through it and are not lost on its next
+/// save, or else the notes file.
+pub struct SharedNotes {
+    pub notebook: Notebook,
+    owner: Owner,
+}
+
+enum Owner {
+    /// No TUI is open, so the notes file is read and written directly
+    File,
+    /// A TUI has the file open, with its blocks at this version
+    Tui(u64),
+    UnreachableTui,
+}
+
+/// Why a change was not made: the running TUI refused it or could not be given it.
+#[derive(Debug)]
+pub struct Refused(pub String);
+
+impl std::fmt::Display for Refused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Refused {}
+
+impl SharedNotes {
+    pub fn open(notes_path: &Path) -> std::result::Result<SharedNotes, NotebookError> {
+        let owner = match send_request(notes_path, &ControlRequest::Blocks) {
+            Ok(Some(ControlResponse::Blocks { version, blocks })) => {
+                return Ok(SharedNotes {
+                    notebook: Notebook::with_blocks(notes_path, blocks),
+                    owner: Owner::Tui(version),
+                });
+            }
+            Ok(None) => Owner::File,
+            // A TUI has the file open but cannot take writes, such as an older thoth
+            Ok(Some(_)) | Err(_) => Owner::UnreachableTui,
+        };
+        Ok(SharedNotes {
+            notebook: Notebook::open(notes_path)?,
+            owner,
+        })
+    }
+
+    /// Writes the notebook back to where it came from. A running TUI gets the change
+    /// made by `change`, called with the version its blocks were read at. Fails with
+    /// `Refused` when the TUI did not make it.
+    pub fn commit(&self, change: impl FnOnce(u64) -> ControlRequest) -> Result<()> {
+        let version = match self.owner {
+            Owner::File => return Ok(self.notebook.save()?),
+            Owner::UnreachableTui => {
+                return Err(Refused(
+                    "The notes file is open in a thoth that cannot take changes".to_string(),
+                )
+                .into())
+            }
+            Owner::Tui(version) => version,
+        };
+        match send_request(self.notebook.path(), &change(version))? {
+            Some(ControlResponse::Ok(_)) => Ok(()),
+            Some(ControlResponse::Error(message)) => Err(Refused(message).into()),
+            Some(ControlResponse::Blocks { .. }) => {
+                bail!("The open thoth sent an unexpected answer")
+            }
+            None => Err(Refused(
+                "The open thoth closed before taking the change, try again".to_string(),
+            )
+            .into()),
+        }
+    }
+}
+
+/// Socket a TUI editing `notes_path` listens on. Each notes file gets its own.
+pub fn socket_path(notes_path: &Path) -> PathBuf {
+    let notes_path = notes_path
+        .canonicalize()
+        .unwrap_or_else(|_| notes_path.to_path_buf());
+    let hash = stable_hash(notes_path.to_string_lossy().bytes());
+    get_runtime_dir().join(format!("{:016x}.sock", hash))
+}
+
+/// A request read off a connection, with where its answer goes.
+#[cfg(unix)]
+type PendingRequest = (ControlRequest, Sender<ControlResponse>);
+
+/// Listens for CLI commands while the TUI is open, and removes its socket when dropped.
+pub struct ControlServer {
+    path: PathBuf,
+    #[cfg(unix)]
+    listener: UnixListener,
+    /// Connections are read on their own threads, which pass the requests back here
+    #[cfg(unix)]
+    requests: (Sender<PendingRequest>, Receiver<PendingRequest>),
+}
+
+impl ControlServer {
+    /// Listens on the socket of `notes_path`. `None` when another TUI already does,
+    /// or on platforms without Unix sockets.
+    pub fn bind(notes_path: &Path) -> Result<Option<ControlServer>> {
+        Self::bind_at(socket_path(notes_path))
+    }
+
+    #[cfg(unix)]
+    pub fn bind_at(path: PathBuf) -> Result<Option<ControlServer>> {
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Ok(None);
+            }
+            // Left behind by a TUI that did not exit cleanly
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+        }
+        if let Some(dir) = path.parent() {
+            create_private_dir(dir)?;
+        }
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to listen on {}", path.display()))?;
+        listener.set_nonblocking(true)?;
+        Ok(Some(ControlServer {
+            path,
+            listener,
+            requests: mpsc::channel(),
+        }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind_at(_path: PathBuf) -> Result<Option<ControlServer>> {
+        Ok(None)
+    }
+
+    /// Answers every request that has been read off the socket with `handle`, without
+    /// blocking on clients that are slow to send theirs. Returns how many were answered.
+    #[cfg(unix)]
+    pub fn serve_pending(
+        &self,
+        mut handle: impl FnMut(ControlRequest) -> ControlResponse,
+    ) -> usize {
+        while let Ok((stream, _)) = self.listener.accept() {
+            let requests = self.requests.0.clone();
+            thread::spawn(move || {
+                // A client that sends garbage or hangs up only loses its own request
+                let _ = serve_connection(stream, requests);
+            });
+        }
+        let mut served = 0;
+        while let Ok((request, answer)) = self.requests.1.try_recv() {
+            // The client may have given up waiting, which only matters to it
+            let _ = answer.send(handle(request));
+            served += 1;
+        }
+        served
+    }
+
+    #[cfg(not(unix))]
+    pub fn serve_pending(&self, _handle: impl FnMut(ControlRequest) -> ControlResponse) -> usize {
+        0
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Creates `dir` only the current user can use, and makes sure an existing one is
+/// like that too, since whoever can write to it can take over the socket.
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    let metadata = fs::symlink_metadata(dir)
+        .with_context(|| format!("Failed to inspect {}", dir.display()))?;
+    // SAFETY: getuid cannot fail and has no preconditions
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o777 != 0o700 {
+        bail!(
+            "Refusing to use {} for the control socket: it must be a directory owned by you with mode 0700",
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Reads the request on `stream`, hands it to the TUI through `requests` and writes
+/// back its answer. Runs on its own thread so a slow client cannot hold up the TUI.
+#[cfg(unix)]
+fn serve_connection(stream: UnixStream, requests: Sender<PendingRequest>) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    if line.trim().is_empty() {
+        // Only checked whether a TUI is listening
+        bail!("Connection closed without a request");
+    }
+    let response = match serde_json::from_str(&line) {
+        Ok(request) => {
+            let (answer, response) = mpsc::channel();
+            requests.send((request, answer))?;
+            // Fails when the TUI quit before getting to the request, and closing the
+            // connection without an answer tells the client it was not carried out
+            response.recv()?
+        }
+        Err(e) => ControlResponse::Error(format!("Invalid request: {}", e)),
+    };
+    writeln!(&stream, "{}", serde_json::to_string(&response)?)?;
+    Ok(())
+}
+
+/// Sends `request` to the TUI editing `notes_path`. `None` when no TUI is running,
+/// in which case the caller works on the notes file itself.
+pub fn send_request(
+    notes_path: &Path,
+    request: &ControlRequest,
+) -> Result<Option<ControlResponse>> {
+    send_request_to(&socket_path(notes_path), request)
+}
+
+#[cfg(unix)]
+pub fn send_request_to(path: &Path, request: &ControlRequest) -> Result<Option<ControlResponse>> {
+    let stream = match UnixStream::connect(path) {
+        Ok(stream) => stream,
+        Err(_) => return Ok(None),
+    };
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    writeln!(&stream, "{}", serde_json::to_string(request)?)?;
+    let mut line = String::new();
+    match BufReader::new(&stream).read_line(&mut line) {
+        // The TUI quit before getting to the request, so it was not carried out
+        Ok(0) => return Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => return Ok(None),
+        result => {
+            result.context("The running thoth did not answer")?;
+        }
+    }
+    let response =
+        serde_json::from_str(&line).context("The running thoth sent an invalid answer")?;
+    Ok(Some(response))
+}
+
+#[cfg(not(unix))]
+pub fn send_request_to(_path: &Path, _request: &ControlRequest) -> Result<Option<ControlResponse>> {
+    Ok(None)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_request_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run").join("notes.sock");
+        let request = ControlRequest::View {
+            name: "todo".to_string(),
+        };
+        assert_eq!(send_request_to(&path, &request).unwrap(), None);
+
+        let server = ControlServer::bind_at(path.clone()).unwrap().unwrap();
+        assert!(ControlServer::bind_at(path.clone()).unwrap().is_none());
+        assert_eq!(server.serve_pending(|_| unreachable!()), 0);
+
+        let client_path = path.clone();
+        let client = thread::spawn(move || send_request_to(&client_path, &request).unwrap());
+        let mut received = Vec::new();
+        while received.is_empty() {
+            server.serve_pending(|request| {
+                received.push(request);
+                ControlResponse::Ok("- [ ] tests".to_string())
+            });
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(
+            client.join().unwrap(),
+            Some(ControlResponse::Ok("- [ ] tests".to_string()))
+        );
+        assert_eq!(
+            received,
+            [ControlRequest::View {
+                name: "todo".to_string()
+            }]
+        );
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_silent_client_does_not_hold_up_the_tui() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run").join("notes.sock");
+        let server = ControlServer::bind_at(path.clone()).unwrap().unwrap();
+        let _silent = UnixStream::connect(&path).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        let started = std::time::Instant::now();
+        assert_eq!(server.serve_pending(|_| unreachable!()), 0);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // Requests that come in meanwhile are still answered
+        let client_path = path.clone();
+        let client =
+            thread::spawn(move || send_request_to(&client_path, &ControlRequest::Blocks).unwrap());
+        while server.serve_pending(|_| ControlResponse::Ok(String::new())) == 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(
+            client.join().unwrap(),
+            Some(ControlResponse::Ok(String::new()))
+        );
+    }
+
+    #[test]
+    fn test_shared_socket_dir_is_refused() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("thoth");
+        fs::create_dir(&shared).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o777)).unwrap();
+        let error = ControlServer::bind_at(shared.join("notes.sock"))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("mode 0700"), "{}", error);
+
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(ControlServer::bind_at(shared.join("notes.sock"))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_stale_socket_is_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run").join("notes.sock");
+        create_private_dir(path.parent().unwrap()).unwrap();
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        assert!(ControlServer::bind_at(path).unwrap().is_some());
+    }
+}
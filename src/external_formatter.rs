@@ -0,0 +1,180 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+
+use crate::formatter::Formatter;
+
+pub const DEFAULT_FORMATTER_TIMEOUT_SECS: u64 = 5;
+
+/// A `[formatters.<language>]` entry in `config.toml`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ExternalFormatterConfig {
+    /// Program and arguments, e.g. `["rustfmt", "--emit", "stdout"]`
+    pub command: Vec<String>,
+    /// Extra code fence tokens handled by the command, e.g. `["rs"]`
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub timeout_secs: Option<u64>,
+}
+
+/// Formats by piping the input through an external command's stdin and reading its stdout.
+pub struct CommandFormatter {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    aliases: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandFormatter {
+    pub fn new(name: &str, config: &ExternalFormatterConfig) -> Result<Self> {
+        let (program, args) = match config.command.split_first() {
+            Some((program, args)) if !program.trim().is_empty() => (program, args),
+            _ => bail!("Formatter '{}' has an empty command", name),
+        };
+        Ok(CommandFormatter {
+            name: name.to_string(),
+            program: program.clone(),
+            args: args.to_vec(),
+            aliases: config.aliases.clone(),
+            timeout: Duration::from_secs(
+                config
+                    .timeout_secs
+                    .unwrap_or(DEFAULT_FORMATTER_TIMEOUT_SECS),
+            ),
+        })
+    }
+
+    fn run(&self, input: &str) -> Result<String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run '{}'", self.program))?;
+
+        // Feed stdin and drain the pipes on their own threads so a chatty
+        // formatter cannot fill a pipe buffer and deadlock against us
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = input.to_string();
+        let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let stdout = read_to_end_in_background(child.stdout.take().expect("stdout is piped"));
+        let stderr = read_to_end_in_background(child.stderr.take().expect("stderr is piped"));
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() >= self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!(
+                    "'{}' timed out after {} seconds",
+                    self.program,
+                    self.timeout.as_secs()
+                );
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        // A formatter may exit before reading all of stdin; its status decides success
+        let _ = writer.join();
+        let stdout = stdout
+            .join()
+            .map_err(|_| anyhow!("Failed to read output of '{}'", self.program))??;
+        let stderr = stderr
+            .join()
+            .map_err(|_| anyhow!("Failed to read errors of '{}'", self.program))??;
+
+        if !status.success() {
+            let stderr = String::from_utf8_lossy(&stderr);
+            bail!("'{}' failed ({}): {}", self.program, status, stderr.trim());
+        }
+        let output = String::from_utf8(stdout)
+            .with_context(|| format!("'{}' produced output that is not UTF-8", self.program))?;
+        Ok(output.trim_end_matches('\n').to_string())
+    }
+}
+
+fn read_to_end_in_background<R: Read + Send + 'static>(
+    mut reader: R,
+) -> thread::JoinHandle<std::io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    })
+}
+
+impl Formatter for CommandFormatter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn handles(&self, language: &str) -> bool {
+        self.name.eq_ignore_ascii_case(language)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(language))
+    }
+
+    fn format(&self, input: &str) -> Result<String> {
+        self.run(input)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn formatter(command: &[&str], timeout_secs: Option<u64>) -> CommandFormatter {
+        let config = ExternalFormatterConfig {
+            command: command.iter().map(|s| s.to_string()).collect(),
+            aliases: vec!["up".to_string()],
+            timeout_secs,
+        };
+        CommandFormatter::new("upper", &config).unwrap()
+    }
+
+    #[test]
+    fn test_pipes_input_through_command() {
+        let upper = formatter(&["tr", "a-z", "A-Z"], None);
+        assert_eq!(upper.format("hello\nworld\n").unwrap(), "HELLO\nWORLD");
+        assert!(upper.handles("UP"));
+        assert!(!upper.handles("down"));
+    }
+
+    #[test]
+    fn test_failure_reports_stderr() {
+        let failing = formatter(&["sh", "-c", "echo bad input >&2; exit 3"], None);
+        let error = failing.format("x").unwrap_err().to_string();
+        assert!(error.contains("bad input"), "{}", error);
+    }
+
+    #[test]
+    fn test_times_out() {
+        let slow = formatter(&["sleep", "5"], Some(0));
+        let error = slow.format("x").unwrap_err().to_string();
+        assert!(error.contains("timed out"), "{}", error);
+    }
+
+    #[test]
+    fn test_missing_program_and_empty_command() {
+        let missing = formatter(&["thoth-no-such-formatter"], None);
+        assert!(missing.format("x").is_err());
+
+        let empty = ExternalFormatterConfig {
+            command: Vec::new(),
+            aliases: Vec::new(),
+            timeout_secs: None,
+        };
+        assert!(CommandFormatter::new("empty", &empty).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+pub struct FormatPopup {
+    pub formats: Vec<String>,
+    pub selected_index: usize,
+    pub visible: bool,
+    /// Format the focused block was detected as when the popup was opened
+    pub detected: Option<String>,
+    /// True when the cursor is in a fenced code block, which is formatted on its own
+    pub code_block: bool,
+}
+
+impl FormatPopup {
+    pub fn new() -> Self {
+        FormatPopup {
+            formats: Vec::new(),
+            selected_index: 0,
+            visible: false,
+            detected: None,
+            code_block: false,
+        }
+    }
+
+    pub fn open(&mut self, formats: Vec<String>, detected: Option<&str>, code_block: bool) {
+        self.selected_index = detected
+            .and_then(|d| formats.iter().position(|f| f == d))
+            .unwrap_or(0);
+        self.formats = formats;
+        self.detected = detected.map(str::to_string);
+        self.code_block = code_block;
+        self.visible = true;
+    }
+
+    pub fn selected_format(&self) -> Option<&str> {
+        self.formats.get(self.selected_index).map(String::as_str)
+    }
+}
+
+impl Default for FormatPopup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_selects_detected_format() {
+        let mut popup = FormatPopup::new();
+        assert_eq!(popup.selected_format(), None);
+
+        popup.open(
+            vec!["json".to_string(), "yaml".to_string()],
+            Some("yaml"),
+            true,
+        );
+        assert!(popup.visible);
+        assert_eq!(popup.selected_format(), Some("yaml"));
+        assert_eq!(popup.detected.as_deref(), Some("yaml"));
+        assert!(popup.code_block);
+
+        popup.open(vec!["json".to_string(), "yaml".to_string()], None, false);
+        assert_eq!(popup.selected_format(), Some("json"));
+    }
+}
@@ -1,7 +1,99 @@
-use anyhow::Result;
+use std::fmt;
+
+use anyhow::{anyhow, Result};
 use pulldown_cmark::{Options, Parser};
 use pulldown_cmark_to_cmark::cmark;
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+use serde::Deserialize;
 use serde_json::Value;
+use sqlformat::{FormatOptions, Indent, QueryParams};
+
+use crate::config::ThothConfig;
+use crate::external_formatter::CommandFormatter;
+use crate::json_tools::sort_json_keys;
+
+/// A parse error pointing at a 1-based line and column of the formatter input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub language: &'static str,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn at_offset(
+        language: &'static str,
+        input: &str,
+        offset: usize,
+        message: String,
+    ) -> Self {
+        let (line, column) = line_and_column(input, offset);
+        ParseError {
+            language,
+            line,
+            column: Some(column),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(
+                f,
+                "Invalid {} at line {}, column {}: {}",
+                self.language, self.line, column, self.message
+            ),
+            None => write!(
+                f,
+                "Invalid {} at line {}: {}",
+                self.language, self.line, self.message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// 1-based line and character column of the byte `offset` in `input`.
+fn line_and_column(input: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(input.len());
+    while !input.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &input[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// Lines that carry content, skipping blanks and `#` comments.
+fn significant_lines(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+}
+
+pub trait Formatter {
+    /// Name shown in the format picker.
+    fn name(&self) -> &str;
+
+    /// True for code fence tokens this formatter handles, such as `yml`.
+    fn handles(&self, language: &str) -> bool {
+        self.name().eq_ignore_ascii_case(language)
+    }
+
+    /// True when `input` looks like this format.
+    fn detect(&self, _input: &str) -> bool {
+        false
+    }
+
+    fn format(&self, input: &str) -> Result<String>;
+}
 
 pub fn format_markdown(input: &str) -> Result<String> {
     let mut options = Options::empty();
@@ -16,7 +108,634 @@ pub fn format_markdown(input: &str) -> Result<String> {
     Ok(output)
 }
 
+/// Parses JSON, reporting failures as a [`ParseError`].
+pub fn parse_json(input: &str) -> Result<Value> {
+    Ok(serde_json::from_str(input).map_err(|e| ParseError {
+        language: "JSON",
+        line: e.line(),
+        column: Some(e.column()),
+        message: e.to_string(),
+    })?)
+}
+
+/// Pretty prints JSON with its keys sorted. serde_json keeps them in their original
+/// order, which the JSON tools' pretty print relies on, so they are sorted here.
 pub fn format_json(input: &str) -> Result<String> {
-    let parsed: Value = serde_json::from_str(input)?;
-    Ok(serde_json::to_string_pretty(&parsed)?)
+    sort_json_keys(input)
+}
+
+pub(crate) fn yaml_error(e: serde_yaml::Error) -> ParseError {
+    let (line, column) = e
+        .location()
+        .map_or((1, None), |l| (l.line(), Some(l.column())));
+    ParseError {
+        language: "YAML",
+        line,
+        column,
+        message: e.to_string(),
+    }
+}
+
+/// Reformats YAML by round-tripping it through `serde_yaml::Value`, which would drop
+/// comments and expand anchors, so input with either is refused.
+pub fn format_yaml(input: &str) -> Result<String> {
+    let mut documents = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(input) {
+        let value = serde_yaml::Value::deserialize(document).map_err(yaml_error)?;
+        documents.push(serde_yaml::to_string(&value)?);
+    }
+    if let Some((line, what)) = yaml_lossy_line(input) {
+        return Err(anyhow!(
+            "Formatting would remove the {} at line {}, so the YAML was left as it is",
+            what,
+            line
+        ));
+    }
+    Ok(documents.join("---\n").trim_end().to_string())
+}
+
+/// 1-based line of the first comment, anchor or alias in `input`, and which it is.
+/// Block scalars and quoted strings are skipped, as a `#` or `&` there is content.
+fn yaml_lossy_line(input: &str) -> Option<(usize, &'static str)> {
+    // Indent of the line that started the block scalar being skipped
+    let mut block_scalar: Option<usize> = None;
+    let mut quote: Option<char> = None;
+    for (number, line) in input.lines().enumerate() {
+        let indent = line.len() - line.trim_start().len();
+        if let Some(opener) = block_scalar {
+            if line.trim().is_empty() || indent > opener {
+                continue;
+            }
+            block_scalar = None;
+        }
+
+        let mut prev: Option<char> = None;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            // Tokens start after whitespace or flow punctuation, not inside plain scalars
+            let token_start = prev.is_none_or(|p| p.is_whitespace() || "[{,".contains(p));
+            match quote {
+                Some('"') if c == '\\' => {
+                    chars.next();
+                }
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c == '#' && prev.is_none_or(char::is_whitespace) => {
+                    return Some((number + 1, "comment"));
+                }
+                None if token_start && (c == '"' || c == '\'') => quote = Some(c),
+                None if token_start
+                    && (c == '&' || c == '*')
+                    && chars.peek().is_some_and(|next| !next.is_whitespace()) =>
+                {
+                    let what = if c == '&' { "anchor" } else { "alias" };
+                    return Some((number + 1, what));
+                }
+                None => {}
+            }
+            prev = Some(c);
+        }
+
+        let opens_block_scalar = quote.is_none()
+            && line.split_whitespace().last().is_some_and(|token| {
+                token.starts_with(['|', '>'])
+                    && token[1..]
+                        .chars()
+                        .all(|c| c == '-' || c == '+' || c.is_ascii_digit())
+            });
+        if opens_block_scalar {
+            block_scalar = Some(indent);
+        }
+    }
+    None
+}
+
+pub(crate) fn toml_error(input: &str, e: toml_edit::TomlError) -> ParseError {
+    let offset = e.span().map_or(0, |span| span.start);
+    ParseError::at_offset("TOML", input, offset, e.message().to_string())
+}
+
+pub fn format_toml(input: &str) -> Result<String> {
+    let mut document: toml_edit::DocumentMut = input
+        .parse()
+        .map_err(|e: toml_edit::TomlError| toml_error(input, e))?;
+    normalize_toml_table(document.as_table_mut());
+    Ok(document.to_string().trim().to_string())
+}
+
+fn decor_str(raw: Option<&toml_edit::RawString>) -> String {
+    raw.and_then(|r| r.as_str()).unwrap_or_default().to_string()
+}
+
+/// Puts single spaces around `=` at every nesting level, keeping comments intact.
+/// `DocumentMut::fmt` is not recursive and drops the comments above each key.
+fn normalize_toml_table(table: &mut toml_edit::Table) {
+    for (mut key, item) in table.iter_mut() {
+        match item {
+            toml_edit::Item::Value(value) => {
+                // Comment lines above a key live in the prefix of its decor
+                let prefix = decor_str(key.leaf_decor().prefix());
+                let leaf_decor = key.leaf_decor_mut();
+                leaf_decor.set_prefix(prefix.trim_end_matches([' ', '\t']));
+                leaf_decor.set_suffix(" ");
+
+                let comment = decor_str(value.decor().suffix()).trim().to_string();
+                value.decor_mut().set_prefix(" ");
+                if comment.is_empty() {
+                    value.decor_mut().set_suffix("");
+                } else {
+                    value.decor_mut().set_suffix(format!(" {}", comment));
+                }
+                match value {
+                    toml_edit::Value::InlineTable(inline) => inline.fmt(),
+                    toml_edit::Value::Array(array) => array.fmt(),
+                    _ => {}
+                }
+            }
+            toml_edit::Item::Table(child) => normalize_toml_table(child),
+            toml_edit::Item::ArrayOfTables(tables) => {
+                for child in tables.iter_mut() {
+                    normalize_toml_table(child);
+                }
+            }
+            toml_edit::Item::None => {}
+        }
+    }
+}
+
+pub fn format_xml(input: &str) -> Result<String> {
+    let mut reader = Reader::from_str(input);
+    reader.config_mut().trim_text(true);
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Eof) => break,
+            Ok(event) => writer.write_event(event)?,
+            Err(e) => {
+                return Err(ParseError::at_offset(
+                    "XML",
+                    input,
+                    reader.error_position(),
+                    e.to_string(),
+                )
+                .into())
+            }
+        }
+    }
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+pub fn format_sql(input: &str) -> Result<String> {
+    let options = FormatOptions {
+        indent: Indent::Spaces(2),
+        uppercase: true,
+        lines_between_queries: 2,
+    };
+    Ok(sqlformat::format(input, &QueryParams::None, options))
+}
+
+pub fn format_csv(input: &str) -> Result<String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in reader.records() {
+        let record = record.map_err(|e| {
+            let line = e.position().map_or(1, |p| p.line() as usize);
+            let message = match e.kind() {
+                csv::ErrorKind::UnequalLengths {
+                    expected_len, len, ..
+                } => format!("expected {} fields, found {}", expected_len, len),
+                _ => e.to_string(),
+            };
+            ParseError {
+                language: "CSV",
+                line,
+                column: None,
+                message,
+            }
+        })?;
+        writer.write_record(&record)?;
+    }
+    let output = writer.into_inner().map_err(|e| anyhow!(e.to_string()))?;
+    Ok(String::from_utf8(output)?.trim_end().to_string())
+}
+
+struct MarkdownFormatter;
+
+impl Formatter for MarkdownFormatter {
+    fn name(&self) -> &str {
+        "markdown"
+    }
+
+    fn handles(&self, language: &str) -> bool {
+        matches!(language.to_ascii_lowercase().as_str(), "markdown" | "md")
+    }
+
+    fn format(&self, input: &str) -> Result<String> {
+        format_markdown(input)
+    }
+}
+
+struct JsonFormatter;
+
+impl Formatter for JsonFormatter {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn detect(&self, input: &str) -> bool {
+        let trimmed = input.trim_start();
+        // TOML tables also start with `[`, so arrays have to parse to count
+        trimmed.starts_with('{')
+            || (trimmed.starts_with('[') && serde_json::from_str::<Value>(input).is_ok())
+    }
+
+    fn format(&self, input: &str) -> Result<String> {
+        format_json(input)
+    }
+}
+
+struct XmlFormatter;
+
+impl Formatter for XmlFormatter {
+    fn name(&self) -> &str {
+        "xml"
+    }
+
+    fn handles(&self, language: &str) -> bool {
+        matches!(
+            language.to_ascii_lowercase().as_str(),
+            "xml" | "html" | "svg" | "xhtml"
+        )
+    }
+
+    fn detect(&self, input: &str) -> bool {
+        input.trim_start().starts_with('<')
+    }
+
+    fn format(&self, input: &str) -> Result<String> {
+        format_xml(input)
+    }
+}
+
+struct SqlFormatter;
+
+const SQL_KEYWORDS: &[&str] = &[
+    "select", "insert", "update", "delete", "create", "alter", "drop", "with", "explain",
+];
+
+impl Formatter for SqlFormatter {
+    fn name(&self) -> &str {
+        "sql"
+    }
+
+    fn detect(&self, input: &str) -> bool {
+        let first_word = input
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        SQL_KEYWORDS.contains(&first_word.as_str())
+    }
+
+    fn format(&self, input: &str) -> Result<String> {
+        format_sql(input)
+    }
+}
+
+struct TomlFormatter;
+
+fn is_toml_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    if trimmed.starts_with('[') && trimmed.ends_with(']') {
+        return true;
+    }
+    // Continuation lines of multi-line arrays and strings
+    if line.starts_with(char::is_whitespace) || trimmed.starts_with([']', '"', '\'']) {
+        return true;
+    }
+    match trimmed.split_once('=') {
+        Some((key, _)) => {
+            let key = key.trim();
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '"' | '\''))
+        }
+        None => false,
+    }
+}
+
+impl Formatter for TomlFormatter {
+    fn name(&self) -> &str {
+        "toml"
+    }
+
+    fn detect(&self, input: &str) -> bool {
+        let mut lines = significant_lines(input).peekable();
+        lines.peek().is_some() && lines.all(is_toml_line)
+    }
+
+    fn format(&self, input: &str) -> Result<String> {
+        format_toml(input)
+    }
+}
+
+struct YamlFormatter;
+
+fn is_yaml_mapping_line(line: &str) -> bool {
+    match line.trim_start().trim_start_matches("- ").split_once(':') {
+        Some((key, value)) => {
+            !key.is_empty()
+                && !key.contains(char::is_whitespace)
+                && (value.is_empty() || value.starts_with(' '))
+        }
+        None => false,
+    }
+}
+
+impl Formatter for YamlFormatter {
+    fn name(&self) -> &str {
+        "yaml"
+    }
+
+    fn handles(&self, language: &str) -> bool {
+        matches!(language.to_ascii_lowercase().as_str(), "yaml" | "yml")
+    }
+
+    fn detect(&self, input: &str) -> bool {
+        let lines: Vec<&str> = significant_lines(input).collect();
+        // Plain bullet lists are far more likely to be markdown, so require a mapping
+        lines.iter().any(|line| is_yaml_mapping_line(line))
+            && lines.iter().all(|line| {
+                *line == "---"
+                    || line.starts_with(char::is_whitespace)
+                    || line.starts_with("- ")
+                    || is_yaml_mapping_line(line)
+            })
+    }
+
+    fn format(&self, input: &str) -> Result<String> {
+        format_yaml(input)
+    }
+}
+
+struct CsvFormatter;
+
+impl Formatter for CsvFormatter {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    fn detect(&self, input: &str) -> bool {
+        let lines: Vec<&str> = input.lines().filter(|l| !l.trim().is_empty()).collect();
+        lines.len() > 1 && lines.iter().all(|line| line.contains(','))
+    }
+
+    fn format(&self, input: &str) -> Result<String> {
+        format_csv(input)
+    }
+}
+
+/// Formatters available to the TUI, in detection order.
+pub struct FormatterRegistry {
+    formatters: Vec<Box<dyn Formatter>>,
+}
+
+impl FormatterRegistry {
+    pub fn new() -> Self {
+        FormatterRegistry {
+            formatters: Vec::new(),
+        }
+    }
+
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(JsonFormatter));
+        registry.register(Box::new(XmlFormatter));
+        registry.register(Box::new(SqlFormatter));
+        registry.register(Box::new(TomlFormatter));
+        registry.register(Box::new(YamlFormatter));
+        registry.register(Box::new(CsvFormatter));
+        registry.register(Box::new(MarkdownFormatter));
+        registry
+    }
+
+    /// Built-in formatters plus the external commands from the `[formatters]` config
+    /// table. A command registered under a built-in name, such as `json`, replaces it.
+    pub fn from_config(config: &ThothConfig) -> Result<Self> {
+        let mut registry = Self::with_builtins();
+        for (name, formatter) in &config.formatters {
+            registry.register(Box::new(CommandFormatter::new(name, formatter)?));
+        }
+        Ok(registry)
+    }
+
+    /// Adds a formatter, replacing any registered under the same name.
+    pub fn register(&mut self, formatter: Box<dyn Formatter>) {
+        match self
+            .formatters
+            .iter()
+            .position(|f| f.name() == formatter.name())
+        {
+            Some(index) => self.formatters[index] = formatter,
+            None => self.formatters.push(formatter),
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.formatters
+            .iter()
+            .map(|f| f.name().to_string())
+            .collect()
+    }
+
+    /// Looks a formatter up by name or by a code fence token it handles.
+    pub fn get(&self, language: &str) -> Option<&dyn Formatter> {
+        self.formatters
+            .iter()
+            .find(|f| f.name() == language)
+            .or_else(|| self.formatters.iter().find(|f| f.handles(language)))
+            .map(|f| f.as_ref())
+    }
+
+    /// The first formatter whose format `input` looks like.
+    pub fn detect(&self, input: &str) -> Option<&dyn Formatter> {
+        self.formatters
+            .iter()
+            .find(|f| f.detect(input))
+            .map(|f| f.as_ref())
+    }
+
+    pub fn format(&self, language: &str, input: &str) -> Result<String> {
+        match self.get(language) {
+            Some(formatter) => formatter.format(input),
+            None => Err(anyhow!("No formatter for '{}'", language)),
+        }
+    }
+}
+
+impl Default for FormatterRegistry {
+    fn default() -> Self {
+        Self::with_builtins()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(result: Result<String>) -> ParseError {
+        result.unwrap_err().downcast::<ParseError>().unwrap()
+    }
+
+    #[test]
+    fn test_line_and_column() {
+        assert_eq!(line_and_column("ab\ncd", 0), (1, 1));
+        assert_eq!(line_and_column("ab\ncd", 4), (2, 2));
+        assert_eq!(line_and_column("é\nx", 1), (1, 1));
+        assert_eq!(line_and_column("ab", 99), (1, 3));
+    }
+
+    #[test]
+    fn test_format_json_sorts_keys() {
+        let formatted = format_json(r#"{"b": 1, "a": {"d": [{"f": 1, "e": 2}], "c": 2}}"#).unwrap();
+        assert_eq!(
+            formatted,
+            "{\n  \"a\": {\n    \"c\": 2,\n    \"d\": [\n      {\n        \"e\": 2,\n        \"f\": 1\n      }\n    ]\n  },\n  \"b\": 1\n}"
+        );
+    }
+
+    #[test]
+    fn test_format_json_error_position() {
+        let error = parse_error(format_json("{\n  \"a\": 1,\n}"));
+        assert_eq!(error.line, 3);
+        assert_eq!(error.column, Some(1));
+        assert!(error
+            .to_string()
+            .starts_with("Invalid JSON at line 3, column 1"));
+    }
+
+    #[test]
+    fn test_format_yaml() {
+        let formatted = format_yaml("a:   1\nb:\n    - x\n    - y\n").unwrap();
+        assert_eq!(formatted, "a: 1\nb:\n- x\n- y");
+
+        let documents = format_yaml("a: 1\n---\nb: 2").unwrap();
+        assert_eq!(documents, "a: 1\n---\nb: 2");
+
+        let error = parse_error(format_yaml("a: 1\nb: [1, 2\n"));
+        assert_eq!(error.language, "YAML");
+        assert!(error.line >= 2);
+    }
+
+    #[test]
+    fn test_format_yaml_keeps_comments_and_anchors() {
+        let manifest = "apiVersion: v1\nkind: ConfigMap\n# settings for prod\ndata:\n  port: \"80\"   # http\n";
+        let error = format_yaml(manifest).unwrap_err().to_string();
+        assert!(error.contains("comment at line 3"), "{}", error);
+
+        let anchors = "base: &base\n  image: nginx\nweb:\n  <<: *base\n";
+        let error = format_yaml(anchors).unwrap_err().to_string();
+        assert!(error.contains("anchor at line 1"), "{}", error);
+
+        // Only content that looks like them
+        let content = "url: http://x/#top\nquoted: \"# not a comment\"\nmath: a&b\nscript: |\n  # shell comment\n  echo *\nglob: '*.rs'\n";
+        assert!(format_yaml(content).is_ok());
+    }
+
+    #[test]
+    fn test_format_toml() {
+        let formatted = format_toml(
+            "# settings\n[server]\n# the port\n  port=8080 # comment\nhost  =  \"x\"\nlist=[1,2]",
+        )
+        .unwrap();
+        assert_eq!(
+            formatted,
+            "# settings\n[server]\n# the port\nport = 8080 # comment\nhost = \"x\"\nlist = [1, 2]"
+        );
+
+        let error = parse_error(format_toml("a = 1\nb = \n"));
+        assert_eq!(error.line, 2);
+        assert_eq!(error.column, Some(5));
+    }
+
+    #[test]
+    fn test_format_xml() {
+        let formatted = format_xml("<a><b>text</b><c/></a>").unwrap();
+        assert_eq!(formatted, "<a>\n  <b>text</b>\n  <c/>\n</a>");
+
+        let error = parse_error(format_xml("<a>\n  <b></c>\n</a>"));
+        assert_eq!(error.language, "XML");
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn test_format_sql() {
+        let formatted = format_sql("select a, b from t where a = 1").unwrap();
+        assert_eq!(formatted, "SELECT\n  a,\n  b\nFROM\n  t\nWHERE\n  a = 1");
+    }
+
+    #[test]
+    fn test_format_csv() {
+        let formatted = format_csv("a , b\n\"x\",  y  \n").unwrap();
+        assert_eq!(formatted, "a,b\nx,y");
+
+        let error = parse_error(format_csv("a,b\n1,2\n3\n"));
+        assert_eq!(error.line, 3);
+        assert_eq!(error.column, None);
+        assert_eq!(
+            error.to_string(),
+            "Invalid CSV at line 3: expected 2 fields, found 1"
+        );
+    }
+
+    #[test]
+    fn test_detect() {
+        let registry = FormatterRegistry::with_builtins();
+        let detect = |input: &str| registry.detect(input).map(|f| f.name().to_string());
+
+        assert_eq!(detect("{\"a\": 1}").as_deref(), Some("json"));
+        assert_eq!(detect("[1, 2]").as_deref(), Some("json"));
+        assert_eq!(detect("[server]\nport = 1").as_deref(), Some("toml"));
+        assert_eq!(detect("<root/>").as_deref(), Some("xml"));
+        assert_eq!(detect("SELECT * FROM t").as_deref(), Some("sql"));
+        assert_eq!(
+            detect("name: thoth\nitems:\n  - a").as_deref(),
+            Some("yaml")
+        );
+        assert_eq!(detect("a,b\n1,2").as_deref(), Some("csv"));
+        assert_eq!(detect("# Notes\n\n- buy milk\n- eggs"), None);
+        assert_eq!(detect("Some prose, with a comma."), None);
+    }
+
+    #[test]
+    fn test_registry_from_config() {
+        let config = ThothConfig::parse(
+            "[formatters.rust]\ncommand = [\"rustfmt\"]\naliases = [\"rs\"]\n[formatters.json]\ncommand = [\"jq\", \".\"]",
+        )
+        .unwrap();
+        let registry = FormatterRegistry::from_config(&config).unwrap();
+        assert_eq!(registry.get("rs").unwrap().name(), "rust");
+        let builtins = FormatterRegistry::with_builtins().names().len();
+        assert_eq!(registry.names().len(), builtins + 1);
+
+        let empty = ThothConfig::parse("[formatters.bad]\ncommand = []").unwrap();
+        assert!(FormatterRegistry::from_config(&empty).is_err());
+    }
+
+    #[test]
+    fn test_registry_lookup() {
+        let mut registry = FormatterRegistry::with_builtins();
+        assert_eq!(registry.get("yml").unwrap().name(), "yaml");
+        assert_eq!(registry.get("md").unwrap().name(), "markdown");
+        assert!(registry.get("cobol").is_none());
+        assert!(registry.format("cobol", "").is_err());
+
+        let count = registry.names().len();
+        registry.register(Box::new(JsonFormatter));
+        assert_eq!(registry.names().len(), count);
+    }
 }
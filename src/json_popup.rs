@@ -0,0 +1,73 @@
+use crate::json_tools::JsonOperation;
+
+const QUERY_LABEL: &str = "Query path into a new block";
+
+/// Menu of the JSON toolbox. Choosing the query entry switches the popup into a path prompt.
+pub struct JsonPopup {
+    pub selected_index: usize,
+    pub visible: bool,
+    /// True when the cursor is in a fenced code block, which is transformed on its own
+    pub code_block: bool,
+    /// Path typed after choosing the query entry
+    pub query: Option<String>,
+}
+
+impl JsonPopup {
+    pub fn new() -> Self {
+        JsonPopup {
+            selected_index: 0,
+            visible: false,
+            code_block: false,
+            query: None,
+        }
+    }
+
+    pub fn open(&mut self, code_block: bool) {
+        self.selected_index = 0;
+        self.code_block = code_block;
+        self.query = None;
+        self.visible = true;
+    }
+
+    pub fn close(&mut self) {
+        self.visible = false;
+        self.query = None;
+    }
+
+    pub fn labels(&self) -> Vec<&'static str> {
+        JsonOperation::ALL
+            .iter()
+            .map(JsonOperation::label)
+            .chain(std::iter::once(QUERY_LABEL))
+            .collect()
+    }
+
+    /// The selected operation, or `None` when the query entry is selected.
+    pub fn selected_operation(&self) -> Option<JsonOperation> {
+        JsonOperation::ALL.get(self.selected_index).copied()
+    }
+}
+
+impl Default for JsonPopup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_is_the_last_entry() {
+        let mut popup = JsonPopup::new();
+        popup.open(true);
+        assert_eq!(popup.selected_operation(), Some(JsonOperation::Pretty));
+        popup.selected_index = popup.labels().len() - 1;
+        assert_eq!(popup.selected_operation(), None);
+        popup.query = Some(".a".to_string());
+        popup.close();
+        assert!(!popup.visible);
+        assert_eq!(popup.query, None);
+    }
+}
@@ -0,0 +1,485 @@
+use std::fmt;
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+
+use crate::formatter::{parse_json, yaml_error, ParseError};
+
+/// A transformation from the JSON toolbox, applied to a whole block or a code fence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonOperation {
+    Pretty,
+    Minify,
+    SortKeys,
+    ToYaml,
+    FromYaml,
+    ToToml,
+    FromToml,
+    Escape,
+    Unescape,
+}
+
+impl JsonOperation {
+    pub const ALL: [JsonOperation; 9] = [
+        JsonOperation::Pretty,
+        JsonOperation::Minify,
+        JsonOperation::SortKeys,
+        JsonOperation::ToYaml,
+        JsonOperation::FromYaml,
+        JsonOperation::ToToml,
+        JsonOperation::FromToml,
+        JsonOperation::Escape,
+        JsonOperation::Unescape,
+    ];
+
+    /// Name shown in the JSON tools menu.
+    pub fn label(&self) -> &'static str {
+        match self {
+            JsonOperation::Pretty => "Pretty print",
+            JsonOperation::Minify => "Minify",
+            JsonOperation::SortKeys => "Sort keys",
+            JsonOperation::ToYaml => "JSON to YAML",
+            JsonOperation::FromYaml => "YAML to JSON",
+            JsonOperation::ToToml => "JSON to TOML",
+            JsonOperation::FromToml => "TOML to JSON",
+            JsonOperation::Escape => "Escape as JSON string",
+            JsonOperation::Unescape => "Unescape JSON string",
+        }
+    }
+
+    /// Language of the output when it differs from the input, used to relabel code fences.
+    pub fn output_language(&self) -> Option<&'static str> {
+        match self {
+            JsonOperation::ToYaml => Some("yaml"),
+            JsonOperation::ToToml => Some("toml"),
+            JsonOperation::FromYaml | JsonOperation::FromToml => Some("json"),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, input: &str) -> Result<String> {
+        match self {
+            JsonOperation::Pretty => Ok(serde_json::to_string_pretty(&parse_json(input)?)?),
+            JsonOperation::Minify => minify_json(input),
+            JsonOperation::SortKeys => sort_json_keys(input),
+            JsonOperation::ToYaml => json_to_yaml(input),
+            JsonOperation::FromYaml => yaml_to_json(input),
+            JsonOperation::ToToml => json_to_toml(input),
+            JsonOperation::FromToml => toml_to_json(input),
+            JsonOperation::Escape => Ok(escape_json_string(input)),
+            JsonOperation::Unescape => unescape_json_string(input),
+        }
+    }
+}
+
+pub fn minify_json(input: &str) -> Result<String> {
+    Ok(serde_json::to_string(&parse_json(input)?)?)
+}
+
+/// Pretty prints with the keys of every object, nested ones included, in lexical order.
+pub fn sort_json_keys(input: &str) -> Result<String> {
+    let mut value = parse_json(input)?;
+    sort_keys(&mut value);
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = std::mem::take(map).into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, mut item) in entries {
+                sort_keys(&mut item);
+                map.insert(key, item);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+pub fn json_to_yaml(input: &str) -> Result<String> {
+    let yaml = serde_yaml::to_string(&parse_json(input)?)?;
+    Ok(yaml.trim_end().to_string())
+}
+
+pub fn yaml_to_json(input: &str) -> Result<String> {
+    let value: Value = serde_yaml::from_str(input).map_err(yaml_error)?;
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+pub fn json_to_toml(input: &str) -> Result<String> {
+    let table = match json_to_toml_value(&parse_json(input)?, "")? {
+        toml::Value::Table(table) => table,
+        _ => bail!("Only a JSON object can be converted to TOML"),
+    };
+    Ok(toml::to_string_pretty(&table)?.trim_end().to_string())
+}
+
+/// TOML has no null, so nulls are reported with their path instead of silently dropped.
+fn json_to_toml_value(value: &Value, path: &str) -> Result<toml::Value> {
+    Ok(match value {
+        Value::Null => bail!(
+            "TOML has no null value, found one at '{}'",
+            if path.is_empty() { "." } else { path }
+        ),
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            None => toml::Value::Float(
+                n.as_f64()
+                    .ok_or_else(|| anyhow!("Number {} does not fit in TOML", n))?,
+            ),
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Array(items) => toml::Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| json_to_toml_value(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(map) => toml::Value::Table(
+            map.iter()
+                .map(|(key, item)| {
+                    let value = json_to_toml_value(item, &format!("{}.{}", path, key))?;
+                    Ok((key.clone(), value))
+                })
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+pub fn toml_to_json(input: &str) -> Result<String> {
+    let table: toml::Table = toml::from_str(input).map_err(|e| {
+        let offset = e.span().map_or(0, |span| span.start);
+        ParseError::at_offset("TOML", input, offset, e.message().to_string())
+    })?;
+    let value = toml_to_json_value(toml::Value::Table(table));
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+fn toml_to_json_value(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        // JSON has no date type, so datetimes keep their TOML spelling
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => {
+            Value::Array(items.into_iter().map(toml_to_json_value).collect())
+        }
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json_value(value)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+/// Quotes `input` as a JSON string literal.
+pub fn escape_json_string(input: &str) -> String {
+    Value::String(input.to_string()).to_string()
+}
+
+/// Decodes a JSON string literal. The surrounding quotes are optional so escaped
+/// text copied out of a larger document can be decoded as is.
+pub fn unescape_json_string(input: &str) -> Result<String> {
+    let trimmed = input.trim();
+    let literal = if trimmed.len() >= 2 && trimmed.starts_with('"') && trimmed.ends_with('"') {
+        trimmed.to_string()
+    } else {
+        format!("\"{}\"", trimmed)
+    };
+    match parse_json(&literal)? {
+        Value::String(s) => Ok(s),
+        _ => bail!("Input is not a JSON string"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathSegment {
+    Key(String),
+    Index(i64),
+    Iterate,
+}
+
+/// Error in a query path, pointing at a 1-based character position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid query at position {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Parses a jq-style path: `.`, `.key`, `."quoted key"`, `[0]`, `[-1]`, `["key"]` and `[]`.
+fn parse_query(query: &str) -> std::result::Result<Vec<PathSegment>, QueryError> {
+    let chars: Vec<char> = query.trim().chars().collect();
+    let error = |position: usize, message: &str| QueryError {
+        position: position + 1,
+        message: message.to_string(),
+    };
+    if chars.first() != Some(&'.') {
+        return Err(error(0, "a query starts with '.'"));
+    }
+
+    let mut segments = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                i += 1;
+                match chars.get(i) {
+                    Some('"') => {
+                        let (key, next) = parse_quoted(&chars, i).map_err(|m| error(i, m))?;
+                        segments.push(PathSegment::Key(key));
+                        i = next;
+                    }
+                    Some(c) if c.is_alphabetic() || *c == '_' => {
+                        let start = i;
+                        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                            i += 1;
+                        }
+                        segments.push(PathSegment::Key(chars[start..i].iter().collect()));
+                    }
+                    // `.` on its own, `.[0]` and `..` style chains are all allowed
+                    Some('[') | None => {}
+                    Some(_) => return Err(error(i, "expected a key after '.'")),
+                }
+            }
+            '[' => {
+                i += 1;
+                match chars.get(i) {
+                    Some(']') => {
+                        segments.push(PathSegment::Iterate);
+                        i += 1;
+                    }
+                    Some('"') => {
+                        let (key, next) = parse_quoted(&chars, i).map_err(|m| error(i, m))?;
+                        if chars.get(next) != Some(&']') {
+                            return Err(error(next, "expected ']'"));
+                        }
+                        segments.push(PathSegment::Key(key));
+                        i = next + 1;
+                    }
+                    _ => {
+                        let start = i;
+                        while i < chars.len() && chars[i] != ']' {
+                            i += 1;
+                        }
+                        if i == chars.len() {
+                            return Err(error(start - 1, "unclosed '['"));
+                        }
+                        let index: String = chars[start..i].iter().collect();
+                        let index = index
+                            .trim()
+                            .parse()
+                            .map_err(|_| error(start, "expected an array index, a key or ']'"))?;
+                        segments.push(PathSegment::Index(index));
+                        i += 1;
+                    }
+                }
+            }
+            _ => return Err(error(i, "expected '.' or '['")),
+        }
+    }
+    Ok(segments)
+}
+
+/// Reads the JSON string literal starting at `start`, returning it and the index after it.
+fn parse_quoted(
+    chars: &[char],
+    start: usize,
+) -> std::result::Result<(String, usize), &'static str> {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => {
+                let literal: String = chars[start..=i].iter().collect();
+                let key = serde_json::from_str(&literal).map_err(|_| "invalid quoted key")?;
+                return Ok((key, i + 1));
+            }
+            _ => i += 1,
+        }
+    }
+    Err("unclosed '\"'")
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn apply_segment(value: Value, segment: &PathSegment) -> Result<Vec<Value>> {
+    Ok(match (segment, value) {
+        (PathSegment::Key(key), Value::Object(mut map)) => {
+            vec![map.remove(key).unwrap_or(Value::Null)]
+        }
+        (PathSegment::Index(index), Value::Array(mut items)) => {
+            let len = items.len() as i64;
+            let index = if *index < 0 { len + index } else { *index };
+            if (0..len).contains(&index) {
+                vec![items.swap_remove(index as usize)]
+            } else {
+                vec![Value::Null]
+            }
+        }
+        (PathSegment::Iterate, Value::Array(items)) => items,
+        (PathSegment::Iterate, Value::Object(map)) => map.into_iter().map(|(_, v)| v).collect(),
+        // Like jq, looking anything up in null yields null
+        (PathSegment::Key(_) | PathSegment::Index(_), Value::Null) => vec![Value::Null],
+        (PathSegment::Key(key), other) => {
+            bail!("Cannot index {} with \"{}\"", type_name(&other), key)
+        }
+        (PathSegment::Index(index), other) => {
+            bail!("Cannot index {} with {}", type_name(&other), index)
+        }
+        (PathSegment::Iterate, other) => bail!("Cannot iterate over {}", type_name(&other)),
+    })
+}
+
+/// Runs a jq-style path query such as `.items[].name` and pretty prints the result.
+/// Queries containing `[]` produce many values, which are collected into an array.
+pub fn query_json(input: &str, query: &str) -> Result<String> {
+    let segments = parse_query(query)?;
+    let mut values = vec![parse_json(input)?];
+    for segment in &segments {
+        let mut next = Vec::new();
+        for value in values {
+            next.extend(apply_segment(value, segment)?);
+        }
+        values = next;
+    }
+    let result = if segments.contains(&PathSegment::Iterate) {
+        Value::Array(values)
+    } else {
+        values.pop().unwrap_or(Value::Null)
+    };
+    Ok(serde_json::to_string_pretty(&result)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str =
+        r#"{"zeta": 1, "items": [{"name": "a", "id": 2}, {"name": "b", "id": 3}], "alpha": null}"#;
+
+    #[test]
+    fn test_minify_keeps_key_order() {
+        assert_eq!(
+            minify_json("{\n  \"b\": [1, 2],\n  \"a\": {}\n}").unwrap(),
+            r#"{"b":[1,2],"a":{}}"#
+        );
+    }
+
+    #[test]
+    fn test_sort_keys_is_recursive() {
+        let sorted = sort_json_keys(r#"{"b": {"y": 1, "x": 2}, "a": [{"d": 1, "c": 2}]}"#).unwrap();
+        assert_eq!(
+            minify_json(&sorted).unwrap(),
+            r#"{"a":[{"c":2,"d":1}],"b":{"x":2,"y":1}}"#
+        );
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let yaml = json_to_yaml(r#"{"name": "thoth", "tags": ["a", "b"]}"#).unwrap();
+        assert_eq!(yaml, "name: thoth\ntags:\n- a\n- b");
+        assert_eq!(
+            minify_json(&yaml_to_json(&yaml).unwrap()).unwrap(),
+            r#"{"name":"thoth","tags":["a","b"]}"#
+        );
+        assert!(yaml_to_json("a: [1")
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid YAML"));
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let toml = json_to_toml(r#"{"server": {"port": 80}, "name": "thoth"}"#).unwrap();
+        assert_eq!(toml, "name = \"thoth\"\n\n[server]\nport = 80");
+        assert_eq!(
+            minify_json(&toml_to_json(&toml).unwrap()).unwrap(),
+            r#"{"name":"thoth","server":{"port":80}}"#
+        );
+        assert_eq!(
+            toml_to_json("when = 1979-05-27").unwrap(),
+            "{\n  \"when\": \"1979-05-27\"\n}"
+        );
+    }
+
+    #[test]
+    fn test_toml_rejects_null_and_non_objects() {
+        let error = json_to_toml(r#"{"a": {"b": [1, null]}}"#).unwrap_err();
+        assert!(error.to_string().contains(".a.b[1]"), "{}", error);
+        assert!(json_to_toml("[1]").is_err());
+    }
+
+    #[test]
+    fn test_escape_and_unescape() {
+        let text = "line \"one\"\n\ttwo";
+        let escaped = escape_json_string(text);
+        assert_eq!(escaped, r#""line \"one\"\n\ttwo""#);
+        assert_eq!(unescape_json_string(&escaped).unwrap(), text);
+        assert_eq!(unescape_json_string(r#"a\nb"#).unwrap(), "a\nb");
+        assert!(unescape_json_string(r#""bad \q""#).is_err());
+    }
+
+    #[test]
+    fn test_query_paths() {
+        assert_eq!(
+            minify_json(&query_json(RESPONSE, ".items[].name").unwrap()).unwrap(),
+            r#"["a","b"]"#
+        );
+        assert_eq!(query_json(RESPONSE, ".items[-1].id").unwrap(), "3");
+        assert_eq!(query_json(RESPONSE, ".items[5]").unwrap(), "null");
+        assert_eq!(query_json(RESPONSE, ".[\"zeta\"]").unwrap(), "1");
+        assert_eq!(query_json(RESPONSE, ".\"zeta\"").unwrap(), "1");
+        assert_eq!(query_json(RESPONSE, ".alpha.missing").unwrap(), "null");
+        assert_eq!(
+            query_json("[[1], [2]]", ".[][0]").unwrap(),
+            "[\n  1,\n  2\n]"
+        );
+        assert_eq!(
+            minify_json(&query_json(RESPONSE, ".").unwrap()).unwrap(),
+            minify_json(RESPONSE).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_query_errors() {
+        assert_eq!(
+            query_json(RESPONSE, ".zeta.name").unwrap_err().to_string(),
+            "Cannot index number with \"name\""
+        );
+        assert_eq!(
+            query_json(RESPONSE, "items").unwrap_err().to_string(),
+            "Invalid query at position 1: a query starts with '.'"
+        );
+        assert!(query_json(RESPONSE, ".items[").is_err());
+        assert!(query_json(RESPONSE, ".items[x]").is_err());
+        assert!(query_json(RESPONSE, ".zeta[]").is_err());
+    }
+}
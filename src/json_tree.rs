@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
+use serde_json::Value;
+
+use crate::theme::UiPalette;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PathKey {
+    Key(String),
+    Index(usize),
+}
+
+/// A node shown in the tree. Children of collapsed containers get no row.
+#[derive(Debug, Clone, PartialEq)]
+struct TreeRow {
+    path: Vec<PathKey>,
+    depth: usize,
+    /// Number of children for objects and arrays, `None` for scalars
+    children: Option<usize>,
+}
+
+/// Collapsible view of a JSON document. Only the root is expanded at first.
+pub struct JsonTree {
+    root: Value,
+    expanded: HashSet<Vec<PathKey>>,
+    rows: Vec<TreeRow>,
+    pub selected: usize,
+    pub scroll: usize,
+}
+
+impl JsonTree {
+    pub fn parse(input: &str) -> Option<Self> {
+        let root: Value = serde_json::from_str(input).ok()?;
+        let mut expanded = HashSet::new();
+        expanded.insert(Vec::new());
+        let mut tree = JsonTree {
+            root,
+            expanded,
+            rows: Vec::new(),
+            selected: 0,
+            scroll: 0,
+        };
+        tree.rebuild_rows();
+        Some(tree)
+    }
+
+    /// Carries the expanded nodes and the selection of `previous` over, as far
+    /// as they still exist, so an edited document keeps its shape.
+    pub fn restore(&mut self, previous: JsonTree) {
+        let selected_path = previous.rows.get(previous.selected).map(|r| r.path.clone());
+        self.expanded = previous.expanded;
+        self.expanded.insert(Vec::new());
+        self.rebuild_rows();
+        self.selected = selected_path
+            .and_then(|path| self.rows.iter().position(|row| row.path == path))
+            .unwrap_or(previous.selected)
+            .min(self.rows.len().saturating_sub(1));
+        self.scroll = previous.scroll;
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn rebuild_rows(&mut self) {
+        let mut rows = Vec::new();
+        let mut path = Vec::new();
+        self.push_rows(&self.root, &mut path, &mut rows);
+        self.rows = rows;
+    }
+
+    fn push_rows(&self, value: &Value, path: &mut Vec<PathKey>, rows: &mut Vec<TreeRow>) {
+        let children = match value {
+            Value::Object(map) => Some(map.len()),
+            Value::Array(items) => Some(items.len()),
+            _ => None,
+        };
+        rows.push(TreeRow {
+            path: path.clone(),
+            depth: path.len(),
+            children,
+        });
+        if !self.expanded.contains(path) {
+            return;
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    path.push(PathKey::Key(key.clone()));
+                    self.push_rows(child, path, rows);
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                for (index, child) in items.iter().enumerate() {
+                    path.push(PathKey::Index(index));
+                    self.push_rows(child, path, rows);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn value_at(&self, path: &[PathKey]) -> Option<&Value> {
+        path.iter().try_fold(&self.root, |value, key| match key {
+            PathKey::Key(key) => value.get(key),
+            PathKey::Index(index) => value.get(index),
+        })
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.rows.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn select_first(&mut self) {
+        self.selected = 0;
+    }
+
+    pub fn select_last(&mut self) {
+        self.selected = self.rows.len().saturating_sub(1);
+    }
+
+    /// Expands the selected container, or moves into it when it is already expanded.
+    pub fn expand(&mut self) {
+        let row = match self.rows.get(self.selected) {
+            Some(row) if row.children.is_some() => row,
+            _ => return,
+        };
+        if self.expanded.contains(&row.path) {
+            if row.children != Some(0) {
+                self.selected += 1;
+            }
+        } else {
+            self.expanded.insert(row.path.clone());
+            self.rebuild_rows();
+        }
+    }
+
+    /// Collapses the selected container, or moves to the parent of anything else.
+    pub fn collapse(&mut self) {
+        let row = match self.rows.get(self.selected) {
+            Some(row) => row.clone(),
+            None => return,
+        };
+        if row.children.is_some() && self.expanded.contains(&row.path) {
+            self.expanded.remove(&row.path);
+            self.rebuild_rows();
+        } else if let Some(parent) = row.path.split_last().map(|(_, parent)| parent) {
+            if let Some(index) = self.rows.iter().position(|r| r.path == parent) {
+                self.selected = index;
+            }
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        match self.rows.get(self.selected) {
+            Some(row) if row.children.is_some() && self.expanded.contains(&row.path) => {
+                self.collapse()
+            }
+            Some(_) => self.expand(),
+            None => {}
+        }
+    }
+
+    /// jq-style path of the selected node, e.g. `.items[0]."display name"`.
+    pub fn selected_path(&self) -> String {
+        let path = match self.rows.get(self.selected) {
+            Some(row) if !row.path.is_empty() => &row.path,
+            _ => return String::from("."),
+        };
+        path.iter()
+            .map(|key| match key {
+                PathKey::Key(key) if is_identifier(key) => format!(".{}", key),
+                PathKey::Key(key) => format!(".{}", Value::String(key.clone())),
+                PathKey::Index(index) => format!("[{}]", index),
+            })
+            .collect()
+    }
+
+    /// The selected node as pretty printed JSON, strings without their quotes.
+    pub fn selected_value(&self) -> String {
+        let value = self
+            .rows
+            .get(self.selected)
+            .and_then(|row| self.value_at(&row.path));
+        match value {
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => serde_json::to_string_pretty(value).unwrap_or_default(),
+            None => String::new(),
+        }
+    }
+
+    /// Keeps the selection within `height` rows and returns the lines to draw.
+    pub fn render_lines(&mut self, height: usize, palette: &UiPalette) -> Vec<Line<'static>> {
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if height > 0 && self.selected >= self.scroll + height {
+            self.scroll = self.selected + 1 - height;
+        }
+        self.rows
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(height)
+            .map(|(i, row)| self.render_row(row, i == self.selected, palette))
+            .collect()
+    }
+
+    fn render_row(&self, row: &TreeRow, selected: bool, palette: &UiPalette) -> Line<'static> {
+        let value = self.value_at(&row.path).unwrap_or(&Value::Null);
+        let expanded = self.expanded.contains(&row.path);
+        let marker = match row.children {
+            Some(_) if expanded => "▾ ",
+            Some(_) => "▸ ",
+            None => "  ",
+        };
+        let mut spans = vec![Span::raw(format!("{}{}", "  ".repeat(row.depth), marker))];
+        match row.path.last() {
+            Some(PathKey::Key(key)) => {
+                spans.push(Span::styled(
+                    key.clone(),
+                    Style::default().fg(palette.highlight),
+                ));
+                spans.push(Span::raw(": "));
+            }
+            Some(PathKey::Index(index)) => {
+                spans.push(Span::styled(
+                    format!("[{}]", index),
+                    Style::default().fg(palette.gutter),
+                ));
+                spans.push(Span::raw(": "));
+            }
+            None => {}
+        }
+        match (value, row.children) {
+            (Value::Object(_), Some(count)) => {
+                spans.push(Span::raw(if expanded { "{" } else { "{…}" }));
+                spans.push(child_count(count, "key", palette));
+            }
+            (Value::Array(_), Some(count)) => {
+                spans.push(Span::raw(if expanded { "[" } else { "[…]" }));
+                spans.push(child_count(count, "item", palette));
+            }
+            (scalar, _) => spans.push(Span::styled(
+                scalar.to_string(),
+                Style::default().fg(palette.text_fg),
+            )),
+        }
+
+        let line = Line::from(spans);
+        if selected {
+            line.style(Style::default().bg(palette.selection_bg))
+        } else {
+            line
+        }
+    }
+}
+
+fn child_count(count: usize, noun: &str, palette: &UiPalette) -> Span<'static> {
+    let plural = if count == 1 { "" } else { "s" };
+    Span::styled(
+        format!(" {} {}{}", count, noun, plural),
+        Style::default().fg(palette.gutter),
+    )
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_tools::query_json;
+
+    const PAYLOAD: &str =
+        r#"{"items": [{"name": "a", "tags": []}, {"name": "b"}], "odd key": "x", "n": 1}"#;
+
+    fn line_text(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn test_starts_with_root_expanded() {
+        let mut tree = JsonTree::parse(PAYLOAD).unwrap();
+        assert_eq!(tree.len(), 4);
+        let lines: Vec<String> = tree
+            .render_lines(10, &UiPalette::dark())
+            .iter()
+            .map(line_text)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "▾ { 3 keys",
+                "  ▸ items: […] 2 items",
+                "    odd key: \"x\"",
+                "    n: 1"
+            ]
+        );
+        assert!(JsonTree::parse("{ not json").is_none());
+    }
+
+    #[test]
+    fn test_expand_collapse_and_navigate() {
+        let mut tree = JsonTree::parse(PAYLOAD).unwrap();
+        tree.move_selection(1);
+        tree.expand();
+        assert_eq!(tree.len(), 6);
+        // Expanding an expanded node steps into it
+        tree.expand();
+        assert_eq!(tree.selected_path(), ".items[0]");
+        tree.expand();
+        tree.move_selection(2);
+        assert_eq!(tree.selected_path(), ".items[0].tags");
+        // An empty container has nothing to step into
+        tree.expand();
+        tree.expand();
+        assert_eq!(tree.selected_path(), ".items[0].tags");
+        // Left on a leaf goes to the parent, then collapses it
+        tree.move_selection(-1);
+        tree.collapse();
+        assert_eq!(tree.selected_path(), ".items[0]");
+        tree.collapse();
+        assert_eq!(tree.len(), 6);
+        tree.select_last();
+        assert_eq!(tree.selected_path(), ".n");
+        tree.move_selection(5);
+        assert_eq!(tree.selected_path(), ".n");
+        tree.select_first();
+        tree.toggle();
+        assert_eq!(tree.len(), 1);
+    }
+
+    #[test]
+    fn test_selected_path_and_value_match_queries() {
+        let mut tree = JsonTree::parse(PAYLOAD).unwrap();
+        assert_eq!(tree.selected_path(), ".");
+        tree.move_selection(2);
+        assert_eq!(tree.selected_path(), ".\"odd key\"");
+        assert_eq!(tree.selected_value(), "x");
+        assert_eq!(query_json(PAYLOAD, &tree.selected_path()).unwrap(), "\"x\"");
+
+        tree.move_selection(-1);
+        assert_eq!(
+            tree.selected_value(),
+            query_json(PAYLOAD, &tree.selected_path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_restore_keeps_expanded_nodes() {
+        let mut tree = JsonTree::parse(PAYLOAD).unwrap();
+        tree.move_selection(1);
+        tree.expand();
+        tree.move_selection(3);
+        assert_eq!(tree.selected_path(), ".\"odd key\"");
+
+        let mut edited = JsonTree::parse(r#"{"new": 0, "items": [1], "odd key": "y"}"#).unwrap();
+        edited.restore(tree);
+        assert_eq!(edited.len(), 5);
+        assert_eq!(edited.selected_path(), ".\"odd key\"");
+    }
+
+    #[test]
+    fn test_render_keeps_selection_visible() {
+        let mut tree = JsonTree::parse("[1, 2, 3, 4, 5, 6]").unwrap();
+        tree.select_last();
+        let lines = tree.render_lines(3, &UiPalette::dark());
+        assert_eq!(tree.scroll, 4);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(line_text(&lines[2]), "    [5]: 6");
+    }
+}
@@ -1,20 +1,51 @@
+pub mod backlinks_popup;
+pub mod capture;
 pub mod cli;
+pub mod clip_history;
+pub mod clip_history_popup;
 pub mod clipboard;
+pub mod code_fence;
+pub mod config;
+pub mod control;
+pub mod external_formatter;
+pub mod format_popup;
 pub mod formatter;
+pub mod json_popup;
+pub mod json_tools;
+pub mod json_tree;
 pub mod markdown_renderer;
+pub mod notebook;
+pub mod osc52;
+pub mod outline;
+pub mod outline_popup;
+pub mod render_cache;
 pub mod scrollable_textarea;
+pub mod server;
+pub mod syntaxes;
+pub mod table_view;
+pub mod task_list;
+pub mod theme;
+pub mod theme_popup;
 pub mod title_popup;
 pub mod title_select_popup;
 pub mod ui;
 pub mod ui_handler;
 pub mod utils;
+pub mod wiki_links;
 
+pub use backlinks_popup::BacklinksPopup;
+pub use clip_history_popup::ClipHistoryPopup;
 pub use clipboard::EditorClipboard;
-use dirs::home_dir;
-pub use formatter::{format_json, format_markdown};
+use dirs::{cache_dir, config_dir, data_dir, home_dir, runtime_dir};
+pub use format_popup::FormatPopup;
+pub use formatter::{format_json, format_markdown, Formatter, FormatterRegistry};
+pub use json_popup::JsonPopup;
 pub use markdown_renderer::MarkdownRenderer;
+pub use notebook::{Block, Notebook, NotebookError};
+pub use outline_popup::OutlinePopup;
 pub use scrollable_textarea::ScrollableTextArea;
 use std::path::PathBuf;
+pub use theme_popup::ThemePopup;
 pub use title_popup::TitlePopup;
 pub use title_select_popup::TitleSelectPopup;
 pub use utils::{load_textareas, save_textareas};
@@ -25,6 +56,50 @@ pub fn get_save_file_path() -> PathBuf {
 pub fn get_save_backup_file_path() -> PathBuf {
     home_dir().unwrap_or_default().join("thoth_notes_backup.md")
 }
+pub fn get_config_dir() -> PathBuf {
+    config_dir()
+        .unwrap_or_else(|| home_dir().unwrap_or_default().join(".config"))
+        .join("thoth")
+}
+pub fn get_config_file_path() -> PathBuf {
+    get_config_dir().join("config.toml")
+}
+pub fn get_themes_dir() -> PathBuf {
+    get_config_dir().join("themes")
+}
+pub fn get_syntaxes_dir() -> PathBuf {
+    get_config_dir().join("syntaxes")
+}
+pub fn get_data_dir() -> PathBuf {
+    data_dir()
+        .unwrap_or_else(|| home_dir().unwrap_or_default().join(".local").join("share"))
+        .join("thoth")
+}
+pub fn get_clip_history_file_path() -> PathBuf {
+    get_data_dir().join("clipboard_history.json")
+}
+/// Where sockets of running thoth instances live.
+pub fn get_runtime_dir() -> PathBuf {
+    match runtime_dir() {
+        Some(dir) => dir.join("thoth"),
+        // The temp directory is shared with other users, so each gets their own
+        None => std::env::temp_dir().join(shared_runtime_dir_name()),
+    }
+}
+#[cfg(unix)]
+fn shared_runtime_dir_name() -> String {
+    // SAFETY: getuid cannot fail and has no preconditions
+    format!("thoth-{}", unsafe { libc::getuid() })
+}
+#[cfg(not(unix))]
+fn shared_runtime_dir_name() -> String {
+    "thoth".to_string()
+}
+pub fn get_cache_dir() -> PathBuf {
+    cache_dir()
+        .unwrap_or_else(|| home_dir().unwrap_or_default().join(".cache"))
+        .join("thoth")
+}
 
 pub const ORANGE: ratatui::style::Color = ratatui::style::Color::Rgb(255, 165, 0);
 pub const DAEMONIZE_ARG: &str = "__thoth_copy_daemonize";
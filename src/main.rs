@@ -1,7 +1,9 @@
 use anyhow::{bail, Result};
 use clap::Parser;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -11,16 +13,26 @@ use std::{
     thread,
 };
 use thoth_cli::{
-    cli::{add_block, copy_block, delete_block, list_blocks, view_block},
+    cli::{
+        add_block, append_block, capture_block, clip_history, copy_block, delete_block,
+        format_block, json_block, list_blocks, list_syntaxes, list_todos, paste_block, serve,
+        view_block,
+    },
     get_save_backup_file_path, EditorClipboard,
 };
 use thoth_cli::{
-    cli::{Cli, Commands},
-    ui_handler::{draw_ui, handle_input, UIState},
+    cli::{Cli, Commands, SyntaxesCommands},
+    control::ControlServer,
+    get_save_file_path,
+    ui_handler::{draw_ui, handle_control_request, handle_event, UIState},
     utils::save_textareas,
 };
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+const IDLE_REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the control socket is checked for CLI commands between key presses.
+const CONTROL_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 fn main() -> Result<()> {
     #[cfg(target_os = "linux")]
@@ -29,22 +41,17 @@ fn main() -> Result<()> {
 
     match &cli.command {
         Some(Commands::Add { name, content }) => {
-            let content = match content {
-                Some(c) => c.to_string(),
-                None => {
-                    let mut buffer = String::new();
-                    if atty::is(atty::Stream::Stdin) {
-                        bail!(format!("Couldn't create '{}' because nothing was passed in. Either pipe in contents or use `thoth add {} <contents>`", name, name));
-                    }
-                    io::stdin().read_to_string(&mut buffer)?;
-                    if buffer.trim().is_empty() {
-                        bail!(format!("Couldn't create '{}' because nothing was passed in. Either pipe in contents or use `thoth add {} <contents>`", name, name));
-                    }
-                    buffer
-                }
-            };
+            let content = content_or_stdin(content.as_deref(), || {
+                format!("Couldn't create '{}' because nothing was passed in. Either pipe in contents or use `thoth add {} <contents>`", name, name)
+            })?;
             add_block(name, &content)?;
         }
+        Some(Commands::Append { name, content }) => {
+            let content = content_or_stdin(content.as_deref(), || {
+                format!("Couldn't append to '{}' because nothing was passed in. Either pipe in contents or use `thoth append {} <contents>`", name, name)
+            })?;
+            append_block(name, &content)?;
+        }
         Some(Commands::List) => {
             list_blocks()?;
         }
@@ -57,6 +64,43 @@ fn main() -> Result<()> {
         Some(Commands::Copy { name }) => {
             copy_block(name)?;
         }
+        Some(Commands::Fmt { name, format }) => {
+            format_block(name, format.as_deref())?;
+        }
+        Some(Commands::Json { command }) => {
+            json_block(command)?;
+        }
+        Some(Commands::Paste {
+            name,
+            append,
+            format,
+        }) => {
+            paste_block(name, *append, format.as_deref())?;
+        }
+        Some(Commands::Capture {
+            name,
+            append,
+            tee,
+            command,
+        }) => {
+            let code = capture_block(name, *append, *tee, command)?;
+            // Scripts can still tell whether the captured command failed
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Some(Commands::Clip { command }) => {
+            clip_history(command)?;
+        }
+        Some(Commands::Todo) => {
+            list_todos()?;
+        }
+        Some(Commands::Serve { host, port, token }) => {
+            serve(*host, *port, token.as_deref())?;
+        }
+        Some(Commands::Syntaxes { command }) => match command {
+            SyntaxesCommands::List => list_syntaxes()?,
+        },
         None => {
             run_ui()?;
         }
@@ -65,16 +109,47 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// `content` when given, otherwise what is piped in, failing with `nothing_passed`
+/// when that is empty too.
+fn content_or_stdin(content: Option<&str>, nothing_passed: impl Fn() -> String) -> Result<String> {
+    if let Some(content) = content {
+        return Ok(content.to_string());
+    }
+    let mut buffer = String::new();
+    if atty::is(atty::Stream::Stdin) {
+        bail!(nothing_passed());
+    }
+    io::stdin().read_to_string(&mut buffer)?;
+    if buffer.trim().is_empty() {
+        bail!(nothing_passed());
+    }
+    Ok(buffer)
+}
+
 pub fn run_ui() -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     let mut state = UIState::new()?;
-
-    let draw_interval = Duration::from_millis(33);
+    // CLI commands go through this window instead of racing it on the notes file
+    let control = match ControlServer::bind(&get_save_file_path()) {
+        Ok(control) => control,
+        Err(e) => {
+            state.error_popup.show(format!(
+                "CLI commands will not show up in this window: {:#}",
+                e
+            ));
+            None
+        }
+    };
 
     let copy_textareas = state.scrollable_textarea.textareas.clone();
     let copy_titles = state.scrollable_textarea.titles.clone();
@@ -83,27 +158,55 @@ pub fn run_ui() -> Result<()> {
         thread::sleep(Duration::from_secs(60)); // save backup every minute
     });
 
-    loop {
-        let should_draw = state.last_draw.elapsed() >= draw_interval;
-        if should_draw {
-            draw_ui(&mut terminal, &mut state)?;
-            state.last_draw = std::time::Instant::now();
-        }
+    draw_ui(&mut terminal, &mut state)?;
+    state.last_draw = Instant::now();
 
-        if event::poll(Duration::from_millis(1))? {
-            if let Event::Key(key) = event::read()? {
-                if handle_input(&mut terminal, &mut state, key)? {
+    // Redraw only after input, a resize or the idle timer instead of polling in a busy loop
+    'main: loop {
+        let mut timeout = IDLE_REDRAW_INTERVAL.saturating_sub(state.last_draw.elapsed());
+        if control.is_some() {
+            timeout = timeout.min(CONTROL_POLL_INTERVAL);
+        }
+        if let Some(due) = state.scrollable_textarea.primary_selection_due() {
+            timeout = timeout.min(due);
+        }
+        let mut changed = false;
+        if event::poll(timeout)? {
+            // Drain everything that is already queued before paying for a redraw
+            loop {
+                if handle_event(&mut terminal, &mut state, event::read()?)? {
+                    break 'main;
+                }
+                if !event::poll(Duration::ZERO)? {
                     break;
                 }
             }
+            changed = true;
+        }
+        if let Some(control) = &control {
+            let served =
+                control.serve_pending(|request| handle_control_request(&mut state, request));
+            changed |= served > 0;
+        }
+        if let Err(e) = state.scrollable_textarea.flush_primary_selection(false) {
+            state.error_popup.show(format!("{}", e));
+            changed = true;
+        }
+
+        if changed || state.last_draw.elapsed() >= IDLE_REDRAW_INTERVAL {
+            draw_ui(&mut terminal, &mut state)?;
+            state.last_draw = Instant::now();
         }
     }
 
+    // Nothing is left to show an error in
+    let _ = state.scrollable_textarea.flush_primary_selection(true);
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     Ok(())
 }
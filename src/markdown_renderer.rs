@@ -1,21 +1,32 @@
-use std::collections::HashMap;
-
-use anyhow::{anyhow, Result};
+use crate::code_fence::{backtick_fence_len, closes_backtick_fence};
+use crate::syntaxes::load_syntax_set;
+use crate::task_list::{parse_task, CHECKED_GLYPH, UNCHECKED_GLYPH};
+use crate::theme::{load_theme_set, theme_names, UiPalette, DEFAULT_THEME};
+use crate::wiki_links::style_wiki_links;
+use anyhow::{anyhow, bail, Result};
 use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
 };
 use syntect::{
-    easy::HighlightLines,
-    highlighting::{Style as SyntectStyle, ThemeSet},
-    parsing::{SyntaxReference, SyntaxSet},
+    highlighting::{
+        HighlightIterator, HighlightState, Highlighter, Style as SyntectStyle, ThemeSet,
+    },
+    parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet},
 };
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const CONTINUATION_MARKER: &str = "↪";
 
 pub struct MarkdownRenderer {
     syntax_set: SyntaxSet,
     theme_set: ThemeSet,
     theme: String,
-    cache: HashMap<String, Text<'static>>,
+    palette: UiPalette,
+    /// Why the user syntaxes could not be loaded, in which case only the defaults are
+    syntax_error: Option<String>,
+    /// Why some user themes could not be loaded, in which case they are left out
+    theme_error: Option<String>,
 }
 
 impl Default for MarkdownRenderer {
@@ -26,200 +37,508 @@ impl Default for MarkdownRenderer {
 
 impl MarkdownRenderer {
     pub fn new() -> Self {
+        let (syntax_set, syntax_error) = match load_syntax_set() {
+            Ok(syntax_set) => (syntax_set, None),
+            Err(e) => (
+                SyntaxSet::load_defaults_newlines(),
+                Some(format!("{:#}", e)),
+            ),
+        };
+        let (theme_set, theme_error) = load_theme_set();
         MarkdownRenderer {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
-            theme: "base16-mocha.dark".to_string(),
-            cache: HashMap::new(),
+            syntax_set,
+            theme_set,
+            theme: DEFAULT_THEME.to_string(),
+            palette: UiPalette::dark(),
+            syntax_error,
+            theme_error,
         }
     }
 
-    pub fn render_markdown(
-        &mut self,
-        markdown: String,
-        title: String,
+    pub fn syntax_error(&self) -> Option<&str> {
+        self.syntax_error.as_deref()
+    }
+
+    pub fn theme_error(&self) -> Option<&str> {
+        self.theme_error.as_deref()
+    }
+
+    /// Switches the syntax theme. The UI palette is derived from the theme
+    /// background unless `palette` is given.
+    pub fn set_theme(&mut self, name: &str, palette: Option<UiPalette>) -> Result<()> {
+        let theme = match self.theme_set.themes.get(name) {
+            Some(theme) => theme,
+            None => bail!("Unknown theme '{}'", name),
+        };
+        self.palette = palette.unwrap_or_else(|| UiPalette::for_theme(theme));
+        self.theme = name.to_string();
+        Ok(())
+    }
+
+    pub fn theme_name(&self) -> &str {
+        &self.theme
+    }
+
+    pub fn theme_names(&self) -> Vec<String> {
+        theme_names(&self.theme_set)
+    }
+
+    pub fn palette(&self) -> UiPalette {
+        self.palette
+    }
+
+    pub fn render_markdown(&self, markdown: String, width: usize) -> Result<Text<'static>> {
+        let lines: Vec<String> = markdown.lines().map(|x| x.to_string()).collect();
+        let mut memo = BlockRenderMemo::default();
+        self.render_incremental(&mut memo, &lines, width, usize::MAX)
+    }
+
+    /// Renders `lines`, reusing whatever `memo` still holds from the previous render
+    /// of the same block. Only lines from the first edit onwards are highlighted again,
+    /// and rendering stops once the highlighter state converges with the old render.
+    /// At most enough source lines to produce `min_rows` rows are rendered, so a
+    /// caller showing only the top of a long block does not pay for the rest.
+    pub fn render_incremental(
+        &self,
+        memo: &mut BlockRenderMemo,
+        lines: &[String],
         width: usize,
+        min_rows: usize,
     ) -> Result<Text<'static>> {
-        if let Some(lines) = self.cache.get(&format!("{}{}", &title, &markdown)) {
-            return Ok(lines.clone());
+        let json = looks_like_json(lines);
+        if memo.width != width || memo.theme != self.theme || memo.json != json {
+            *memo = BlockRenderMemo {
+                width,
+                theme: self.theme.clone(),
+                json,
+                ..Default::default()
+            };
         }
 
-        let md_syntax = self.syntax_set.find_syntax_by_extension("md").unwrap();
-        let mut lines = Vec::new();
-        let mut in_code_block = false;
-        let mut code_block_lang = String::new();
-        let mut code_block_content = Vec::new();
         let theme = &self.theme_set.themes[&self.theme];
-        let mut h = HighlightLines::new(md_syntax, theme);
-
-        const HEADER_COLORS: [Color; 6] = [
-            Color::Red,
-            Color::Green,
-            Color::Yellow,
-            Color::Blue,
-            Color::Magenta,
-            Color::Cyan,
-        ];
-
-        // Check if the entire markdown is JSON
-        if (markdown.trim_start().starts_with('{') || markdown.trim_start().starts_with('['))
-            && (markdown.trim_end().ends_with('}') || markdown.trim_end().ends_with(']'))
-        {
-            let json_syntax = self.syntax_set.find_syntax_by_extension("json").unwrap();
-            return Ok(Text::from(self.highlight_code_block(
-                &markdown.lines().map(|x| x.to_string()).collect::<Vec<_>>(),
-                "json",
-                json_syntax,
-                theme,
-                width,
-            )?));
-        }
+        let highlighter = Highlighter::new(theme);
 
-        let updated_markdown = markdown.clone();
-        let mut markdown_lines = updated_markdown.lines().map(|x| x.to_string()).peekable();
-        while let Some(line) = markdown_lines.next() {
-            if line.starts_with("```") {
-                if in_code_block {
-                    // End of code block
-                    let syntax = self
-                        .syntax_set
-                        .find_syntax_by_token(&code_block_lang)
-                        .unwrap_or(md_syntax);
-                    lines.extend(self.highlight_code_block(
-                        &code_block_content.clone(),
-                        &code_block_lang,
-                        syntax,
-                        theme,
-                        width,
-                    )?);
-                    code_block_content.clear();
-                    in_code_block = false;
-                } else {
-                    // Start of code block
-                    in_code_block = true;
-                    code_block_lang = line.trim_start_matches('`').to_string();
-
-                    // Check if it's a one-line code block
-                    if let Some(next_line) = markdown_lines.peek() {
-                        if next_line.starts_with("```") {
-                            // It's a one-line code block
-                            let syntax = self
-                                .syntax_set
-                                .find_syntax_by_token(&code_block_lang)
-                                .unwrap_or(md_syntax);
-                            lines.extend(self.highlight_code_block(
-                                &["".to_string()],
-                                &code_block_lang,
-                                syntax,
-                                theme,
-                                width,
-                            )?);
-                            in_code_block = false;
-                            markdown_lines.next(); // Skip the closing ```
-                            continue;
-                        }
-                    }
-                }
-            } else if in_code_block {
-                code_block_content.push(line.to_string());
-            } else {
-                let highlighted = h
-                    .highlight_line(&line, &self.syntax_set)
-                    .map_err(|e| anyhow!("Highlight error: {}", e))?;
-                let mut spans: Vec<Span> = highlighted.into_iter().map(into_span).collect();
-
-                // Optimized header handling
-                if let Some(header_level) = line.bytes().position(|b| b != b'#') {
-                    if header_level > 0
-                        && header_level <= 6
-                        && line.as_bytes().get(header_level) == Some(&b' ')
-                    {
-                        let header_color = HEADER_COLORS[header_level.saturating_sub(1)];
-                        spans = vec![Span::styled(
-                            line,
-                            Style::default()
-                                .fg(header_color)
-                                .add_modifier(Modifier::BOLD),
-                        )];
-                    }
+        let old_len = memo.sources.len();
+        let new_len = lines.len();
+        let mut start = memo
+            .sources
+            .iter()
+            .zip(lines)
+            .take(memo.rendered.len())
+            .take_while(|(old, new)| old == new)
+            .count();
+
+        // Code line numbers are right-aligned to the block's line count, so an edit
+        // that changes the number of digits re-renders the whole code block
+        if start > 0 {
+            if let Some(code) = &memo.rendered[start - 1].state.code {
+                let fence = (start - 1) - code.line_number;
+                let first_code_line = if code.fence.is_some() { fence + 1 } else { 0 };
+                let gutter = gutter_width(code_block_len(lines, first_code_line, code.fence));
+                if gutter != code.gutter_width {
+                    start = if code.fence.is_some() { fence } else { 0 };
                 }
+            }
+        }
 
-                // Pad regular Markdown lines to full width
-                let line_content: String =
-                    spans.iter().map(|span| span.content.to_string()).collect();
-                let padding_width = width.saturating_sub(line_content.len());
-                if padding_width > 0 {
-                    spans.push(Span::styled(" ".repeat(padding_width), Style::default()));
+        let old_rendered = memo.rendered.split_off(start);
+        let common_suffix = memo
+            .sources
+            .iter()
+            .rev()
+            .zip(lines.iter().rev())
+            .take(old_len.min(new_len) - start)
+            .take_while(|(old, new)| old == new)
+            .count();
+
+        let mut state = match memo.rendered.last() {
+            Some(rendered) => rendered.state.clone(),
+            None => self.initial_state(lines, json, &highlighter),
+        };
+        let mut rows: usize = memo.rendered.iter().map(|r| r.output.len()).sum();
+
+        memo.last_rendered = 0;
+        let mut index = start;
+        while index < new_len && rows < min_rows {
+            memo.last_rendered += 1;
+            let output = self.render_line(lines, index, &mut state, &highlighter, width)?;
+            rows += output.len();
+            memo.rendered.push(RenderedLine {
+                state: state.clone(),
+                output,
+            });
+
+            // Once the remaining lines are unchanged and the highlighter is back in
+            // the state it had there before, the rest of the old render still holds
+            let remaining = new_len - index - 1;
+            if remaining <= common_suffix && old_len > remaining {
+                let old_index = old_len - remaining - 1;
+                if old_index >= start && old_index - start < old_rendered.len() {
+                    let old = &old_rendered[old_index - start];
+                    if old.state == state {
+                        memo.rendered
+                            .extend(old_rendered.into_iter().skip(old_index - start + 1));
+                        break;
+                    }
                 }
-
-                lines.push(Line::from(spans));
             }
+            index += 1;
+        }
+
+        if memo.sources.as_slice() != lines {
+            memo.sources = lines.to_vec();
         }
 
-        let markdown_lines = Text::from(lines);
-        let new_key = &format!("{}{}", &title, &markdown);
-        self.cache.insert(new_key.clone(), markdown_lines.clone());
-        Ok(markdown_lines)
+        Ok(Text::from(
+            memo.rendered
+                .iter()
+                .flat_map(|rendered| rendered.output.iter().cloned())
+                .collect::<Vec<_>>(),
+        ))
     }
 
-    fn highlight_code_block(
+    fn initial_state(&self, lines: &[String], json: bool, highlighter: &Highlighter) -> LineState {
+        let md_syntax = self.syntax_set.find_syntax_by_extension("md").unwrap();
+        let code = if json {
+            let json_syntax = self.syntax_set.find_syntax_by_extension("json").unwrap();
+            Some(CodeBlockState {
+                lang: "json".to_string(),
+                fence: None,
+                line_number: 0,
+                gutter_width: gutter_width(lines.len()),
+                checkpoint: HighlightCheckpoint::new(json_syntax, highlighter),
+            })
+        } else {
+            None
+        };
+        LineState {
+            markdown: HighlightCheckpoint::new(md_syntax, highlighter),
+            code,
+        }
+    }
+
+    fn render_line(
         &self,
-        code: &[String],
-        lang: &str,
-        syntax: &SyntaxReference,
-        theme: &syntect::highlighting::Theme,
+        lines: &[String],
+        index: usize,
+        state: &mut LineState,
+        highlighter: &Highlighter,
         width: usize,
     ) -> Result<Vec<Line<'static>>> {
-        let mut h = HighlightLines::new(syntax, theme);
-        let mut result = Vec::new();
-
-        let max_line_num = code.len();
-        let line_num_width = max_line_num.to_string().len();
-
-        if lang != "json" {
-            result.push(Line::from(Span::styled(
+        let line = &lines[index];
+        let palette = self.palette;
+        let separator = || {
+            Line::from(Span::styled(
                 "─".repeat(width),
-                Style::default().fg(Color::White),
-            )));
+                Style::default().fg(palette.gutter),
+            ))
+        };
+
+        if let Some(code) = state.code.as_mut() {
+            if code
+                .fence
+                .is_some_and(|len| closes_backtick_fence(line, len))
+            {
+                // End of code block
+                let mut output = Vec::new();
+                if code.line_number == 0 {
+                    // An empty code block still shows a single numbered line
+                    output.extend(self.code_rows(code, Vec::new(), 1, width));
+                }
+                if code.lang != "json" {
+                    output.push(separator());
+                }
+                state.code = None;
+                return Ok(output);
+            }
+
+            code.line_number += 1;
+            let spans = self.highlight(line, &mut code.checkpoint, highlighter)?;
+            let line_number = code.line_number;
+            return Ok(self.code_rows(code, spans, line_number, width));
         }
 
-        for (line_number, line) in code.iter().enumerate() {
-            let highlighted = h
-                .highlight_line(line, &self.syntax_set)
-                .map_err(|e| anyhow!("Highlight error: {}", e))?;
+        if let Some(fence_len) = backtick_fence_len(line) {
+            // Start of code block
+            let lang = line.trim_start().trim_start_matches('`').to_string();
+            let md_syntax = self.syntax_set.find_syntax_by_extension("md").unwrap();
+            let syntax = self
+                .syntax_set
+                .find_syntax_by_token(&lang)
+                .unwrap_or(md_syntax);
+            let output = if lang != "json" {
+                vec![separator()]
+            } else {
+                Vec::new()
+            };
+            state.code = Some(CodeBlockState {
+                gutter_width: gutter_width(code_block_len(lines, index + 1, Some(fence_len))),
+                lang,
+                fence: Some(fence_len),
+                line_number: 0,
+                checkpoint: HighlightCheckpoint::new(syntax, highlighter),
+            });
+            return Ok(output);
+        }
 
-            let mut spans = if lang == "json" {
-                vec![Span::styled(
-                    format!("{:>width$} ", line_number + 1, width = line_num_width),
-                    Style::default().fg(Color::White),
-                )]
+        let mut spans = self.highlight(line, &mut state.markdown, highlighter)?;
+
+        if let Some(task) = parse_task(line) {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let (glyph, glyph_style, text_style) = if task.checked {
+                (
+                    CHECKED_GLYPH,
+                    Style::default().fg(palette.task_done),
+                    Style::default()
+                        .fg(palette.task_done)
+                        .add_modifier(Modifier::CROSSED_OUT),
+                )
             } else {
-                vec![Span::styled(
-                    format!("{:>width$} │ ", line_number + 1, width = line_num_width),
-                    Style::default().fg(Color::White),
-                )]
+                (
+                    UNCHECKED_GLYPH,
+                    Style::default().fg(palette.task_open),
+                    Style::default(),
+                )
             };
-            spans.extend(highlighted.into_iter().map(into_span));
+            spans = vec![
+                Span::raw(indent.to_string()),
+                Span::styled(glyph, glyph_style),
+                Span::raw(" "),
+                Span::styled(task.text, text_style),
+            ];
+        }
+
+        // Optimized header handling
+        if let Some(header_level) = line.bytes().position(|b| b != b'#') {
+            if header_level > 0
+                && header_level <= 6
+                && line.as_bytes().get(header_level) == Some(&b' ')
+            {
+                let header_color = palette.headings[header_level.saturating_sub(1)];
+                spans = vec![Span::styled(
+                    line.clone(),
+                    Style::default()
+                        .fg(header_color)
+                        .add_modifier(Modifier::BOLD),
+                )];
+            }
+        }
+
+        spans = style_wiki_links(
+            spans,
+            Style::default()
+                .fg(palette.link)
+                .add_modifier(Modifier::UNDERLINED),
+        );
+
+        // Pad regular Markdown lines to full width
+        pad_to_width(&mut spans, width);
+        Ok(vec![Line::from(spans)])
+    }
+
+    fn highlight(
+        &self,
+        line: &str,
+        checkpoint: &mut HighlightCheckpoint,
+        highlighter: &Highlighter,
+    ) -> Result<Vec<Span<'static>>> {
+        let ops = checkpoint
+            .parse
+            .parse_line(line, &self.syntax_set)
+            .map_err(|e| anyhow!("Highlight error: {}", e))?;
+        Ok(
+            HighlightIterator::new(&mut checkpoint.highlight, &ops, line, highlighter)
+                .map(into_span)
+                .collect(),
+        )
+    }
 
-            // Pad the line to full width
-            let line_content: String = spans.iter().map(|span| span.content.to_string()).collect();
-            let padding_width = width.saturating_sub(line_content.len());
-            if padding_width > 0 {
-                spans.push(Span::styled(" ".repeat(padding_width), Style::default()));
+    fn code_rows(
+        &self,
+        code: &CodeBlockState,
+        body: Vec<Span<'static>>,
+        line_number: usize,
+        width: usize,
+    ) -> Vec<Line<'static>> {
+        let gutter = |marker: String| {
+            if code.lang == "json" {
+                format!("{:>width$} ", marker, width = code.gutter_width)
+            } else {
+                format!("{:>width$} │ ", marker, width = code.gutter_width)
             }
+        };
+        let continuation = gutter(CONTINUATION_MARKER.to_string());
+        let body_width = width.saturating_sub(continuation.width());
+        let gutter_style = Style::default().fg(self.palette.gutter);
+
+        // Soft-wrap long lines ourselves so every row keeps the gutter and padding
+        wrap_spans(body, body_width)
+            .into_iter()
+            .enumerate()
+            .map(|(row_index, row)| {
+                let prefix = if row_index == 0 {
+                    gutter(line_number.to_string())
+                } else {
+                    continuation.clone()
+                };
+                let mut spans = vec![Span::styled(prefix, gutter_style)];
+                spans.extend(row);
+                pad_to_width(&mut spans, width);
+                Line::from(spans)
+            })
+            .collect()
+    }
+}
 
-            result.push(Line::from(spans));
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HighlightCheckpoint {
+    parse: ParseState,
+    highlight: HighlightState,
+}
+
+impl HighlightCheckpoint {
+    fn new(syntax: &SyntaxReference, highlighter: &Highlighter) -> Self {
+        HighlightCheckpoint {
+            parse: ParseState::new(syntax),
+            highlight: HighlightState::new(highlighter, ScopeStack::new()),
         }
+    }
+}
 
-        if lang != "json" {
-            result.push(Line::from(Span::styled(
-                "─".repeat(width),
-                Style::default().fg(Color::White),
-            )));
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CodeBlockState {
+    lang: String,
+    /// Length of the backtick fence that opened the code, `None` when the whole
+    /// block is rendered as JSON without fences
+    fence: Option<usize>,
+    /// Code lines rendered so far, which also locates the opening fence
+    line_number: usize,
+    gutter_width: usize,
+    checkpoint: HighlightCheckpoint,
+}
+
+/// Renderer state after a source line, enough to resume rendering from there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LineState {
+    markdown: HighlightCheckpoint,
+    code: Option<CodeBlockState>,
+}
+
+struct RenderedLine {
+    state: LineState,
+    output: Vec<Line<'static>>,
+}
+
+/// Per-block render state kept between frames by [`MarkdownRenderer::render_incremental`].
+#[derive(Default)]
+pub struct BlockRenderMemo {
+    width: usize,
+    theme: String,
+    json: bool,
+    sources: Vec<String>,
+    rendered: Vec<RenderedLine>,
+    last_rendered: usize,
+}
+
+impl BlockRenderMemo {
+    /// Number of source lines highlighted by the most recent render.
+    pub fn last_rendered(&self) -> usize {
+        self.last_rendered
+    }
+
+    /// True when every source line of the last render has been rendered.
+    pub fn is_complete(&self) -> bool {
+        self.rendered.len() == self.sources.len()
+    }
+}
+
+pub(crate) fn looks_like_json(lines: &[String]) -> bool {
+    let first = lines.iter().map(|l| l.trim_start()).find(|l| !l.is_empty());
+    let last = lines
+        .iter()
+        .rev()
+        .map(|l| l.trim_end())
+        .find(|l| !l.is_empty());
+    match (first, last) {
+        (Some(first), Some(last)) => {
+            (first.starts_with('{') || first.starts_with('['))
+                && (last.ends_with('}') || last.ends_with(']'))
+        }
+        _ => false,
+    }
+}
+
+/// Number of code lines from `start` up to the closing fence (or the end of the block).
+/// Marks task item number `task` of a rendered block with `style`, from its checkbox
+/// to the end of the row.
+pub fn highlight_task(text: &mut Text<'static>, task: usize, style: Style) {
+    let is_task = |line: &&mut Line<'static>| {
+        line.spans
+            .get(1)
+            .is_some_and(|span| span.content == CHECKED_GLYPH || span.content == UNCHECKED_GLYPH)
+    };
+    if let Some(line) = text.lines.iter_mut().filter(is_task).nth(task) {
+        for span in &mut line.spans[1..] {
+            span.style = span.style.patch(style);
         }
+    }
+}
+
+fn code_block_len(lines: &[String], start: usize, fence: Option<usize>) -> usize {
+    let rest = lines.get(start..).unwrap_or_default();
+    if let Some(len) = fence {
+        rest.iter()
+            .take_while(|l| !closes_backtick_fence(l, len))
+            .count()
+    } else {
+        rest.len()
+    }
+}
+
+fn gutter_width(code_lines: usize) -> usize {
+    code_lines.max(1).to_string().len()
+}
+
+/// Pads `spans` with trailing spaces up to `width` display columns.
+fn pad_to_width(spans: &mut Vec<Span<'static>>, width: usize) {
+    let line_width: usize = spans.iter().map(|span| span.width()).sum();
+    let padding_width = width.saturating_sub(line_width);
+    if padding_width > 0 {
+        spans.push(Span::styled(" ".repeat(padding_width), Style::default()));
+    }
+}
+
+/// Splits `spans` into rows no wider than `max_width` display columns, keeping styles.
+/// A wide character that does not fit on a row is moved to the next one.
+pub fn wrap_spans(spans: Vec<Span<'static>>, max_width: usize) -> Vec<Vec<Span<'static>>> {
+    if max_width == 0 {
+        return vec![spans];
+    }
 
-        Ok(result)
+    let mut rows = vec![Vec::new()];
+    let mut row_width = 0;
+    for span in spans {
+        let style = span.style;
+        let mut current = String::new();
+        for ch in span.content.chars() {
+            let ch_width = ch.width().unwrap_or(0);
+            if row_width > 0 && row_width + ch_width > max_width {
+                if !current.is_empty() {
+                    rows.last_mut()
+                        .unwrap()
+                        .push(Span::styled(std::mem::take(&mut current), style));
+                }
+                rows.push(Vec::new());
+                row_width = 0;
+            }
+            current.push(ch);
+            row_width += ch_width;
+        }
+        if !current.is_empty() {
+            rows.last_mut().unwrap().push(Span::styled(current, style));
+        }
     }
+    rows
 }
 
 fn syntect_style_to_ratatui_style(style: SyntectStyle) -> Style {
@@ -261,11 +580,9 @@ mod tests {
 
     #[test]
     fn test_render_markdown() {
-        let mut renderer = MarkdownRenderer::new();
+        let renderer = MarkdownRenderer::new();
         let markdown = "# Header\n\nThis is **bold** and *italic* text.";
-        let rendered = renderer
-            .render_markdown(markdown.to_string(), "".to_string(), 40)
-            .unwrap();
+        let rendered = renderer.render_markdown(markdown.to_string(), 40).unwrap();
 
         assert!(rendered.lines.len() >= 3);
         assert!(rendered.lines[0]
@@ -288,12 +605,10 @@ mod tests {
 
     #[test]
     fn test_render_markdown_with_code_block() {
-        let mut renderer = MarkdownRenderer::new();
+        let renderer = MarkdownRenderer::new();
         let markdown = "# Header\n\n```rust\nfn main() {\n    println!(\"Hello, world!\");\n}\n```";
 
-        let rendered = renderer
-            .render_markdown(markdown.to_string(), "".to_string(), 40)
-            .unwrap();
+        let rendered = renderer.render_markdown(markdown.to_string(), 40).unwrap();
         assert!(rendered.lines.len() > 5);
         assert!(rendered.lines[0]
             .spans
@@ -305,18 +620,39 @@ mod tests {
             .any(|line| line.spans.iter().any(|span| span.content.contains("main"))));
     }
 
+    #[test]
+    fn test_longer_fence_keeps_inner_fences_as_code() {
+        let renderer = MarkdownRenderer::new();
+        let markdown = "````console\n$ cat notes.md\n```\n# not a header\n````\nafter";
+        let rendered = renderer.render_markdown(markdown.to_string(), 40).unwrap();
+        let text: Vec<String> = rendered
+            .lines
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect()
+            })
+            .collect();
+
+        // Separator, three numbered code lines, separator, prose
+        assert_eq!(text.len(), 6, "{:?}", text);
+        assert!(text[2].starts_with("2 │ ```"));
+        assert!(text[3].starts_with("3 │ # not a header"));
+        assert!(text[5].starts_with("after"));
+    }
+
     #[test]
     fn test_render_json() {
-        let mut renderer = MarkdownRenderer::new();
+        let renderer = MarkdownRenderer::new();
         let json = r#"{
   "name": "John Doe",
   "age": 30,
   "city": "New York"
 }"#;
 
-        let rendered = renderer
-            .render_markdown(json.to_string(), "".to_string(), 40)
-            .unwrap();
+        let rendered = renderer.render_markdown(json.to_string(), 40).unwrap();
 
         assert!(rendered.lines.len() == 5);
         assert!(rendered.lines[0]
@@ -331,11 +667,9 @@ mod tests {
 
     #[test]
     fn test_render_markdown_with_one_line_code_block() {
-        let mut renderer = MarkdownRenderer::new();
+        let renderer = MarkdownRenderer::new();
         let markdown = "# Header\n\n```rust\n```\n\nText after.".to_string();
-        let rendered = renderer
-            .render_markdown(markdown, "".to_string(), 40)
-            .unwrap();
+        let rendered = renderer.render_markdown(markdown, 40).unwrap();
 
         assert!(rendered.lines.len() > 3);
         assert!(rendered.lines[0]
@@ -354,4 +688,199 @@ mod tests {
             .iter()
             .any(|span| span.content.contains("Text after.")));
     }
+
+    fn line_width(line: &Line) -> usize {
+        line.spans.iter().map(|span| span.content.width()).sum()
+    }
+
+    #[test]
+    fn test_wide_characters_are_padded_by_display_width() {
+        let renderer = MarkdownRenderer::new();
+        let markdown = "日本語のテキスト\ncafé 🎉\n\n```\n漢字 = 1\n```".to_string();
+        let rendered = renderer.render_markdown(markdown, 40).unwrap();
+
+        for line in &rendered.lines {
+            assert_eq!(line_width(line), 40);
+        }
+    }
+
+    #[test]
+    fn test_long_code_lines_wrap_with_continuation_marker() {
+        let renderer = MarkdownRenderer::new();
+        let long_line = "一二三四五六七八九十".repeat(3);
+        let markdown = format!("```\n{}\nshort\n```", long_line);
+        let rendered = renderer.render_markdown(markdown, 20).unwrap();
+
+        // separator + wrapped rows + "short" + separator
+        assert!(rendered.lines.len() > 4);
+        assert!(rendered.lines[2]
+            .spans
+            .iter()
+            .any(|span| span.content.contains(CONTINUATION_MARKER)));
+        for line in &rendered.lines {
+            assert_eq!(line_width(line), 20);
+        }
+    }
+
+    #[test]
+    fn test_wrap_spans_moves_wide_char_to_next_row() {
+        let rows = wrap_spans(vec![Span::raw("ab漢")], 3);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][0].content, "ab");
+        assert_eq!(rows[1][0].content, "漢");
+    }
+
+    #[test]
+    fn test_set_theme() {
+        let mut renderer = MarkdownRenderer::new();
+        renderer
+            .set_theme("InspiredGitHub", None)
+            .expect("built-in theme");
+        assert_eq!(renderer.theme_name(), "InspiredGitHub");
+        assert_eq!(renderer.palette(), UiPalette::light());
+
+        assert!(renderer.set_theme("no-such-theme", None).is_err());
+        assert_eq!(renderer.theme_name(), "InspiredGitHub");
+    }
+
+    #[test]
+    fn test_set_theme_changes_styles() {
+        let mut renderer = MarkdownRenderer::new();
+        let markdown = "# Header".to_string();
+        let dark = renderer.render_markdown(markdown.clone(), 20).unwrap();
+        renderer.set_theme("InspiredGitHub", None).unwrap();
+        let light = renderer.render_markdown(markdown, 20).unwrap();
+        assert_ne!(dark.lines[0].spans[0].style, light.lines[0].spans[0].style);
+    }
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    fn assert_same_render(incremental: &Text, renderer: &MarkdownRenderer, source: &[String]) {
+        let full = renderer.render_markdown(source.join("\n"), 30).unwrap();
+        assert_eq!(incremental.lines, full.lines);
+    }
+
+    #[test]
+    fn test_incremental_render_matches_full_render() {
+        let renderer = MarkdownRenderer::new();
+        let mut memo = BlockRenderMemo::default();
+        let mut source =
+            lines("# Title\nintro\n```rust\nfn a() {}\nfn b() {}\n```\n- [ ] task\nafter");
+        renderer
+            .render_incremental(&mut memo, &source, 30, usize::MAX)
+            .unwrap();
+
+        // Edit a prose line after the code block
+        source[6] = "- [x] task".to_string();
+        let text = renderer
+            .render_incremental(&mut memo, &source, 30, usize::MAX)
+            .unwrap();
+        assert_same_render(&text, &renderer, &source);
+        assert_eq!(memo.last_rendered(), 1);
+
+        // Edit inside the code block
+        source[3] = "fn a() { let x = 1; }".to_string();
+        let text = renderer
+            .render_incremental(&mut memo, &source, 30, usize::MAX)
+            .unwrap();
+        assert_same_render(&text, &renderer, &source);
+        assert!(memo.last_rendered() < source.len());
+
+        // Drop the closing fence so the rest of the block becomes code
+        source.remove(5);
+        let text = renderer
+            .render_incremental(&mut memo, &source, 30, usize::MAX)
+            .unwrap();
+        assert_same_render(&text, &renderer, &source);
+
+        // Insert a line at the top
+        source.insert(0, "new first line".to_string());
+        let text = renderer
+            .render_incremental(&mut memo, &source, 30, usize::MAX)
+            .unwrap();
+        assert_same_render(&text, &renderer, &source);
+    }
+
+    #[test]
+    fn test_incremental_render_gutter_width_change() {
+        let renderer = MarkdownRenderer::new();
+        let mut memo = BlockRenderMemo::default();
+        let mut source = vec!["```".to_string()];
+        source.extend((1..=9).map(|i| format!("line {}", i)));
+        source.push("```".to_string());
+        renderer
+            .render_incremental(&mut memo, &source, 30, usize::MAX)
+            .unwrap();
+
+        // The tenth code line widens the line number gutter of every code line
+        source.insert(10, "line 10".to_string());
+        let text = renderer
+            .render_incremental(&mut memo, &source, 30, usize::MAX)
+            .unwrap();
+        assert_same_render(&text, &renderer, &source);
+        assert!(text.lines[1].spans[0].content.starts_with(" 1 │"));
+    }
+
+    #[test]
+    fn test_incremental_render_stops_after_min_rows() {
+        let renderer = MarkdownRenderer::new();
+        let mut memo = BlockRenderMemo::default();
+        let source: Vec<String> = (0..100).map(|i| format!("line {}", i)).collect();
+
+        let text = renderer
+            .render_incremental(&mut memo, &source, 30, 10)
+            .unwrap();
+        assert_eq!(text.lines.len(), 10);
+        assert!(!memo.is_complete());
+
+        // Asking for more continues where the previous render stopped
+        let text = renderer
+            .render_incremental(&mut memo, &source, 30, 20)
+            .unwrap();
+        assert_eq!(text.lines.len(), 20);
+        assert_eq!(memo.last_rendered(), 10);
+
+        let text = renderer
+            .render_incremental(&mut memo, &source, 30, usize::MAX)
+            .unwrap();
+        assert!(memo.is_complete());
+        assert_same_render(&text, &renderer, &source);
+    }
+
+    #[test]
+    fn test_incremental_render_resets_on_width_change() {
+        let renderer = MarkdownRenderer::new();
+        let mut memo = BlockRenderMemo::default();
+        let source = lines("# Title\nbody");
+        renderer
+            .render_incremental(&mut memo, &source, 30, usize::MAX)
+            .unwrap();
+        let text = renderer
+            .render_incremental(&mut memo, &source, 20, usize::MAX)
+            .unwrap();
+        assert_eq!(memo.last_rendered(), 2);
+        assert_eq!(text.lines[0].width(), 20);
+    }
+
+    #[test]
+    fn test_render_task_list_checkboxes() {
+        let renderer = MarkdownRenderer::new();
+        let markdown = "- [ ] open\n- [x] done".to_string();
+        let rendered = renderer.render_markdown(markdown, 40).unwrap();
+
+        assert!(rendered.lines[0]
+            .spans
+            .iter()
+            .any(|span| span.content == UNCHECKED_GLYPH));
+        assert!(rendered.lines[1]
+            .spans
+            .iter()
+            .any(|span| span.content == CHECKED_GLYPH));
+        assert!(!rendered.lines[1]
+            .spans
+            .iter()
+            .any(|span| span.content.contains("[x]")));
+    }
 }
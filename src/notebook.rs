@@ -0,0 +1,398 @@
+//! Reading and writing thoth notebooks without the TUI.
+//!
+//! A notebook is a markdown file of blocks, each starting with a `# title` line.
+//! Lines of a block that start with `#` are stored with a `\` in front, except in
+//! fenced code, so they are not taken for titles.
+//!
+//! ```
+//! use thoth_cli::{Block, Notebook, NotebookError};
+//!
+//! # fn main() -> Result<(), NotebookError> {
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let path = dir.path().join("notes.md");
+//! let mut notebook = Notebook::open(&path)?;
+//! notebook.insert(Block::new("todo", "- [ ] ship the library"))?;
+//! notebook.update("todo", "- [x] ship the library")?;
+//! notebook.save()?;
+//!
+//! let reopened = Notebook::open(&path)?;
+//! assert_eq!(reopened.get("todo").unwrap().content, "- [x] ship the library");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::wiki_links::relink_blocks;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A titled block of a notebook.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    pub title: String,
+    /// Lines of the block joined with `\n`, without a trailing newline
+    pub content: String,
+}
+
+impl Block {
+    pub fn new(title: impl Into<String>, content: impl Into<String>) -> Self {
+        Block {
+            title: title.into(),
+            content: content.into(),
+        }
+    }
+}
+
+/// Why a notebook operation failed.
+#[derive(Debug)]
+pub enum NotebookError {
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Write {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// No block has this title
+    NotFound(String),
+    /// Another block already has this title
+    DuplicateTitle(String),
+    /// Titles have to be a single line that is not blank
+    InvalidTitle(String),
+}
+
+impl fmt::Display for NotebookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotebookError::Read { path, source } => {
+                write!(f, "Failed to read {}: {}", path.display(), source)
+            }
+            NotebookError::Write { path, source } => {
+                write!(f, "Failed to write {}: {}", path.display(), source)
+            }
+            NotebookError::NotFound(title) => write!(f, "Block '{}' not found", title),
+            NotebookError::DuplicateTitle(title) => {
+                write!(f, "Block '{}' already exists", title)
+            }
+            NotebookError::InvalidTitle(title) => {
+                write!(
+                    f,
+                    "'{}' is not a valid title: it must be one line that is not blank",
+                    title
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for NotebookError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NotebookError::Read { source, .. } | NotebookError::Write { source, .. } => {
+                Some(source)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T, E = NotebookError> = std::result::Result<T, E>;
+
+/// The blocks of a notes file, changed in memory until `save` writes them back.
+///
+/// Blocks are looked up by title. Files written by older versions of thoth may
+/// hold several blocks with the same title, in which case the first one is used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notebook {
+    path: PathBuf,
+    blocks: Vec<Block>,
+}
+
+impl Notebook {
+    /// Reads the notebook at `path`. A file that does not exist yet is an empty notebook.
+    ///
+    /// ```
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// let notebook = thoth_cli::Notebook::open(dir.path().join("new.md")).unwrap();
+    /// assert!(notebook.blocks().is_empty());
+    /// ```
+    pub fn open(path: impl AsRef<Path>) -> Result<Notebook> {
+        let path = path.as_ref().to_path_buf();
+        let blocks = match fs::read_to_string(&path) {
+            Ok(text) => parse_blocks(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(source) => return Err(NotebookError::Read { path, source }),
+        };
+        Ok(Notebook { path, blocks })
+    }
+
+    /// A notebook holding `blocks` that `save` writes to `path`, for blocks that
+    /// come from somewhere other than the file, such as a running TUI.
+    pub fn with_blocks(path: impl AsRef<Path>, blocks: Vec<Block>) -> Notebook {
+        Notebook {
+            path: path.as_ref().to_path_buf(),
+            blocks,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every block, in the order they are shown.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    pub fn get(&self, title: &str) -> Option<&Block> {
+        self.blocks.iter().find(|block| block.title == title)
+    }
+
+    fn position(&self, title: &str) -> Result<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.title == title)
+            .ok_or_else(|| NotebookError::NotFound(title.to_string()))
+    }
+
+    /// Adds `block` after the others. Its title must not be taken.
+    ///
+    /// ```
+    /// use thoth_cli::{Block, Notebook, NotebookError};
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// let mut notebook = Notebook::open(dir.path().join("notes.md")).unwrap();
+    /// notebook.insert(Block::new("ideas", "")).unwrap();
+    /// assert!(matches!(
+    ///     notebook.insert(Block::new("ideas", "again")),
+    ///     Err(NotebookError::DuplicateTitle(_))
+    /// ));
+    /// ```
+    pub fn insert(&mut self, block: Block) -> Result<&Block> {
+        check_title(&block.title)?;
+        if self.get(&block.title).is_some() {
+            return Err(NotebookError::DuplicateTitle(block.title));
+        }
+        self.blocks.push(block);
+        Ok(self.blocks.last().unwrap())
+    }
+
+    /// Replaces the content of the block titled `title`.
+    pub fn update(&mut self, title: &str, content: impl Into<String>) -> Result<&Block> {
+        let index = self.position(title)?;
+        self.blocks[index].content = content.into();
+        Ok(&self.blocks[index])
+    }
+
+    /// Gives the block titled `title` a title that no other block has, pointing
+    /// every wiki link to it at the new title.
+    pub fn rename(&mut self, title: &str, new_title: impl Into<String>) -> Result<&Block> {
+        let new_title = new_title.into();
+        let index = self.position(title)?;
+        check_title(&new_title)?;
+        let taken = self
+            .blocks
+            .iter()
+            .enumerate()
+            .any(|(i, block)| i != index && block.title == new_title);
+        if taken {
+            return Err(NotebookError::DuplicateTitle(new_title));
+        }
+        let titles: Vec<String> = self.blocks.iter().map(|b| b.title.clone()).collect();
+        let lines: Vec<Vec<&str>> = self
+            .blocks
+            .iter()
+            .map(|b| b.content.split('\n').collect())
+            .collect();
+        let blocks: Vec<&[&str]> = lines.iter().map(Vec::as_slice).collect();
+        for (block, lines) in relink_blocks(&titles, &blocks, index, &new_title) {
+            self.blocks[block].content = lines.join("\n");
+        }
+        self.blocks[index].title = new_title;
+        Ok(&self.blocks[index])
+    }
+
+    /// Takes the block titled `title` out of the notebook.
+    pub fn remove(&mut self, title: &str) -> Result<Block> {
+        let index = self.position(title)?;
+        Ok(self.blocks.remove(index))
+    }
+
+    /// Writes the blocks back to the file the notebook was opened from.
+    pub fn save(&self) -> Result<()> {
+        fs::write(&self.path, blocks_to_markdown(&self.blocks)).map_err(|source| {
+            NotebookError::Write {
+                path: self.path.clone(),
+                source,
+            }
+        })
+    }
+}
+
+fn check_title(title: &str) -> Result<()> {
+    if title.trim().is_empty() || title.contains(['\n', '\r']) {
+        return Err(NotebookError::InvalidTitle(title.to_string()));
+    }
+    Ok(())
+}
+
+/// The notes file toggles fenced code on every line starting with three backticks,
+/// whatever their count, unlike the renderer. Escaping follows the same rule, so
+/// longer fences around fenced output still round-trip, and files written before
+/// the renderer matched fences by length load as they always did.
+fn is_fence(line: &str) -> bool {
+    line.trim().starts_with("```")
+}
+
+/// Blocks of a notes file. Lines before the first title are not part of any block.
+pub fn parse_blocks(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(String, Vec<&str>)> = None;
+    let mut in_code_block = false;
+
+    for line in text.lines() {
+        if !in_code_block {
+            if let Some(title) = line.strip_prefix("# ") {
+                blocks.extend(current.take());
+                current = Some((title.to_string(), Vec::new()));
+                continue;
+            }
+        }
+        if is_fence(line) {
+            in_code_block = !in_code_block;
+        }
+        if let Some((_, lines)) = current.as_mut() {
+            let line = match line.strip_prefix('\\') {
+                Some(unescaped) if !in_code_block => unescaped,
+                _ => line,
+            };
+            lines.push(line);
+        }
+    }
+    blocks.extend(current);
+
+    blocks
+        .into_iter()
+        .filter(|(title, _)| !title.is_empty())
+        .map(|(title, lines)| Block::new(title, lines.join("\n")))
+        .collect()
+}
+
+/// The notes file holding `blocks`.
+pub fn blocks_to_markdown(blocks: &[Block]) -> String {
+    let mut text = String::new();
+    for block in blocks {
+        text.push_str("# ");
+        text.push_str(&block.title);
+        text.push('\n');
+        if block.content.is_empty() {
+            continue;
+        }
+        let mut in_code_block = false;
+        for line in block.content.split('\n') {
+            if is_fence(line) {
+                in_code_block = !in_code_block;
+            }
+            if !in_code_block && line.starts_with('#') {
+                text.push('\\');
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_write_round_trip() {
+        let text = "ignored\n# Notes\n\\# not a title\n```sh\n# a comment\n```\n\n# Empty\n# Todo\n- [ ] x\n";
+        let blocks = parse_blocks(text);
+        assert_eq!(
+            blocks,
+            [
+                Block::new("Notes", "# not a title\n```sh\n# a comment\n```\n"),
+                Block::new("Empty", ""),
+                Block::new("Todo", "- [ ] x"),
+            ]
+        );
+        assert_eq!(
+            blocks_to_markdown(&blocks),
+            text.trim_start_matches("ignored\n")
+        );
+    }
+
+    #[test]
+    fn test_files_written_with_toggling_fences_load_unchanged() {
+        // A ```rust line closes the ```md fence, so the #x line after it is escaped
+        let text = "# Notes\n```md\n```rust\n\\#x\n```\n```\n";
+        let blocks = parse_blocks(text);
+        assert_eq!(
+            blocks,
+            [Block::new("Notes", "```md\n```rust\n#x\n```\n```")]
+        );
+        assert_eq!(blocks_to_markdown(&blocks), text);
+
+        // A ```` fence is closed by the first ``` line in it, the next one opens code again
+        let text = "# Notes\n````\n```\n\\# outside\n```\n# inside\n````\n";
+        assert_eq!(
+            parse_blocks(text),
+            [Block::new(
+                "Notes",
+                "````\n```\n# outside\n```\n# inside\n````"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_titles_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut notebook = Notebook::open(dir.path().join("notes.md")).unwrap();
+        notebook.insert(Block::new("a", "1")).unwrap();
+        notebook.insert(Block::new("b", "2")).unwrap();
+        assert!(matches!(
+            notebook.insert(Block::new(" ", "")),
+            Err(NotebookError::InvalidTitle(_))
+        ));
+        assert!(matches!(
+            notebook.rename("a", "b"),
+            Err(NotebookError::DuplicateTitle(title)) if title == "b"
+        ));
+        assert!(matches!(
+            notebook.update("c", ""),
+            Err(NotebookError::NotFound(title)) if title == "c"
+        ));
+        notebook.rename("a", "a2\nb").unwrap_err();
+        assert_eq!(notebook.rename("a", "c").unwrap().content, "1");
+        assert_eq!(notebook.remove("b").unwrap(), Block::new("b", "2"));
+        assert_eq!(notebook.blocks(), [Block::new("c", "1")]);
+    }
+
+    #[test]
+    fn test_rename_rewrites_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut notebook = Notebook::open(dir.path().join("notes.md")).unwrap();
+        notebook.insert(Block::new("Todo", "- [ ] x\n")).unwrap();
+        let linking = "See [[todo#Today|the list]]\n```md\n[[Todo]]\n```\n";
+        notebook.insert(Block::new("Index", linking)).unwrap();
+        notebook.rename("Todo", "Done").unwrap();
+        assert_eq!(
+            notebook.get("Index").unwrap().content,
+            "See [[Done#Today|the list]]\n```md\n[[Todo]]\n```\n"
+        );
+        assert_eq!(notebook.get("Done").unwrap().content, "- [ ] x\n");
+    }
+
+    #[test]
+    fn test_read_errors_keep_the_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = Notebook::open(dir.path()).unwrap_err();
+        assert!(matches!(error, NotebookError::Read { .. }));
+        assert!(error.to_string().starts_with("Failed to read"));
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+/// Escape sequence asking the terminal to put `content` on the system clipboard.
+/// Inside tmux the sequence is wrapped in a DCS passthrough so it reaches the
+/// outer terminal (needs `set -g allow-passthrough on` on tmux 3.3+).
+pub fn osc52_sequence(content: &str, tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(content));
+    if tmux {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+/// Copies `content` by writing the OSC 52 sequence to the controlling terminal,
+/// falling back to stdout when there is none.
+pub fn copy_osc52(content: &str) -> Result<()> {
+    let sequence = osc52_sequence(content, env::var_os("TMUX").is_some());
+    match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => tty.write_all(sequence.as_bytes()),
+        Err(_) => {
+            let mut stdout = io::stdout();
+            stdout
+                .write_all(sequence.as_bytes())
+                .and_then(|_| stdout.flush())
+        }
+    }
+    .context("Failed to write the OSC 52 clipboard sequence to the terminal")
+}
+
+/// Whether an X11 or Wayland display is available for the system clipboard.
+pub fn has_display() -> bool {
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| env::var_os(var).is_some_and(|v| !v.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_sequence() {
+        assert_eq!(osc52_sequence("hi", false), "\x1b]52;c;aGk=\x07");
+        assert_eq!(
+            osc52_sequence("hi", true),
+            "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use crate::code_fence::code_fences;
+
+pub const TOC_START: &str = "<!-- toc -->";
+pub const TOC_END: &str = "<!-- tocstop -->";
+
+/// An ATX heading (`#` to `######`) of a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// Line index within the block
+    pub line: usize,
+    pub level: usize,
+    pub text: String,
+}
+
+fn parse_heading(line: &str) -> Option<(usize, String)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let level = rest.len() - rest.trim_start_matches('#').len();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let text = &rest[level..];
+    if !text.is_empty() && !text.starts_with([' ', '\t']) {
+        return None;
+    }
+    // An optional closing run of `#` is not part of the text
+    let text = text.trim();
+    let without_closing = text.trim_end_matches('#');
+    let text = if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        without_closing.trim_end()
+    } else {
+        text
+    };
+    Some((level, text.to_string()))
+}
+
+/// Headings of `lines` in order, skipping empty ones and anything inside fenced code blocks.
+pub fn headings<S: AsRef<str>>(lines: &[S]) -> Vec<Heading> {
+    let fences = code_fences(lines);
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(row, _)| {
+            !fences
+                .iter()
+                .any(|fence| fence.open <= *row && *row <= fence.close)
+        })
+        .filter_map(|(row, line)| match parse_heading(line.as_ref()) {
+            Some((level, text)) if !text.is_empty() => Some(Heading {
+                line: row,
+                level,
+                text,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// GitHub style anchor for a heading: lowercase, punctuation dropped, spaces as dashes.
+pub fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// A nested list linking to every heading, wrapped in the TOC markers.
+pub fn generate_toc(headings: &[Heading]) -> Vec<String> {
+    let min_level = headings.iter().map(|h| h.level).min().unwrap_or(1);
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut toc = vec![TOC_START.to_string()];
+    for heading in headings {
+        let slug = slugify(&heading.text);
+        // Repeated headings get `-1`, `-2`, ... like GitHub anchors
+        let count = seen.entry(slug.clone()).or_insert(0);
+        let anchor = match *count {
+            0 => slug,
+            n => format!("{}-{}", slug, n),
+        };
+        *count += 1;
+        toc.push(format!(
+            "{}- [{}](#{})",
+            "  ".repeat(heading.level - min_level),
+            heading.text,
+            anchor
+        ));
+    }
+    toc.push(TOC_END.to_string());
+    toc
+}
+
+/// Lines of the existing TOC, markers included, when the block has one.
+fn toc_range<S: AsRef<str>>(lines: &[S]) -> Option<(usize, usize)> {
+    let start = lines.iter().position(|l| l.as_ref().trim() == TOC_START)?;
+    let end = lines[start..]
+        .iter()
+        .position(|l| l.as_ref().trim() == TOC_END)?;
+    Some((start, start + end))
+}
+
+/// Replaces the TOC between the markers, or inserts one before line `row` when
+/// there is none. Returns the new lines and the line of the closing marker.
+pub fn insert_or_refresh_toc<S: AsRef<str>>(lines: &[S], row: usize) -> (Vec<String>, usize) {
+    let toc = generate_toc(&headings(lines));
+    let (start, end) = match toc_range(lines) {
+        Some((start, end)) => (start, end + 1),
+        None => {
+            let row = row.min(lines.len());
+            (row, row)
+        }
+    };
+    let toc_len = toc.len();
+    let updated = lines[..start]
+        .iter()
+        .map(|line| line.as_ref().to_string())
+        .chain(toc)
+        .chain(lines[end..].iter().map(|line| line.as_ref().to_string()))
+        .collect();
+    (updated, start + toc_len - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_headings_skip_code_and_non_headings() {
+        let note = lines("# Title #\n#hashtag\n```sh\n# comment\n```\n  ## Setup & Run\n####### seven\n###\ntext");
+        let found = headings(&note);
+        assert_eq!(
+            found,
+            vec![
+                Heading {
+                    line: 0,
+                    level: 1,
+                    text: "Title".to_string()
+                },
+                Heading {
+                    line: 5,
+                    level: 2,
+                    text: "Setup & Run".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Setup & Run"), "setup--run");
+        assert_eq!(slugify("Use `thoth fmt`!"), "use-thoth-fmt");
+        assert_eq!(slugify("snake_case-Name"), "snake_case-name");
+    }
+
+    #[test]
+    fn test_generate_toc_nests_and_dedupes_anchors() {
+        let note = lines("## Intro\n### Notes\n## Usage\n### Notes");
+        assert_eq!(
+            generate_toc(&headings(&note)),
+            lines(
+                "<!-- toc -->\n- [Intro](#intro)\n  - [Notes](#notes)\n- [Usage](#usage)\n  - [Notes](#notes-1)\n<!-- tocstop -->"
+            )
+        );
+    }
+
+    #[test]
+    fn test_insert_then_refresh_toc() {
+        let note = lines("intro\n# A\ntext");
+        let (with_toc, end) = insert_or_refresh_toc(&note, 1);
+        assert_eq!(
+            with_toc,
+            lines("intro\n<!-- toc -->\n- [A](#a)\n<!-- tocstop -->\n# A\ntext")
+        );
+        assert_eq!(end, 3);
+
+        let mut edited = with_toc.clone();
+        edited.push("## B".to_string());
+        let (refreshed, end) = insert_or_refresh_toc(&edited, 0);
+        assert_eq!(
+            refreshed,
+            lines("intro\n<!-- toc -->\n- [A](#a)\n  - [B](#b)\n<!-- tocstop -->\n# A\ntext\n## B")
+        );
+        assert_eq!(end, 4);
+    }
+}
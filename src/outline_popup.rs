@@ -0,0 +1,76 @@
+/// A heading, or in notebook scope also a block title, listed in the outline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineEntry {
+    pub block: usize,
+    pub line: usize,
+    /// Heading level, 0 for block titles
+    pub level: usize,
+    pub text: String,
+}
+
+pub struct OutlinePopup {
+    pub entries: Vec<OutlineEntry>,
+    pub selected_index: usize,
+    pub visible: bool,
+    /// List the headings of every block instead of only the focused one
+    pub whole_notebook: bool,
+}
+
+impl OutlinePopup {
+    pub fn new() -> Self {
+        OutlinePopup {
+            entries: Vec::new(),
+            selected_index: 0,
+            visible: false,
+            whole_notebook: false,
+        }
+    }
+
+    /// Shows `entries`, selecting the last one at or before `line` of `block`.
+    pub fn open(&mut self, entries: Vec<OutlineEntry>, block: usize, line: usize) {
+        self.selected_index = entries
+            .iter()
+            .rposition(|e| (e.block, e.line) <= (block, line))
+            .unwrap_or(0);
+        self.entries = entries;
+        self.visible = true;
+    }
+
+    pub fn selected_entry(&self) -> Option<&OutlineEntry> {
+        self.entries.get(self.selected_index)
+    }
+}
+
+impl Default for OutlinePopup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(block: usize, line: usize) -> OutlineEntry {
+        OutlineEntry {
+            block,
+            line,
+            level: 1,
+            text: format!("{}:{}", block, line),
+        }
+    }
+
+    #[test]
+    fn test_open_selects_heading_above_cursor() {
+        let mut popup = OutlinePopup::new();
+        assert_eq!(popup.selected_entry(), None);
+
+        let entries = vec![entry(0, 0), entry(1, 2), entry(1, 9)];
+        popup.open(entries.clone(), 1, 5);
+        assert!(popup.visible);
+        assert_eq!(popup.selected_entry(), Some(&entries[1]));
+
+        popup.open(entries.clone(), 0, 0);
+        assert_eq!(popup.selected_index, 0);
+    }
+}
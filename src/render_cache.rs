@@ -0,0 +1,260 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
+
+use ratatui::text::{Line, Span, Text};
+
+pub const DEFAULT_RENDER_CACHE_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RenderKey {
+    pub content_hash: u64,
+    pub width: usize,
+    pub theme: String,
+}
+
+impl RenderKey {
+    pub fn new<S: AsRef<str>>(lines: &[S], width: usize, theme: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        for line in lines {
+            line.as_ref().hash(&mut hasher);
+        }
+        RenderKey {
+            content_hash: hasher.finish(),
+            width,
+            theme: theme.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: usize,
+    pub max_bytes: usize,
+}
+
+struct Entry {
+    text: Text<'static>,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// Least-recently-used cache of rendered blocks, bounded by the approximate
+/// heap size of the cached text rather than by entry count.
+pub struct RenderCache {
+    entries: HashMap<RenderKey, Entry>,
+    recency: BTreeMap<u64, RenderKey>,
+    tick: u64,
+    bytes: usize,
+    max_bytes: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl Default for RenderCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_RENDER_CACHE_BYTES)
+    }
+}
+
+impl RenderCache {
+    pub fn new(max_bytes: usize) -> Self {
+        RenderCache {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            bytes: 0,
+            max_bytes,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &RenderKey) -> Option<Text<'static>> {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                self.recency.remove(&entry.last_used);
+                entry.last_used = tick;
+                self.recency.insert(tick, key.clone());
+                self.hits += 1;
+                Some(entry.text.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: RenderKey, text: Text<'static>) {
+        let bytes = estimate_size(&text);
+        if bytes > self.max_bytes {
+            return;
+        }
+        self.remove(&key);
+        while self.bytes + bytes > self.max_bytes {
+            if !self.evict_oldest() {
+                break;
+            }
+        }
+
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                text,
+                bytes,
+                last_used: self.tick,
+            },
+        );
+        self.bytes += bytes;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.bytes = 0;
+    }
+
+    pub fn stats(&self) -> RenderCacheStats {
+        RenderCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+            entries: self.entries.len(),
+            bytes: self.bytes,
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    fn remove(&mut self, key: &RenderKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+            self.bytes -= entry.bytes;
+        }
+    }
+
+    fn evict_oldest(&mut self) -> bool {
+        let oldest = match self.recency.pop_first() {
+            Some((_, key)) => key,
+            None => return false,
+        };
+        if let Some(entry) = self.entries.remove(&oldest) {
+            self.bytes -= entry.bytes;
+            self.evictions += 1;
+        }
+        true
+    }
+}
+
+/// Approximate heap footprint of a rendered text.
+fn estimate_size(text: &Text) -> usize {
+    size_of::<Text>()
+        + text
+            .lines
+            .iter()
+            .map(|line| {
+                size_of::<Line>()
+                    + line
+                        .spans
+                        .iter()
+                        .map(|span| size_of::<Span>() + span.content.len())
+                        .sum::<usize>()
+            })
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(content: &str) -> Text<'static> {
+        Text::from(content.to_string())
+    }
+
+    #[test]
+    fn test_key_depends_on_content_width_and_theme() {
+        let key = RenderKey::new(&["a"], 10, "dark");
+        assert_eq!(key, RenderKey::new(&["a"], 10, "dark"));
+        assert_ne!(key, RenderKey::new(&["b"], 10, "dark"));
+        assert_ne!(key, RenderKey::new(&["a"], 11, "dark"));
+        assert_ne!(key, RenderKey::new(&["a"], 10, "light"));
+    }
+
+    #[test]
+    fn test_hits_and_misses() {
+        let mut cache = RenderCache::default();
+        let key = RenderKey::new(&["a"], 10, "dark");
+        assert!(cache.get(&key).is_none());
+        cache.insert(key.clone(), text("a"));
+        assert!(cache.get(&key).is_some());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.bytes, estimate_size(&text("a")));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_within_byte_cap() {
+        let entry_size = estimate_size(&text("a"));
+        let mut cache = RenderCache::new(entry_size * 2);
+        let a = RenderKey::new(&["a"], 1, "t");
+        let b = RenderKey::new(&["b"], 1, "t");
+        let c = RenderKey::new(&["c"], 1, "t");
+
+        cache.insert(a.clone(), text("a"));
+        cache.insert(b.clone(), text("b"));
+        // Touch `a` so `b` becomes the least recently used entry
+        cache.get(&a);
+        cache.insert(c.clone(), text("c"));
+
+        assert!(cache.get(&a).is_some());
+        assert!(cache.get(&b).is_none());
+        assert!(cache.get(&c).is_some());
+        assert_eq!(cache.stats().evictions, 1);
+        assert!(cache.stats().bytes <= entry_size * 2);
+    }
+
+    #[test]
+    fn test_reinserting_key_does_not_double_count() {
+        let mut cache = RenderCache::default();
+        let key = RenderKey::new(&["a"], 1, "t");
+        cache.insert(key.clone(), text("a"));
+        cache.insert(key, text("a"));
+        assert_eq!(cache.stats().entries, 1);
+        assert_eq!(cache.stats().bytes, estimate_size(&text("a")));
+    }
+
+    #[test]
+    fn test_oversized_entry_is_not_cached() {
+        let mut cache = RenderCache::new(1);
+        let key = RenderKey::new(&["a"], 1, "t");
+        cache.insert(key.clone(), text("a"));
+        assert!(cache.get(&key).is_none());
+    }
+
+    #[test]
+    fn test_clear_keeps_counters() {
+        let mut cache = RenderCache::default();
+        let key = RenderKey::new(&["a"], 1, "t");
+        cache.insert(key.clone(), text("a"));
+        cache.get(&key);
+        cache.clear();
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.bytes, 0);
+        assert_eq!(stats.hits, 1);
+    }
+}
@@ -1,61 +1,162 @@
 use std::{
     cell::RefCell,
     cmp::{max, min},
-    collections::HashMap,
     rc::Rc,
+    time::{Duration, Instant},
 };
 
+use crate::json_tree::JsonTree;
+use crate::markdown_renderer::{highlight_task, looks_like_json, BlockRenderMemo};
+use crate::outline::headings;
+use crate::render_cache::{RenderCache, RenderCacheStats, RenderKey};
+use crate::table_view::TableView;
+use crate::task_list::{task_progress, task_rows, toggle_task_at, toggle_task_at_cursor};
+use crate::theme::UiPalette;
+use crate::wiki_links::{relink_blocks, resolve_link, wiki_link_at};
 use crate::EditorClipboard;
-use crate::{MarkdownRenderer, ORANGE};
+use crate::MarkdownRenderer;
 use anyhow;
 use anyhow::Result;
 use rand::Rng;
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style},
     text::Text,
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{block::Title, Block, Borders, Paragraph, Wrap},
     Frame,
 };
 use std::collections::HashSet;
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, Scrolling, TextArea};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-const RENDER_CACHE_SIZE: usize = 100;
+/// Rows moved per mouse wheel notch inside a single block.
+const MOUSE_SCROLL_LINES: i16 = 3;
+/// How long a selection has to stay the same before it goes to the primary selection.
+const PRIMARY_SELECTION_DELAY: Duration = Duration::from_millis(300);
 
 struct MarkdownCache {
-    cache: HashMap<String, Text<'static>>,
+    cache: RenderCache,
     renderer: MarkdownRenderer,
+    /// Incremental render state per block, indexed like `ScrollableTextArea::textareas`
+    memos: Vec<BlockRenderMemo>,
 }
 
 impl MarkdownCache {
     fn new() -> Self {
         MarkdownCache {
-            cache: HashMap::with_capacity(RENDER_CACHE_SIZE),
+            cache: RenderCache::default(),
             renderer: MarkdownRenderer::new(),
+            memos: Vec::new(),
         }
     }
 
-    fn get_or_render(&mut self, content: &str, title: &str, width: usize) -> Result<Text<'static>> {
-        let cache_key = format!("{}:{}", title, content);
+    /// Renders block `index`, producing at least `min_rows` rows when the block has them.
+    fn get_or_render(
+        &mut self,
+        index: usize,
+        lines: &[String],
+        width: usize,
+        min_rows: usize,
+    ) -> Result<Text<'static>> {
+        let cache_key = RenderKey::new(lines, width, self.renderer.theme_name());
         if let Some(cached) = self.cache.get(&cache_key) {
-            return Ok(cached.clone());
+            return Ok(cached);
         }
 
-        let content = format!("{}\n", content);
-
+        if self.memos.len() <= index {
+            self.memos.resize_with(index + 1, BlockRenderMemo::default);
+        }
+        let memo = &mut self.memos[index];
         let rendered = self
             .renderer
-            .render_markdown(content, title.to_string(), width)?;
+            .render_incremental(memo, lines, width, min_rows)?;
 
-        if self.cache.len() >= RENDER_CACHE_SIZE {
-            if let Some(old_key) = self.cache.keys().next().cloned() {
-                self.cache.remove(&old_key);
-            }
+        // Partial renders stay in the memo until the rest of the block is needed
+        if memo.is_complete() {
+            self.cache.insert(cache_key, rendered.clone());
         }
-
-        self.cache.insert(cache_key, rendered.clone());
         Ok(rendered)
     }
+
+    fn insert_block(&mut self, index: usize) {
+        if index <= self.memos.len() {
+            self.memos.insert(index, BlockRenderMemo::default());
+        }
+    }
+
+    fn remove_block(&mut self, index: usize) {
+        if index < self.memos.len() {
+            self.memos.remove(index);
+        }
+    }
+
+    /// Drops every render, since all of them were laid out for the old terminal width.
+    fn invalidate_layout(&mut self) {
+        self.cache.clear();
+        self.memos.clear();
+    }
+
+    fn set_theme(&mut self, name: &str, palette: Option<UiPalette>) -> Result<UiPalette> {
+        self.renderer.set_theme(name, palette)?;
+        // Keys already include the theme, but palette overrides do not
+        self.cache.clear();
+        self.memos.clear();
+        Ok(self.renderer.palette())
+    }
+}
+
+/// Number of terminal rows `lines` take up when wrapped at `width` display columns.
+fn wrapped_height(lines: &[String], width: usize) -> usize {
+    lines
+        .iter()
+        .map(|line| line.width().div_ceil(width.max(1)).max(1))
+        .sum()
+}
+
+/// Index of the character drawn `column` cells after character `start` of `line`.
+fn char_index_at_column(line: &str, start: usize, column: usize, tab_len: usize) -> usize {
+    let mut width = 0;
+    for (i, c) in line.chars().enumerate().skip(start) {
+        let char_width = if c == '\t' {
+            tab_len.max(1) - width % tab_len.max(1)
+        } else {
+            c.width().unwrap_or(0)
+        };
+        if width + char_width > column {
+            return i;
+        }
+        width += char_width;
+    }
+    line.chars().count()
+}
+
+/// Moves the cursor of `textarea`, drawn inside `inner`, to the cell at `(column, row)`.
+fn place_cursor(textarea: &mut TextArea, inner: Rect, column: u16, row: u16) {
+    // tui-textarea keeps its scroll offset private, but clamping the origin into
+    // the viewport lands the cursor on the top-left visible cell
+    textarea.move_cursor(CursorMove::Jump(0, 0));
+    textarea.move_cursor(CursorMove::InViewport);
+    let (top_row, top_col) = textarea.cursor();
+
+    let row_offset = row
+        .saturating_sub(inner.y)
+        .min(inner.height.saturating_sub(1));
+    let target_row = (top_row + row_offset as usize).min(textarea.lines().len() - 1);
+    let target_col = char_index_at_column(
+        &textarea.lines()[target_row],
+        top_col,
+        column.saturating_sub(inner.x) as usize,
+        textarea.tab_length() as usize,
+    );
+    textarea.move_cursor(CursorMove::Jump(target_row as u16, target_col as u16));
+}
+
+fn task_progress_title(lines: &[String]) -> Option<Title<'static>> {
+    let (done, total) = task_progress(lines);
+    if total == 0 {
+        return None;
+    }
+    Some(Title::from(format!(" {}/{} done ", done, total)).alignment(Alignment::Right))
 }
 
 pub struct ScrollableTextArea {
@@ -66,8 +167,64 @@ pub struct ScrollableTextArea {
     pub edit_mode: bool,
     pub full_screen_mode: bool,
     pub viewport_height: u16,
+    /// Width of the block list in the last frame, which lines wrap at
+    pub viewport_width: u16,
     pub start_sel: usize,
+    pub palette: UiPalette,
+    pub full_screen_scroll: usize,
+    /// Show JSON and CSV blocks as a tree or table in full screen mode instead of as text
+    pub structured_view_enabled: bool,
+    /// Copy edit mode selections to the primary selection once they stop changing
+    pub primary_selection: bool,
+    /// Selection waiting to be copied to the primary selection, and when it changed
+    pending_primary: Option<(String, Instant)>,
+    /// Task item each block has selected in view mode, counted among its task items
+    selected_tasks: Vec<usize>,
     markdown_cache: Rc<RefCell<MarkdownCache>>,
+    /// Screen area of every block drawn in the last frame, used for mouse hit testing
+    block_areas: Vec<(usize, Rect)>,
+    structured_view: Option<StructuredView>,
+    /// Lines `structured_view` was last built from, kept even when they held no structured data
+    structured_view_source: Vec<String>,
+    /// Where following wiki links came from, most recent last
+    link_history: Vec<LinkHistoryEntry>,
+    /// Opened on first use so a missing clipboard only matters when copying
+    clipboard: Option<EditorClipboard>,
+}
+
+/// A block and cursor position to go back to after following a wiki link.
+/// Kept by title so it survives blocks being added or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LinkHistoryEntry {
+    title: String,
+    cursor: (usize, usize),
+}
+
+/// Interactive full screen view of a block holding structured data.
+pub enum StructuredView {
+    Json(JsonTree),
+    Table(TableView),
+}
+
+impl StructuredView {
+    fn from_lines(lines: &[String]) -> Option<Self> {
+        if looks_like_json(lines) {
+            JsonTree::parse(&lines.join("\n")).map(StructuredView::Json)
+        } else {
+            TableView::from_lines(lines).map(StructuredView::Table)
+        }
+    }
+
+    /// Keeps the state of `previous` when it showed the same kind of data.
+    fn restore(&mut self, previous: StructuredView) {
+        match (self, previous) {
+            (StructuredView::Json(tree), StructuredView::Json(previous)) => tree.restore(previous),
+            (StructuredView::Table(table), StructuredView::Table(previous)) => {
+                table.restore(previous)
+            }
+            _ => {}
+        }
+    }
 }
 
 impl Default for ScrollableTextArea {
@@ -86,25 +243,291 @@ impl ScrollableTextArea {
             edit_mode: false,
             full_screen_mode: false,
             viewport_height: 0,
+            viewport_width: 0,
             start_sel: 0,
+            palette: UiPalette::dark(),
+            full_screen_scroll: 0,
+            structured_view_enabled: true,
+            primary_selection: false,
+            pending_primary: None,
+            selected_tasks: Vec::new(),
             markdown_cache: Rc::new(RefCell::new(MarkdownCache::new())),
+            block_areas: Vec::new(),
+            structured_view: None,
+            structured_view_source: Vec::new(),
+            link_history: Vec::new(),
+            clipboard: None,
         }
     }
 
+    /// Applies a syntax theme and its UI palette, invalidating the render caches.
+    pub fn set_theme(&mut self, name: &str, palette: Option<UiPalette>) -> Result<()> {
+        self.palette = self.markdown_cache.borrow_mut().set_theme(name, palette)?;
+        Ok(())
+    }
+
+    /// Why the syntaxes in the syntaxes directory could not be loaded.
+    pub fn syntax_error(&self) -> Option<String> {
+        self.markdown_cache
+            .borrow()
+            .renderer
+            .syntax_error()
+            .map(str::to_string)
+    }
+
+    /// Why some themes in the themes directory could not be loaded.
+    pub fn theme_error(&self) -> Option<String> {
+        self.markdown_cache
+            .borrow()
+            .renderer
+            .theme_error()
+            .map(str::to_string)
+    }
+
+    pub fn theme_name(&self) -> String {
+        self.markdown_cache
+            .borrow()
+            .renderer
+            .theme_name()
+            .to_string()
+    }
+
+    pub fn theme_names(&self) -> Vec<String> {
+        self.markdown_cache.borrow().renderer.theme_names()
+    }
+
+    pub fn render_cache_stats(&self) -> RenderCacheStats {
+        self.markdown_cache.borrow().cache.stats()
+    }
+
     pub fn toggle_full_screen(&mut self) {
         self.full_screen_mode = !self.full_screen_mode;
+        self.full_screen_scroll = 0;
+        self.structured_view = None;
+        self.structured_view_source.clear();
+        self.structured_view_enabled = true;
         if self.full_screen_mode {
             self.edit_mode = false;
         }
     }
 
+    /// The tree or table shown for the focused block, when it is on screen.
+    pub fn active_structured_view(&mut self) -> Option<&mut StructuredView> {
+        if self.full_screen_mode && !self.edit_mode && self.structured_view_enabled {
+            self.structured_view.as_mut()
+        } else {
+            None
+        }
+    }
+
+    pub fn active_json_tree(&mut self) -> Option<&mut JsonTree> {
+        match self.active_structured_view() {
+            Some(StructuredView::Json(tree)) => Some(tree),
+            _ => None,
+        }
+    }
+
+    pub fn active_table(&mut self) -> Option<&mut TableView> {
+        match self.active_structured_view() {
+            Some(StructuredView::Table(table)) => Some(table),
+            _ => None,
+        }
+    }
+
+    /// Rebuilds the structured view when the focused block changed since it was built.
+    fn sync_structured_view(&mut self) {
+        let lines = match self.textareas.get(self.focused_index) {
+            Some(textarea) => textarea.lines(),
+            None => return,
+        };
+        if self.structured_view_source == lines {
+            return;
+        }
+        let view = StructuredView::from_lines(lines);
+        self.structured_view_source = lines.to_vec();
+        self.structured_view = match (view, self.structured_view.take()) {
+            (Some(mut view), Some(previous)) => {
+                view.restore(previous);
+                Some(view)
+            }
+            (view, _) => view,
+        };
+    }
+
+    /// Drops layout computed for the previous terminal size.
+    pub fn handle_resize(&mut self, viewport_height: u16) {
+        self.viewport_height = viewport_height;
+        self.block_areas.clear();
+        self.markdown_cache.borrow_mut().invalidate_layout();
+        if !self.textareas.is_empty() {
+            self.adjust_scroll_to_focused();
+        }
+    }
+
+    /// Block index and screen area of the block drawn at `(column, row)` in the last frame.
+    pub fn block_at(&self, column: u16, row: u16) -> Option<(usize, Rect)> {
+        self.block_areas
+            .iter()
+            .find(|(_, area)| {
+                column >= area.x
+                    && column < area.x + area.width
+                    && row >= area.y
+                    && row < area.y + area.height
+            })
+            .copied()
+    }
+
+    /// Focuses the clicked block, or moves the cursor to the clicked cell when it is being edited.
+    pub fn click(&mut self, column: u16, row: u16) {
+        let (index, area) = match self.block_at(column, row) {
+            Some(hit) => hit,
+            None => return,
+        };
+
+        if index != self.focused_index {
+            self.focused_index = index;
+            self.edit_mode = false;
+            self.full_screen_scroll = 0;
+            self.adjust_scroll_to_focused();
+        } else if self.edit_mode {
+            let inner = Block::default().borders(Borders::ALL).inner(area);
+            let textarea = &mut self.textareas[index];
+            textarea.cancel_selection();
+            self.start_sel = usize::MAX;
+            place_cursor(textarea, inner, column, row);
+        }
+    }
+
+    /// Scrolls by `delta` mouse wheel notches: lines of the edited or full screen
+    /// block, or whole blocks in the block list.
+    pub fn scroll_by(&mut self, delta: isize) {
+        if self.edit_mode {
+            if let Some(textarea) = self.textareas.get_mut(self.focused_index) {
+                textarea.scroll(Scrolling::Delta {
+                    rows: delta as i16 * MOUSE_SCROLL_LINES,
+                    cols: 0,
+                });
+            }
+        } else if let Some(view) = self.active_structured_view() {
+            let rows = delta * MOUSE_SCROLL_LINES as isize;
+            match view {
+                StructuredView::Json(tree) => tree.move_selection(rows),
+                StructuredView::Table(table) => table.move_row(rows),
+            }
+        } else if self.full_screen_mode {
+            self.full_screen_scroll = self
+                .full_screen_scroll
+                .saturating_add_signed(delta * MOUSE_SCROLL_LINES as isize);
+        } else {
+            let max_scroll = self.textareas.len().saturating_sub(1);
+            self.scroll = self.scroll.saturating_add_signed(delta).min(max_scroll);
+            // Keep the focused block on screen
+            if self.focused_index < self.scroll {
+                self.focused_index = self.scroll;
+            }
+        }
+    }
+
+    /// Inserts pasted text at the cursor of the focused block as a single edit.
+    pub fn paste_text(&mut self, text: &str) -> bool {
+        // Terminals send line breaks in bracketed pastes as carriage returns
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        match self.textareas.get_mut(self.focused_index) {
+            Some(textarea) => {
+                textarea.insert_str(text);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Renames the focused block and points every wiki link to it at the new title.
     pub fn change_title(&mut self, new_title: String) {
         let unique_title = self.generate_unique_title(new_title);
-        if self.focused_index < self.titles.len() {
-            self.titles[self.focused_index] = unique_title;
+        self.rename_block(self.focused_index, unique_title);
+    }
+
+    /// Gives block `index` the title `new_title` as is and points every wiki link to
+    /// it at the new title.
+    pub fn rename_block(&mut self, index: usize, new_title: String) {
+        if index >= self.titles.len() {
+            return;
+        }
+
+        let blocks: Vec<&[String]> = self.textareas.iter().map(|t| t.lines()).collect();
+        for (block, lines) in relink_blocks(&self.titles, &blocks, index, &new_title) {
+            let (row, col) = self.textareas[block].cursor();
+            let mut textarea = TextArea::new(lines);
+            textarea.move_cursor(CursorMove::Jump(row as u16, col as u16));
+            self.textareas[block] = textarea;
+        }
+
+        let old_title = std::mem::replace(&mut self.titles[index], new_title);
+        for entry in &mut self.link_history {
+            if entry.title == old_title {
+                entry.title.clone_from(&self.titles[index]);
+            }
         }
     }
 
+    /// Follows the wiki link under the cursor of the focused block, creating the
+    /// linked block when no block has that title yet. Returns false when the
+    /// cursor is not on a link.
+    pub fn follow_link_at_cursor(&mut self) -> bool {
+        let Some(textarea) = self.textareas.get(self.focused_index) else {
+            return false;
+        };
+        let (row, col) = textarea.cursor();
+        let Some(link) = textarea
+            .lines()
+            .get(row)
+            .and_then(|line| wiki_link_at(line, col))
+        else {
+            return false;
+        };
+
+        self.push_link_history();
+        match resolve_link(&self.titles, &link.target) {
+            Some(index) => self.jump_to_textarea(index),
+            None => self.add_textarea(TextArea::default(), link.target),
+        }
+
+        let textarea = &mut self.textareas[self.focused_index];
+        let line = link.heading.and_then(|heading| {
+            let heading = heading.to_lowercase();
+            headings(textarea.lines())
+                .into_iter()
+                .find(|h| h.text.to_lowercase() == heading)
+                .map(|h| h.line)
+        });
+        textarea.move_cursor(CursorMove::Jump(line.unwrap_or(0) as u16, 0));
+        true
+    }
+
+    /// Remembers the focused block and cursor so `navigate_back` can return to it.
+    pub fn push_link_history(&mut self) {
+        if let Some(textarea) = self.textareas.get(self.focused_index) {
+            self.link_history.push(LinkHistoryEntry {
+                title: self.titles[self.focused_index].clone(),
+                cursor: textarea.cursor(),
+            });
+        }
+    }
+
+    /// Returns to where the last followed link was, skipping blocks deleted since.
+    /// Returns false when there is nowhere to go back to.
+    pub fn navigate_back(&mut self) -> bool {
+        while let Some(entry) = self.link_history.pop() {
+            if let Some(index) = self.titles.iter().position(|t| *t == entry.title) {
+                self.jump_to_textarea(index);
+                let (row, col) = entry.cursor;
+                self.textareas[index].move_cursor(CursorMove::Jump(row as u16, col as u16));
+                return true;
+            }
+        }
+        false
+    }
+
     fn generate_unique_title(&self, base_title: String) -> String {
         if !self.titles.contains(&base_title) {
             return base_title;
@@ -137,21 +560,105 @@ impl ScrollableTextArea {
         let unique_title = self.generate_unique_title(title);
         self.textareas.insert(new_index, textarea);
         self.titles.insert(new_index, unique_title);
+        self.markdown_cache.borrow_mut().insert_block(new_index);
+        if new_index <= self.selected_tasks.len() {
+            self.selected_tasks.insert(new_index, 0);
+        }
         self.focused_index = new_index;
         self.adjust_scroll_to_focused();
     }
 
-    pub fn copy_textarea_contents(&self) -> Result<()> {
+    /// Adds a block after all the others without moving the focus, as done for blocks
+    /// added from the CLI while the TUI is open. Returns the title it got.
+    pub fn append_textarea(&mut self, textarea: TextArea<'static>, title: String) -> String {
+        let unique_title = self.generate_unique_title(title);
+        let index = self.textareas.len();
+        self.textareas.push(textarea);
+        self.titles.push(unique_title.clone());
+        self.markdown_cache.borrow_mut().insert_block(index);
+        if index == self.selected_tasks.len() {
+            self.selected_tasks.push(0);
+        }
+        unique_title
+    }
+
+    pub fn copy_textarea_contents(&mut self) -> Result<()> {
         if let Some(textarea) = self.textareas.get(self.focused_index) {
-            let content = textarea.lines().join("\n");
-            let mut ctx = EditorClipboard::new()
-                .map_err(|e| anyhow::anyhow!("Failed to create clipboard context: {}", e))?;
-            ctx.set_contents(content)
-                .map_err(|e| anyhow::anyhow!("Failed to set clipboard contents: {}", e))?;
+            self.copy_text(textarea.lines().join("\n"))?;
         }
         Ok(())
     }
 
+    /// Uses `clipboard` instead of the one chosen in the config file.
+    pub fn set_clipboard(&mut self, clipboard: EditorClipboard) {
+        self.clipboard = Some(clipboard);
+    }
+
+    fn clipboard(&mut self) -> Result<&mut EditorClipboard> {
+        if self.clipboard.is_none() {
+            let clipboard = EditorClipboard::new()
+                .map_err(|e| anyhow::anyhow!("Failed to create clipboard context: {}", e))?;
+            self.clipboard = Some(clipboard);
+        }
+        Ok(self.clipboard.as_mut().unwrap())
+    }
+
+    pub fn copy_text(&mut self, content: String) -> Result<()> {
+        self.clipboard()?
+            .set_contents(content)
+            .map_err(|e| anyhow::anyhow!("Failed to set clipboard contents: {}", e))
+    }
+
+    pub fn clipboard_history(&mut self) -> Result<Vec<String>> {
+        self.clipboard()?
+            .history_entries()
+            .map_err(|e| anyhow::anyhow!("Failed to read clipboard history: {}", e))
+    }
+
+    pub fn clipboard_contents(&mut self) -> Result<String> {
+        self.clipboard()?
+            .get_content()
+            .map_err(|e| anyhow::anyhow!("Failed to read clipboard contents: {}", e))
+    }
+
+    /// Toggles the task item on the cursor line in edit mode, or the selected one in
+    /// view mode, where the cursor is not shown.
+    pub fn toggle_task_on_focused(&mut self) -> bool {
+        let selected = self.selected_task_row();
+        let edit_mode = self.edit_mode;
+        match (self.textareas.get_mut(self.focused_index), selected) {
+            (Some(textarea), _) if edit_mode => toggle_task_at_cursor(textarea),
+            (Some(textarea), Some(row)) => toggle_task_at(textarea, row),
+            _ => false,
+        }
+    }
+
+    /// The task item selected in the focused block, counted among its task items.
+    pub fn selected_task(&self) -> Option<usize> {
+        let count = task_rows(self.textareas.get(self.focused_index)?.lines()).len();
+        let selected = self.selected_tasks.get(self.focused_index).copied();
+        (count > 0).then(|| selected.unwrap_or(0).min(count - 1))
+    }
+
+    fn selected_task_row(&self) -> Option<usize> {
+        let rows = task_rows(self.textareas.get(self.focused_index)?.lines());
+        rows.get(self.selected_task()?).copied()
+    }
+
+    /// Selects the task item `delta` items away in the focused block, stopping at the
+    /// first and last.
+    pub fn move_task_selection(&mut self, delta: isize) {
+        let Some(current) = self.selected_task() else {
+            return;
+        };
+        let count = task_rows(self.textareas[self.focused_index].lines()).len();
+        let selected = current.saturating_add_signed(delta).min(count - 1);
+        if self.selected_tasks.len() <= self.focused_index {
+            self.selected_tasks.resize(self.focused_index + 1, 0);
+        }
+        self.selected_tasks[self.focused_index] = selected;
+    }
+
     pub fn jump_to_textarea(&mut self, index: usize) {
         if index < self.textareas.len() {
             self.focused_index = index;
@@ -163,6 +670,10 @@ impl ScrollableTextArea {
         if index < self.textareas.len() {
             self.textareas.remove(index);
             self.titles.remove(index);
+            self.markdown_cache.borrow_mut().remove_block(index);
+            if index < self.selected_tasks.len() {
+                self.selected_tasks.remove(index);
+            }
             if self.focused_index >= self.textareas.len() {
                 self.focused_index = self.textareas.len().saturating_sub(1);
             }
@@ -184,8 +695,7 @@ impl ScrollableTextArea {
         } else {
             let mut height_sum = 0;
             for i in self.scroll..=self.focused_index {
-                let textarea_height = self.textareas[i].lines().len().max(3) as u16 + 2;
-                height_sum += textarea_height;
+                height_sum += self.block_height(i);
 
                 if height_sum > self.viewport_height {
                     self.scroll = i;
@@ -202,89 +712,150 @@ impl ScrollableTextArea {
     }
 
     pub fn calculate_height_to_focused(&self) -> u16 {
-        self.textareas[self.scroll..=self.focused_index]
-            .iter()
-            .map(|ta| ta.lines().len().max(3) as u16 + 2)
+        (self.scroll..=self.focused_index)
+            .map(|i| self.block_height(i))
             .sum()
     }
 
+    /// Rows block `index` takes in the block list, borders included, as `render` sizes it.
+    fn block_height(&self, index: usize) -> u16 {
+        let lines = self.textareas[index].lines();
+        let content_height = if self.viewport_width == 0 {
+            // Not drawn yet, so the width lines wrap at is unknown
+            lines.len()
+        } else {
+            wrapped_height(lines, self.viewport_width.saturating_sub(2) as usize)
+        };
+        (content_height as u16 + 2).max(3)
+    }
+
     pub fn initialize_scroll(&mut self) {
         self.scroll = 0;
         self.focused_index = 0;
     }
 
-    pub fn copy_focused_textarea_contents(&self) -> anyhow::Result<()> {
-        if let Some(textarea) = self.textareas.get(self.focused_index) {
-            let content = textarea.lines().join("\n");
-            let mut ctx = EditorClipboard::new().unwrap();
-            ctx.set_contents(content).unwrap();
-        }
-        Ok(())
+    pub fn copy_focused_textarea_contents(&mut self) -> anyhow::Result<()> {
+        self.copy_textarea_contents()
+    }
+
+    /// Lines between where the selection started and the cursor row.
+    fn selected_text(&self) -> Option<String> {
+        let textarea = self.textareas.get(self.focused_index)?;
+        let all_lines = textarea.lines();
+        let (cur_row, _) = textarea.cursor();
+        let min_row = min(cur_row, self.start_sel);
+        let max_row = max(cur_row, self.start_sel);
+
+        (max_row <= all_lines.len()).then(|| all_lines[min_row..max_row].join("\n"))
     }
 
     pub fn copy_selection_contents(&mut self) -> anyhow::Result<()> {
-        if let Some(textarea) = self.textareas.get(self.focused_index) {
-            let all_lines = textarea.lines();
-            let (cur_row, _) = textarea.cursor();
-            let min_row = min(cur_row, self.start_sel);
-            let max_row = max(cur_row, self.start_sel);
-
-            if max_row <= all_lines.len() {
-                let content = all_lines[min_row..max_row].join("\n");
-                let mut ctx = EditorClipboard::new().unwrap();
-                ctx.set_contents(content).unwrap();
-            }
-        }
+        let content = self.selected_text();
         // reset selection
         self.start_sel = 0;
-        Ok(())
+        match content {
+            Some(content) => self.copy_text(content),
+            None => Ok(()),
+        }
+    }
+
+    /// Queues the current selection for the primary selection when that is turned on.
+    /// It is copied by `flush_primary_selection` once it stops changing.
+    pub fn mirror_selection_to_primary(&mut self) {
+        if !self.primary_selection {
+            return;
+        }
+        if let Some(content) = self.selected_text().filter(|content| !content.is_empty()) {
+            self.pending_primary = Some((content, Instant::now()));
+        }
+    }
+
+    /// How long until the queued selection is due to be copied, if there is one.
+    pub fn primary_selection_due(&self) -> Option<Duration> {
+        self.pending_primary
+            .as_ref()
+            .map(|(_, changed)| PRIMARY_SELECTION_DELAY.saturating_sub(changed.elapsed()))
+    }
+
+    /// Copies the queued selection to the primary selection once it has not changed
+    /// for a moment, or right away with `now`.
+    pub fn flush_primary_selection(&mut self, now: bool) -> Result<()> {
+        if !now && self.primary_selection_due() != Some(Duration::ZERO) {
+            return Ok(());
+        }
+        let Some((content, _)) = self.pending_primary.take() else {
+            return Ok(());
+        };
+        self.clipboard()?
+            .set_primary(content)
+            .map_err(|e| anyhow::anyhow!("Failed to set the primary selection: {}", e))
+    }
+
+    pub fn primary_contents(&mut self) -> Result<String> {
+        // A selection made just before pasting should be what gets pasted
+        self.flush_primary_selection(true)?;
+        self.clipboard()?
+            .get_primary()
+            .map_err(|e| anyhow::anyhow!("Failed to read the primary selection: {}", e))
     }
 
     fn render_full_screen_edit(&mut self, f: &mut Frame, area: Rect) {
+        let palette = self.palette;
         let textarea = &mut self.textareas[self.focused_index];
         let title = &self.titles[self.focused_index];
 
-        let block = Block::default()
+        let mut block = Block::default()
             .title(title.clone())
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(ORANGE));
+            .border_style(Style::default().fg(palette.border));
+        if let Some(progress) = task_progress_title(textarea.lines()) {
+            block = block.title(progress);
+        }
 
-        let edit_style = Style::default().fg(Color::White).bg(Color::Black);
-        let cursor_style = Style::default().fg(Color::White).bg(ORANGE);
+        let edit_style = Style::default().fg(palette.edit_fg).bg(palette.edit_bg);
+        let cursor_style = Style::default().fg(palette.cursor_fg).bg(palette.cursor_bg);
 
         textarea.set_block(block);
         textarea.set_style(edit_style);
         textarea.set_cursor_style(cursor_style);
-        textarea.set_selection_style(Style::default().bg(Color::Red));
+        textarea.set_selection_style(Style::default().bg(palette.selection_bg));
         f.render_widget(textarea.widget(), area);
     }
 
     pub fn render(&mut self, f: &mut Frame, area: Rect) -> Result<()> {
         self.viewport_height = area.height;
+        self.viewport_width = area.width;
+        self.block_areas.clear();
 
         if self.full_screen_mode {
+            self.block_areas.push((self.focused_index, area));
             if self.edit_mode {
                 self.render_full_screen_edit(f, area);
             } else {
                 self.render_full_screen(f, area)?;
             }
         } else {
+            let palette = self.palette;
+            let selected_task = self.selected_task();
             let mut remaining_height = area.height;
             let mut visible_textareas = Vec::with_capacity(self.textareas.len());
 
+            let heights: Vec<u16> = (0..self.textareas.len())
+                .map(|i| self.block_height(i))
+                .collect();
             for (i, textarea) in self.textareas.iter_mut().enumerate().skip(self.scroll) {
                 if remaining_height == 0 {
                     break;
                 }
 
-                let content_height = textarea.lines().len() as u16 + 2;
+                let content_height = heights[i];
                 let is_focused = i == self.focused_index;
                 let is_editing = is_focused && self.edit_mode;
 
                 let height = if is_editing {
                     remaining_height
                 } else {
-                    content_height.min(remaining_height).max(3)
+                    content_height.min(remaining_height)
                 };
 
                 visible_textareas.push((i, textarea, height));
@@ -306,38 +877,53 @@ impl ScrollableTextArea {
                 .split(area);
 
             for ((i, textarea, _), chunk) in visible_textareas.into_iter().zip(chunks.iter()) {
+                self.block_areas.push((i, *chunk));
                 let title = &self.titles[i];
                 let is_focused = i == self.focused_index;
                 let is_editing = is_focused && self.edit_mode;
 
                 let style = if is_focused {
                     if is_editing {
-                        Style::default().fg(Color::White).bg(Color::Black)
+                        Style::default().fg(palette.edit_fg).bg(palette.edit_bg)
                     } else {
-                        Style::default().fg(Color::Black).bg(Color::DarkGray)
+                        Style::default()
+                            .fg(palette.focused_fg)
+                            .bg(palette.focused_bg)
                     }
                 } else {
-                    Style::default().fg(Color::White).bg(Color::Reset)
+                    Style::default().fg(palette.text_fg).bg(Color::Reset)
                 };
 
-                let block = Block::default()
+                let mut block = Block::default()
                     .title(title.to_owned())
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(ORANGE))
+                    .border_style(Style::default().fg(palette.border))
                     .style(style);
+                if let Some(progress) = task_progress_title(textarea.lines()) {
+                    block = block.title(progress);
+                }
 
                 if is_editing {
                     textarea.set_block(block);
                     textarea.set_style(style);
-                    textarea.set_cursor_style(Style::default().fg(Color::White).bg(ORANGE));
+                    textarea.set_cursor_style(
+                        Style::default().fg(palette.cursor_fg).bg(palette.cursor_bg),
+                    );
                     f.render_widget(textarea.widget(), *chunk);
                 } else {
-                    let content = textarea.lines().join("\n");
-                    let rendered_markdown = self.markdown_cache.borrow_mut().get_or_render(
-                        &content,
-                        title,
-                        f.size().width as usize - 2,
+                    let mut rendered_markdown = self.markdown_cache.borrow_mut().get_or_render(
+                        i,
+                        textarea.lines(),
+                        chunk.width.saturating_sub(2) as usize,
+                        chunk.height.saturating_sub(2) as usize,
                     )?;
+                    if let Some(task) = selected_task.filter(|_| is_focused) {
+                        highlight_task(
+                            &mut rendered_markdown,
+                            task,
+                            Style::default().bg(palette.selection_bg),
+                        );
+                    }
                     let paragraph = Paragraph::new(rendered_markdown)
                         .block(block)
                         .wrap(Wrap { trim: true });
@@ -350,35 +936,95 @@ impl ScrollableTextArea {
     }
 
     fn render_full_screen(&mut self, f: &mut Frame, area: Rect) -> Result<()> {
+        if self.structured_view_enabled {
+            self.sync_structured_view();
+            if self.structured_view.is_some() {
+                self.render_structured_view(f, area);
+                return Ok(());
+            }
+        }
+
+        let palette = self.palette;
+        let selected_task = self.selected_task();
         let textarea = &mut self.textareas[self.focused_index];
-        textarea.set_selection_style(Style::default().bg(Color::Red));
+        textarea.set_selection_style(Style::default().bg(palette.selection_bg));
         let title = &self.titles[self.focused_index];
 
-        let block = Block::default()
+        let mut block = Block::default()
             .title(title.clone())
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(ORANGE));
+            .border_style(Style::default().fg(palette.border));
+        if let Some(progress) = task_progress_title(textarea.lines()) {
+            block = block.title(progress);
+        }
 
-        let content = textarea.lines().join("\n");
-        let rendered_markdown = self.markdown_cache.borrow_mut().get_or_render(
-            &content,
-            title,
-            f.size().width as usize - 2,
+        // Only the rows from the scroll offset to the bottom of the screen are rendered
+        let inner_height = area.height.saturating_sub(2) as usize;
+        let mut rendered_markdown = self.markdown_cache.borrow_mut().get_or_render(
+            self.focused_index,
+            textarea.lines(),
+            area.width.saturating_sub(2) as usize,
+            self.full_screen_scroll + inner_height,
         )?;
+        if let Some(task) = selected_task {
+            highlight_task(
+                &mut rendered_markdown,
+                task,
+                Style::default().bg(palette.selection_bg),
+            );
+        }
+        // A short render means the whole block fit, so stop scrolling at its end
+        if rendered_markdown.lines.len() < self.full_screen_scroll + inner_height {
+            self.full_screen_scroll = rendered_markdown.lines.len().saturating_sub(inner_height);
+        }
+        let visible: Vec<_> = rendered_markdown
+            .lines
+            .into_iter()
+            .skip(self.full_screen_scroll)
+            .collect();
 
-        let paragraph = Paragraph::new(rendered_markdown)
+        let paragraph = Paragraph::new(Text::from(visible))
             .block(block)
-            .wrap(Wrap { trim: true })
-            .scroll((self.scroll as u16, 0));
+            .wrap(Wrap { trim: true });
 
         f.render_widget(paragraph, area);
         Ok(())
     }
+
+    fn render_structured_view(&mut self, f: &mut Frame, area: Rect) {
+        let palette = self.palette;
+        let (width, height) = (
+            area.width.saturating_sub(2) as usize,
+            area.height.saturating_sub(2) as usize,
+        );
+        let (hints, lines) = match self.structured_view.as_mut() {
+            Some(StructuredView::Json(tree)) => (
+                String::from("←→:Fold Space:Toggle p:Copy Path c:Copy Value r:Raw"),
+                tree.render_lines(height, &palette),
+            ),
+            Some(StructuredView::Table(table)) => (
+                format!(
+                    "Row {}/{} s:Sort c:Copy Column r:Raw",
+                    (table.selected_row + 1).min(table.row_count()),
+                    table.row_count()
+                ),
+                table.render_lines(width, height, &palette),
+            ),
+            None => return,
+        };
+        let block = Block::default()
+            .title(self.titles[self.focused_index].clone())
+            .title(Title::from(hints).alignment(Alignment::Right))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(palette.border));
+        f.render_widget(Paragraph::new(Text::from(lines)).block(block), area);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clipboard::{ClipboardMode, MemoryProvider};
 
     fn create_test_textarea() -> ScrollableTextArea {
         ScrollableTextArea {
@@ -389,8 +1035,20 @@ mod tests {
             edit_mode: false,
             full_screen_mode: false,
             viewport_height: 0,
+            viewport_width: 0,
             start_sel: 0,
+            palette: UiPalette::dark(),
+            full_screen_scroll: 0,
+            structured_view_enabled: true,
+            primary_selection: false,
+            pending_primary: None,
+            selected_tasks: Vec::new(),
             markdown_cache: Rc::new(RefCell::new(MarkdownCache::new())),
+            block_areas: Vec::new(),
+            structured_view: None,
+            structured_view_source: Vec::new(),
+            link_history: Vec::new(),
+            clipboard: None,
         }
     }
 
@@ -433,6 +1091,60 @@ mod tests {
         assert_eq!(sta.titles[0], "New Title");
     }
 
+    #[test]
+    fn test_follow_link_and_navigate_back() {
+        let mut sta = create_test_textarea();
+        sta.add_textarea(
+            TextArea::new(vec!["see [[api#Auth]]".to_string()]),
+            "Index".to_string(),
+        );
+        sta.add_textarea(
+            TextArea::new(vec!["# API".to_string(), "## Auth".to_string()]),
+            "API".to_string(),
+        );
+        sta.jump_to_textarea(0);
+        assert!(!sta.follow_link_at_cursor());
+
+        sta.textareas[0].move_cursor(CursorMove::Jump(0, 8));
+        assert!(sta.follow_link_at_cursor());
+        assert_eq!(sta.focused_index, 1);
+        assert_eq!(sta.textareas[1].cursor(), (1, 0));
+
+        assert!(sta.navigate_back());
+        assert_eq!(sta.focused_index, 0);
+        assert_eq!(sta.textareas[0].cursor(), (0, 8));
+        assert!(!sta.navigate_back());
+    }
+
+    #[test]
+    fn test_follow_missing_link_creates_block() {
+        let mut sta = create_test_textarea();
+        sta.add_textarea(
+            TextArea::new(vec!["[[Ideas]]".to_string()]),
+            "Index".to_string(),
+        );
+        assert!(sta.follow_link_at_cursor());
+        assert_eq!(sta.titles, vec!["Index", "Ideas"]);
+        assert_eq!(sta.focused_index, 1);
+    }
+
+    #[test]
+    fn test_change_title_rewrites_links() {
+        let mut sta = create_test_textarea();
+        sta.add_textarea(
+            TextArea::new(vec!["[[todo|tasks]] and [[Other]]".to_string()]),
+            "Index".to_string(),
+        );
+        sta.add_textarea(TextArea::default(), "Todo".to_string());
+        sta.push_link_history();
+        sta.change_title("Tasks".to_string());
+        assert_eq!(sta.textareas[0].lines(), ["[[Tasks|tasks]] and [[Other]]"]);
+
+        sta.jump_to_textarea(0);
+        assert!(sta.navigate_back());
+        assert_eq!(sta.focused_index, 1);
+    }
+
     #[test]
     fn test_toggle_full_screen() {
         let mut sta = create_test_textarea();
@@ -448,6 +1160,10 @@ mod tests {
         let mut textarea = TextArea::default();
         textarea.insert_str("Test content");
         sta.add_textarea(textarea, "Test".to_string());
+        // Without the clipboard history, which tests should not write to
+        if let Ok(clipboard) = EditorClipboard::with_mode(ClipboardMode::Auto) {
+            sta.set_clipboard(clipboard);
+        }
 
         let result = sta.copy_textarea_contents();
 
@@ -464,6 +1180,331 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_copy_selection_to_memory_clipboard() {
+        let memory = MemoryProvider::new();
+        let mut sta = create_test_textarea();
+        sta.set_clipboard(EditorClipboard::with_provider(Box::new(memory.clone())));
+        let lines = vec!["one", "two", "three"].into_iter().map(String::from);
+        sta.add_textarea(TextArea::new(lines.collect()), "Test".to_string());
+
+        sta.start_sel = 0;
+        sta.textareas[0].move_cursor(CursorMove::Jump(2, 0));
+        sta.copy_selection_contents().unwrap();
+        assert_eq!(memory.contents(), "one\ntwo");
+        assert_eq!(sta.start_sel, 0);
+
+        sta.copy_focused_textarea_contents().unwrap();
+        assert_eq!(memory.contents(), "one\ntwo\nthree");
+        assert_eq!(sta.clipboard_contents().unwrap(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn test_append_textarea_keeps_focus() {
+        let mut sta = create_test_textarea();
+        sta.add_textarea(TextArea::default(), "Notes".to_string());
+        sta.add_textarea(TextArea::default(), "Todo".to_string());
+        sta.move_focus(-1);
+        let title = sta.append_textarea(TextArea::default(), "Notes".to_string());
+        assert_eq!(title, "Notes 1");
+        assert_eq!(sta.titles, ["Notes", "Todo", "Notes 1"]);
+        assert_eq!(sta.focused_index, 0);
+    }
+
+    #[test]
+    fn test_mirror_selection_to_primary() {
+        let memory = MemoryProvider::new();
+        let mut sta = create_test_textarea();
+        sta.set_clipboard(EditorClipboard::with_provider(Box::new(memory.clone())));
+        let lines = vec!["one", "two", "three"].into_iter().map(String::from);
+        sta.add_textarea(TextArea::new(lines.collect()), "Test".to_string());
+        sta.start_sel = 0;
+        sta.textareas[0].move_cursor(CursorMove::Jump(1, 0));
+
+        sta.mirror_selection_to_primary();
+        sta.flush_primary_selection(true).unwrap();
+        assert_eq!(memory.primary_contents(), "");
+
+        sta.primary_selection = true;
+        sta.mirror_selection_to_primary();
+        // Copied only once the selection stops changing
+        sta.flush_primary_selection(false).unwrap();
+        assert_eq!(memory.primary_contents(), "");
+        sta.textareas[0].move_cursor(CursorMove::Jump(2, 0));
+        sta.mirror_selection_to_primary();
+        std::thread::sleep(PRIMARY_SELECTION_DELAY);
+        sta.flush_primary_selection(false).unwrap();
+        assert_eq!(memory.primary_contents(), "one\ntwo");
+        assert_eq!(sta.primary_selection_due(), None);
+        assert_eq!(memory.contents(), "");
+
+        sta.textareas[0].move_cursor(CursorMove::Jump(1, 0));
+        sta.mirror_selection_to_primary();
+        assert_eq!(sta.primary_contents().unwrap(), "one");
+    }
+
+    #[test]
+    fn test_wrapped_height_uses_display_width() {
+        let lines = vec!["日本語日本語".to_string(), "abc".to_string(), String::new()];
+        // 12 columns at width 5 -> 3 rows, plus one row each for the others
+        assert_eq!(wrapped_height(&lines, 5), 5);
+        assert_eq!(wrapped_height(&lines, 12), 3);
+    }
+
+    #[test]
+    fn test_toggle_task_on_focused() {
+        let mut sta = create_test_textarea();
+        sta.add_textarea(
+            TextArea::new(vec!["- [ ] task".to_string()]),
+            "Test".to_string(),
+        );
+        assert!(sta.toggle_task_on_focused());
+        assert_eq!(sta.textareas[0].lines()[0], "- [x] task");
+    }
+
+    #[test]
+    fn test_view_mode_toggles_the_selected_task() {
+        let mut sta = create_test_textarea();
+        let block = crate::notebook::Block::new(
+            "Todo",
+            "intro\n- [ ] one\n```\n- [ ] code\n```\n- [ ] two",
+        );
+        sta.add_textarea(crate::utils::block_to_textarea(&block), "Todo".to_string());
+        // Loaded blocks park the cursor on their last, empty line
+        assert_eq!(sta.textareas[0].cursor().0, 6);
+        assert_eq!(sta.selected_task(), Some(0));
+
+        sta.move_task_selection(1);
+        sta.move_task_selection(1);
+        assert_eq!(sta.selected_task(), Some(1));
+        assert!(sta.toggle_task_on_focused());
+        assert_eq!(sta.textareas[0].lines()[5], "- [x] two");
+        assert_eq!(sta.textareas[0].cursor().0, 6);
+
+        sta.move_task_selection(-5);
+        assert!(sta.toggle_task_on_focused());
+        assert_eq!(sta.textareas[0].lines()[1], "- [x] one");
+        assert_eq!(sta.textareas[0].lines()[3], "- [ ] code");
+
+        // Each block keeps its own selection
+        sta.add_textarea(
+            TextArea::new(vec!["no tasks".to_string()]),
+            "Other".to_string(),
+        );
+        assert_eq!(sta.selected_task(), None);
+        assert!(!sta.toggle_task_on_focused());
+        sta.move_focus(-1);
+        assert_eq!(sta.selected_task(), Some(0));
+
+        // The selected task is highlighted where the block is shown
+        let backend = ratatui::backend::TestBackend::new(30, 12);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal.draw(|f| sta.render(f, f.size()).unwrap()).unwrap();
+        let buffer = terminal.backend().buffer();
+        let highlighted: Vec<u16> = (0..buffer.area.height)
+            .filter(|&y| buffer.get(10, y).bg == sta.palette.selection_bg)
+            .collect();
+        assert_eq!(highlighted.len(), 1);
+        let row: String = (0..buffer.area.width)
+            .map(|x| buffer.get(x, highlighted[0]).symbol().to_string())
+            .collect();
+        assert!(row.contains("one"), "{}", row);
+    }
+
+    #[test]
+    fn test_set_theme_updates_palette() {
+        let mut sta = create_test_textarea();
+        sta.set_theme("Solarized (light)", None).unwrap();
+        assert_eq!(sta.palette, UiPalette::light());
+        assert_eq!(sta.theme_name(), "Solarized (light)");
+
+        sta.set_theme("Solarized (light)", Some(UiPalette::dark()))
+            .unwrap();
+        assert_eq!(sta.palette, UiPalette::dark());
+
+        assert!(sta.set_theme("missing", None).is_err());
+    }
+
+    #[test]
+    fn test_render_cache_keyed_by_width_and_theme() {
+        let mut cache = MarkdownCache::new();
+        let lines = vec!["# Header".to_string()];
+        cache.get_or_render(0, &lines, 20, usize::MAX).unwrap();
+        cache.get_or_render(0, &lines, 20, usize::MAX).unwrap();
+        assert_eq!(cache.cache.stats().hits, 1);
+
+        // A resize must not reuse the render padded for the old width
+        let wider = cache.get_or_render(0, &lines, 30, usize::MAX).unwrap();
+        assert_eq!(wider.lines[0].width(), 30);
+        assert_eq!(cache.cache.stats().misses, 2);
+
+        cache.set_theme("InspiredGitHub", None).unwrap();
+        assert_eq!(cache.cache.stats().entries, 0);
+        cache.get_or_render(0, &lines, 20, usize::MAX).unwrap();
+        assert_eq!(cache.cache.stats().misses, 3);
+    }
+
+    #[test]
+    fn test_partial_renders_are_not_cached() {
+        let mut cache = MarkdownCache::new();
+        let lines: Vec<String> = (0..50).map(|i| format!("line {}", i)).collect();
+        let text = cache.get_or_render(0, &lines, 20, 5).unwrap();
+        assert_eq!(text.lines.len(), 5);
+        assert_eq!(cache.cache.stats().entries, 0);
+
+        let text = cache.get_or_render(0, &lines, 20, usize::MAX).unwrap();
+        assert_eq!(text.lines.len(), 50);
+        assert_eq!(cache.cache.stats().entries, 1);
+    }
+
+    fn render_to_test_backend(sta: &mut ScrollableTextArea, width: u16, height: u16) {
+        let backend = ratatui::backend::TestBackend::new(width, height);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal.draw(|f| sta.render(f, f.size()).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_char_index_at_column() {
+        assert_eq!(char_index_at_column("hello", 0, 2, 4), 2);
+        assert_eq!(char_index_at_column("hello", 1, 2, 4), 3);
+        assert_eq!(char_index_at_column("hello", 0, 20, 4), 5);
+        // Wide characters span two cells
+        assert_eq!(char_index_at_column("日本語", 0, 3, 4), 1);
+        assert_eq!(char_index_at_column("\tx", 0, 4, 4), 1);
+    }
+
+    #[test]
+    fn test_click_focuses_block() {
+        let mut sta = create_test_textarea();
+        sta.add_textarea(TextArea::new(vec!["one".to_string()]), "A".to_string());
+        sta.add_textarea(TextArea::new(vec!["two".to_string()]), "B".to_string());
+        sta.initialize_scroll();
+        render_to_test_backend(&mut sta, 20, 10);
+
+        let (index, area) = sta.block_at(1, 4).unwrap();
+        assert_eq!(index, 1);
+        sta.click(area.x + 1, area.y + 1);
+        assert_eq!(sta.focused_index, 1);
+        assert!(!sta.edit_mode);
+        assert!(sta.block_at(1, 9).is_none());
+    }
+
+    #[test]
+    fn test_click_places_cursor_in_edit_mode() {
+        let mut sta = create_test_textarea();
+        sta.add_textarea(
+            TextArea::new(vec!["first line".to_string(), "second".to_string()]),
+            "A".to_string(),
+        );
+        sta.edit_mode = true;
+        render_to_test_backend(&mut sta, 20, 10);
+
+        // Inside the border, the second text row starts at (1, 2)
+        sta.click(4, 2);
+        assert_eq!(sta.textareas[0].cursor(), (1, 3));
+        // Clicking past the end of a line lands on its end
+        sta.click(18, 1);
+        assert_eq!(sta.textareas[0].cursor(), (0, 10));
+    }
+
+    #[test]
+    fn test_wrapped_blocks_keep_focus_on_screen() {
+        let mut sta = create_test_textarea();
+        // Each block is one line that wraps to eight rows at a width of 10
+        for title in ["A", "B"] {
+            sta.add_textarea(TextArea::new(vec!["字".repeat(40)]), title.to_string());
+        }
+        sta.initialize_scroll();
+        render_to_test_backend(&mut sta, 12, 12);
+        sta.move_focus(1);
+        assert_eq!(sta.scroll, 1);
+        assert_eq!(sta.calculate_height_to_focused(), 10);
+        render_to_test_backend(&mut sta, 12, 12);
+        let (index, area) = sta.block_at(1, 1).unwrap();
+        assert_eq!((index, area.height), (1, 10));
+    }
+
+    #[test]
+    fn test_scroll_by_moves_block_list() {
+        let mut sta = create_test_textarea();
+        for title in ["A", "B", "C"] {
+            sta.add_textarea(TextArea::default(), title.to_string());
+        }
+        sta.initialize_scroll();
+        sta.scroll_by(1);
+        assert_eq!(sta.scroll, 1);
+        assert_eq!(sta.focused_index, 1);
+        sta.scroll_by(10);
+        assert_eq!(sta.scroll, 2);
+        sta.scroll_by(-5);
+        assert_eq!(sta.scroll, 0);
+    }
+
+    #[test]
+    fn test_full_screen_scroll_stops_at_end() {
+        let mut sta = create_test_textarea();
+        let lines = (0..20).map(|i| format!("line {}", i)).collect();
+        sta.add_textarea(TextArea::new(lines), "A".to_string());
+        sta.toggle_full_screen();
+        sta.scroll_by(100);
+        render_to_test_backend(&mut sta, 30, 10);
+        assert_eq!(sta.full_screen_scroll, 20 - 8);
+    }
+
+    #[test]
+    fn test_full_screen_json_tree_follows_edits() {
+        let mut sta = create_test_textarea();
+        let json = vec!["{\"items\": [1, 2],".to_string(), "\"n\": 1}".to_string()];
+        sta.add_textarea(TextArea::new(json), "A".to_string());
+        sta.toggle_full_screen();
+        render_to_test_backend(&mut sta, 40, 10);
+        let tree = sta.active_json_tree().unwrap();
+        assert_eq!(tree.len(), 3);
+        tree.move_selection(1);
+        tree.expand();
+
+        // Edits rebuild the tree, keeping what was expanded
+        sta.textareas[0] = TextArea::new(vec!["{\"items\": [1, 2, 3]}".to_string()]);
+        render_to_test_backend(&mut sta, 40, 10);
+        let tree = sta.active_json_tree().unwrap();
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.selected_path(), ".items");
+
+        sta.textareas[0] = TextArea::new(vec!["{ broken".to_string()]);
+        render_to_test_backend(&mut sta, 40, 10);
+        assert!(sta.active_json_tree().is_none());
+
+        sta.toggle_full_screen();
+        assert!(sta.active_json_tree().is_none());
+    }
+
+    #[test]
+    fn test_full_screen_csv_table() {
+        let mut sta = create_test_textarea();
+        let csv = (0..10).map(|i| format!("{},{}", i, i * i)).collect();
+        sta.add_textarea(TextArea::new(csv), "A".to_string());
+        sta.toggle_full_screen();
+        render_to_test_backend(&mut sta, 40, 10);
+        assert!(sta.active_json_tree().is_none());
+        assert_eq!(sta.active_table().unwrap().row_count(), 9);
+
+        sta.scroll_by(2);
+        assert_eq!(sta.active_table().unwrap().selected_row, 6);
+
+        sta.structured_view_enabled = false;
+        assert!(sta.active_table().is_none());
+    }
+
+    #[test]
+    fn test_paste_text_is_a_single_edit() {
+        let mut sta = create_test_textarea();
+        sta.add_textarea(TextArea::default(), "A".to_string());
+        assert!(sta.paste_text("a\rb\r\nc"));
+        assert_eq!(sta.textareas[0].lines(), ["a", "b", "c"]);
+        sta.textareas[0].undo();
+        assert_eq!(sta.textareas[0].lines(), [""]);
+    }
+
     #[test]
     fn test_jump_to_textarea() {
         let mut sta = create_test_textarea();
@@ -0,0 +1,473 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{Cursor, Read};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use tiny_http::{Header, Method, Request, Response};
+
+use crate::control::{ControlRequest, Refused, SharedNotes};
+use crate::notebook::{Block, Notebook, NotebookError};
+use crate::utils::stable_hash;
+
+/// Request bodies larger than this are refused.
+const MAX_BODY_BYTES: u64 = 16 * 1024 * 1024;
+
+type JsonResponse = Response<Cursor<Vec<u8>>>;
+
+/// Settings of `thoth serve`.
+#[derive(Debug, Clone)]
+pub struct ServeConfig {
+    /// Address to listen on. Anything but loopback needs a token
+    pub host: IpAddr,
+    /// 0 picks a free port
+    pub port: u16,
+    /// When set, requests need an `Authorization: Bearer <token>` header
+    pub token: Option<String>,
+    pub notes_path: PathBuf,
+}
+
+/// JSON API over the blocks of a notes file, for editors and scripts.
+///
+/// Every request reads the blocks again, from the TUI when one has the notes file
+/// open and from the file otherwise, and writes go to the same place so the TUI
+/// does not overwrite them when it saves. Each block has an ETag; sending it back in `If-Match` makes
+/// an update or delete fail with 412 when the block changed in the meantime.
+///
+/// On loopback, requests must name localhost in their `Host` header so web pages
+/// cannot reach the API through DNS rebinding, and bodies must be JSON so they
+/// cannot be sent by a cross-site form.
+pub struct Server {
+    http: tiny_http::Server,
+    host: IpAddr,
+    token: Option<String>,
+    notes_path: PathBuf,
+}
+
+fn block_json(block: &Block) -> Value {
+    json!({ "title": block.title, "content": block.content, "etag": etag(block) })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewBlock {
+    title: String,
+    content: String,
+}
+
+/// Body of `PUT /blocks/{title}`. Leaving a field out keeps it as it is.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockUpdate {
+    title: Option<String>,
+    content: Option<String>,
+}
+
+/// A request that failed, answered with `status` and `{"error": message}`.
+#[derive(Debug)]
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::new(500, format!("{:#}", e))
+    }
+}
+
+impl From<NotebookError> for ApiError {
+    fn from(e: NotebookError) -> Self {
+        let status = match e {
+            NotebookError::NotFound(_) => 404,
+            NotebookError::DuplicateTitle(_) => 409,
+            NotebookError::InvalidTitle(_) => 400,
+            NotebookError::Read { .. } | NotebookError::Write { .. } => 500,
+        };
+        ApiError::new(status, e.to_string())
+    }
+}
+
+type ApiResult = std::result::Result<JsonResponse, ApiError>;
+
+impl Server {
+    /// Listens on `config.port` of `config.host`, which has to be a loopback
+    /// address unless a token is set.
+    pub fn bind(config: ServeConfig) -> Result<Server> {
+        if !config.host.is_loopback() && config.token.is_none() {
+            bail!(
+                "Serving on {} makes the notes reachable from other machines, so it needs a token",
+                config.host
+            );
+        }
+        let http = tiny_http::Server::http((config.host, config.port))
+            .map_err(|e| anyhow!("Failed to listen on {}:{}: {}", config.host, config.port, e))?;
+        Ok(Server {
+            http,
+            host: config.host,
+            token: config.token,
+            notes_path: config.notes_path,
+        })
+    }
+
+    /// The port listened on, which is the one picked when binding to port 0.
+    pub fn port(&self) -> u16 {
+        self.http
+            .server_addr()
+            .to_ip()
+            .map(|addr| addr.port())
+            .unwrap_or_default()
+    }
+
+    /// Answers requests one at a time, which keeps writes to the notes file in order.
+    pub fn run(&self) {
+        for mut request in self.http.incoming_requests() {
+            let response = self
+                .route(&mut request)
+                .unwrap_or_else(|e| json_response(e.status, &json!({ "error": e.message })));
+            // The client hanging up early is not the server's problem
+            let _ = request.respond(response);
+        }
+    }
+
+    fn route(&self, request: &mut Request) -> ApiResult {
+        self.check_host(request)?;
+        self.authorize(request)?;
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let segments = path
+            .trim_matches('/')
+            .split('/')
+            .map(percent_decode)
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| ApiError::new(400, e.to_string()))?;
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+        match (request.method(), segments.as_slice()) {
+            (Method::Get, ["blocks"]) => self.list(),
+            (Method::Post, ["blocks"]) => self.create(read_json(request)?),
+            (Method::Get, ["blocks", title]) => {
+                self.get(title, header_value(request, "If-None-Match"))
+            }
+            (Method::Put, ["blocks", title]) => {
+                let if_match = header_value(request, "If-Match");
+                self.update(title, if_match, read_json(request)?)
+            }
+            (Method::Delete, ["blocks", title]) => {
+                self.delete(title, header_value(request, "If-Match"))
+            }
+            (Method::Get, ["search"]) => match query_param(query, "q") {
+                Some(q) if !q.is_empty() => self.search(&q),
+                _ => Err(ApiError::new(400, "Missing the q query parameter")),
+            },
+            (_, ["blocks"]) | (_, ["blocks", _]) | (_, ["search"]) => {
+                Err(ApiError::new(405, "Method not allowed"))
+            }
+            _ => Err(ApiError::new(404, "Not found")),
+        }
+    }
+
+    /// Rejects requests that were not addressed to localhost, such as those of a web
+    /// page whose domain was rebound to 127.0.0.1. Off loopback the token guards instead.
+    fn check_host(&self, request: &Request) -> std::result::Result<(), ApiError> {
+        if !self.host.is_loopback() {
+            return Ok(());
+        }
+        let port = self.port();
+        let allowed = [
+            format!("localhost:{}", port),
+            format!("127.0.0.1:{}", port),
+            format!("[::1]:{}", port),
+        ];
+        match header_value(request, "Host") {
+            Some(host) if allowed.iter().any(|a| a.eq_ignore_ascii_case(host.trim())) => Ok(()),
+            _ => Err(ApiError::new(
+                403,
+                format!("The Host header must be localhost:{}", port),
+            )),
+        }
+    }
+
+    fn authorize(&self, request: &Request) -> std::result::Result<(), ApiError> {
+        let Some(token) = &self.token else {
+            return Ok(());
+        };
+        let given = header_value(request, "Authorization");
+        match given.as_deref().and_then(|v| v.strip_prefix("Bearer ")) {
+            Some(given) if given == token => Ok(()),
+            _ => Err(ApiError::new(401, "Missing or wrong token")),
+        }
+    }
+
+    /// The blocks being served and where writes to them go.
+    fn open(&self) -> std::result::Result<SharedNotes, ApiError> {
+        Ok(SharedNotes::open(&self.notes_path)?)
+    }
+
+    /// Writes `notes` back, answering 409 when the running TUI did not take the change.
+    fn commit(
+        &self,
+        notes: &SharedNotes,
+        change: impl FnOnce(u64) -> ControlRequest,
+    ) -> std::result::Result<(), ApiError> {
+        notes
+            .commit(change)
+            .map_err(|e| match e.downcast::<Refused>() {
+                Ok(refused) => ApiError::new(409, refused.0),
+                Err(e) => e.into(),
+            })
+    }
+
+    fn list(&self) -> ApiResult {
+        let blocks: Vec<Value> = self
+            .open()?
+            .notebook
+            .blocks()
+            .iter()
+            .map(|block| {
+                json!({
+                    "title": block.title,
+                    "lines": block.content.lines().count(),
+                    "etag": etag(block),
+                })
+            })
+            .collect();
+        Ok(json_response(200, &Value::Array(blocks)))
+    }
+
+    fn get(&self, title: &str, if_none_match: Option<String>) -> ApiResult {
+        let notes = self.open()?;
+        let block = notes
+            .notebook
+            .get(title)
+            .ok_or_else(|| NotebookError::NotFound(title.to_string()))?;
+        let etag = etag(block);
+        if if_none_match.is_some_and(|tags| etag_matches(&tags, &etag)) {
+            return Ok(json_response(304, &Value::Null).with_header(header("ETag", &etag)));
+        }
+        Ok(json_response(200, &block_json(block)).with_header(header("ETag", &etag)))
+    }
+
+    fn create(&self, new: NewBlock) -> ApiResult {
+        let mut notes = self.open()?;
+        let block = notes
+            .notebook
+            .insert(Block::new(new.title.trim(), new.content))?
+            .clone();
+        self.commit(&notes, |version| ControlRequest::Insert {
+            version,
+            block: block.clone(),
+        })?;
+        Ok(json_response(201, &block_json(&block))
+            .with_header(header("ETag", &etag(&block)))
+            .with_header(header(
+                "Location",
+                &format!("/blocks/{}", percent_encode(&block.title)),
+            )))
+    }
+
+    fn update(&self, title: &str, if_match: Option<String>, update: BlockUpdate) -> ApiResult {
+        let mut notes = self.open()?;
+        let notebook = &mut notes.notebook;
+        check_if_match(notebook, title, if_match)?;
+        let mut new_title = None;
+        if let Some(requested) = update.title {
+            new_title = Some(notebook.rename(title, requested.trim())?.title.clone());
+        }
+        let current = new_title.as_deref().unwrap_or(title);
+        if let Some(content) = &update.content {
+            notebook.update(current, content.as_str())?;
+        }
+        let block = notebook
+            .get(current)
+            .expect("the block was just updated")
+            .clone();
+        self.commit(&notes, |version| ControlRequest::Update {
+            version,
+            name: title.to_string(),
+            new_name: new_title,
+            content: update.content,
+        })?;
+        Ok(json_response(200, &block_json(&block)).with_header(header("ETag", &etag(&block))))
+    }
+
+    fn delete(&self, title: &str, if_match: Option<String>) -> ApiResult {
+        let mut notes = self.open()?;
+        check_if_match(&notes.notebook, title, if_match)?;
+        notes.notebook.remove(title)?;
+        self.commit(&notes, |version| ControlRequest::Delete {
+            version,
+            name: title.to_string(),
+        })?;
+        Ok(Response::from_string("").with_status_code(204))
+    }
+
+    /// Lines containing `query`, ignoring case.
+    fn search(&self, query: &str) -> ApiResult {
+        let query = query.to_lowercase();
+        let mut matches = Vec::new();
+        for block in self.open()?.notebook.blocks() {
+            for (line, text) in block.content.lines().enumerate() {
+                if text.to_lowercase().contains(&query) {
+                    matches.push(json!({ "title": block.title, "line": line, "text": text }));
+                }
+            }
+        }
+        Ok(json_response(200, &Value::Array(matches)))
+    }
+}
+
+/// Blocks read for a request, and who has to write changes to them.
+/// A quoted hash of the block, which is stable between runs and thoth versions.
+fn etag(block: &Block) -> String {
+    let hash = stable_hash(block.title.bytes().chain([0]).chain(block.content.bytes()));
+    format!("\"{:016x}\"", hash)
+}
+
+/// Whether an `If-Match` or `If-None-Match` list names `etag`.
+fn etag_matches(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Fails with 412 when `if_match` is set and does not match the block titled `title`.
+fn check_if_match(
+    notebook: &Notebook,
+    title: &str,
+    if_match: Option<String>,
+) -> std::result::Result<(), ApiError> {
+    let block = notebook
+        .get(title)
+        .ok_or_else(|| NotebookError::NotFound(title.to_string()))?;
+    match if_match {
+        Some(tags) if !etag_matches(&tags, &etag(block)) => Err(ApiError::new(
+            412,
+            format!("Block '{}' was changed since it was read", block.title),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(
+    request: &mut Request,
+) -> std::result::Result<T, ApiError> {
+    let content_type = header_value(request, "Content-Type").unwrap_or_default();
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    if !mime.eq_ignore_ascii_case("application/json") {
+        return Err(ApiError::new(
+            415,
+            "Request bodies must be sent as Content-Type: application/json",
+        ));
+    }
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_BYTES)
+        .read_to_string(&mut body)
+        .map_err(|e| ApiError::new(400, format!("Failed to read the request body: {}", e)))?;
+    serde_json::from_str(&body)
+        .map_err(|e| ApiError::new(400, format!("Invalid request body: {}", e)))
+}
+
+fn header_value(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str().to_string())
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes())
+        .expect("header names and values are ASCII")
+}
+
+fn json_response(status: u16, body: &Value) -> JsonResponse {
+    let body = if body.is_null() {
+        String::new()
+    } else {
+        body.to_string()
+    };
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| percent_decode(&value.replace('+', " ")).ok())
+}
+
+fn percent_decode(text: &str) -> Result<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| anyhow!("Invalid percent escape in '{}'", text))?;
+            decoded.push(hex);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| anyhow!("'{}' is not UTF-8", text))
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_coding_round_trip() {
+        let title = "Daily log/2024 ü";
+        let encoded = percent_encode(title);
+        assert_eq!(encoded, "Daily%20log%2F2024%20%C3%BC");
+        assert_eq!(percent_decode(&encoded).unwrap(), title);
+        assert!(percent_decode("%zz").is_err());
+        assert_eq!(
+            query_param("a=1&q=hello+world", "q").as_deref(),
+            Some("hello world")
+        );
+        assert_eq!(query_param("a=1", "q"), None);
+    }
+
+    #[test]
+    fn test_etag_and_conditions() {
+        let tag = etag(&Block::new("a", "b"));
+        assert_ne!(etag(&Block::new("a", "bc")), etag(&Block::new("ab", "c")));
+        assert_eq!(etag(&Block::new("a", "b")), tag);
+        assert!(etag_matches(&format!("\"x\", {}", tag), &tag));
+        assert!(etag_matches("*", &tag));
+        assert!(!etag_matches("\"x\"", &tag));
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use syntect::dumps::{dump_to_file, from_dump_file};
+use syntect::parsing::SyntaxSet;
+
+use crate::{get_cache_dir, get_syntaxes_dir};
+
+const SYNTAX_DUMP_FILE: &str = "syntaxes.packdump";
+const SYNTAX_FINGERPRINT_FILE: &str = "syntaxes.fingerprint";
+
+/// Default syntaxes plus the user's `.sublime-syntax` files.
+pub fn load_syntax_set() -> Result<SyntaxSet> {
+    load_syntax_set_from(&get_syntaxes_dir(), &get_cache_dir())
+}
+
+/// Builds the syntax set from `syntaxes_dir`, reusing the binary dump in `cache_dir`
+/// as long as none of the `.sublime-syntax` files changed since it was written.
+pub fn load_syntax_set_from(syntaxes_dir: &Path, cache_dir: &Path) -> Result<SyntaxSet> {
+    build_syntax_set(SyntaxSet::load_defaults_newlines, syntaxes_dir, cache_dir)
+}
+
+fn build_syntax_set(
+    base: impl FnOnce() -> SyntaxSet,
+    syntaxes_dir: &Path,
+    cache_dir: &Path,
+) -> Result<SyntaxSet> {
+    let files = sublime_syntax_files(syntaxes_dir)?;
+    if files.is_empty() {
+        return Ok(base());
+    }
+
+    let fingerprint = fingerprint(&files)?.to_string();
+    let dump_path = cache_dir.join(SYNTAX_DUMP_FILE);
+    let fingerprint_path = cache_dir.join(SYNTAX_FINGERPRINT_FILE);
+
+    if fs::read_to_string(&fingerprint_path).ok().as_deref() == Some(fingerprint.as_str()) {
+        if let Ok(syntax_set) = from_dump_file::<SyntaxSet, _>(&dump_path) {
+            return Ok(syntax_set);
+        }
+    }
+
+    let mut builder = base().into_builder();
+    builder
+        .add_from_folder(syntaxes_dir, true)
+        .with_context(|| format!("Failed to load syntaxes from {}", syntaxes_dir.display()))?;
+    let syntax_set = builder.build();
+
+    // The cache only speeds up startup, so failing to write it is not an error
+    if fs::create_dir_all(cache_dir).is_ok() && dump_to_file(&syntax_set, &dump_path).is_ok() {
+        let _ = fs::write(&fingerprint_path, fingerprint);
+    }
+
+    Ok(syntax_set)
+}
+
+fn sublime_syntax_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "sublime-syntax") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn fingerprint(files: &[PathBuf]) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    for file in files {
+        let metadata = fs::metadata(file)?;
+        file.hash(&mut hasher);
+        metadata.len().hash(&mut hasher);
+        metadata.modified()?.hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
+/// Name and fence tokens (file extensions) of every syntax, sorted by name.
+pub fn recognized_syntaxes(syntax_set: &SyntaxSet) -> Vec<(String, Vec<String>)> {
+    let mut syntaxes: Vec<(String, Vec<String>)> = syntax_set
+        .syntaxes()
+        .iter()
+        .map(|syntax| (syntax.name.clone(), syntax.file_extensions.clone()))
+        .collect();
+    syntaxes.sort_by_key(|(name, _)| name.to_lowercase());
+    syntaxes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const TEST_SYNTAX: &str = r#"%YAML 1.2
+---
+name: ThothTest
+file_extensions: [thothtest]
+scope: source.thothtest
+contexts:
+  main:
+    - match: '\b(key)\b'
+      scope: keyword.control.thothtest
+"#;
+
+    #[test]
+    fn test_defaults_without_user_syntaxes() {
+        let syntaxes = tempdir().unwrap();
+        let cache = tempdir().unwrap();
+        let set = load_syntax_set_from(syntaxes.path(), cache.path()).unwrap();
+        assert!(set.find_syntax_by_token("rust").is_some());
+        assert!(!cache.path().join(SYNTAX_DUMP_FILE).exists());
+    }
+
+    #[test]
+    fn test_loads_and_caches_user_syntaxes() {
+        let syntaxes = tempdir().unwrap();
+        let cache = tempdir().unwrap();
+        fs::write(
+            syntaxes.path().join("thothtest.sublime-syntax"),
+            TEST_SYNTAX,
+        )
+        .unwrap();
+
+        // Compiling on top of the full default set is slow in debug builds
+        let set = build_syntax_set(SyntaxSet::new, syntaxes.path(), cache.path()).unwrap();
+        assert!(set.find_syntax_by_token("thothtest").is_some());
+        assert!(cache.path().join(SYNTAX_DUMP_FILE).exists());
+        assert!(cache.path().join(SYNTAX_FINGERPRINT_FILE).exists());
+
+        let cached = build_syntax_set(
+            || panic!("the cached dump should be used"),
+            syntaxes.path(),
+            cache.path(),
+        )
+        .unwrap();
+        assert!(cached.find_syntax_by_token("thothtest").is_some());
+    }
+
+    #[test]
+    fn test_invalid_user_syntax_is_an_error() {
+        let syntaxes = tempdir().unwrap();
+        let cache = tempdir().unwrap();
+        fs::write(
+            syntaxes.path().join("broken.sublime-syntax"),
+            "name: [unclosed",
+        )
+        .unwrap();
+
+        assert!(build_syntax_set(SyntaxSet::new, syntaxes.path(), cache.path()).is_err());
+    }
+
+    #[test]
+    fn test_recognized_syntaxes_sorted() {
+        let syntaxes = recognized_syntaxes(&SyntaxSet::load_defaults_newlines());
+        let names: Vec<_> = syntaxes.iter().map(|(n, _)| n.to_lowercase()).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+        assert!(syntaxes
+            .iter()
+            .any(|(name, tokens)| name == "Rust" && tokens.contains(&"rs".to_string())));
+    }
+}
@@ -0,0 +1,459 @@
+use std::cmp::Ordering;
+
+use ratatui::{
+    style::{Modifier, Style},
+    text::{Line, Span},
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::code_fence::code_fences;
+use crate::theme::UiPalette;
+
+/// Widest a column gets before its cells are cut off with `…`.
+const MAX_COLUMN_WIDTH: usize = 40;
+const COLUMN_SEPARATOR: &str = " │ ";
+/// Fewest rows, header included, for a block to be detected as a table.
+const MIN_DETECTED_ROWS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// Grid view of CSV or TSV data with a header row, a selected cell and column sorting.
+pub struct TableView {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+    /// Display order of `rows`, changed by sorting
+    order: Vec<usize>,
+    sort: Option<(usize, SortOrder)>,
+    widths: Vec<usize>,
+    pub selected_row: usize,
+    pub selected_column: usize,
+    pub scroll_row: usize,
+    /// First column drawn, for horizontal scrolling
+    pub scroll_column: usize,
+}
+
+/// The delimiter of `text` when every record has the same number of fields, at least two.
+fn consistent_delimiter(text: &str) -> Option<u8> {
+    [b'\t', b',']
+        .into_iter()
+        .find(|&delimiter| match parse_records(text, delimiter) {
+            Some(records) => {
+                let columns = records[0].len();
+                records.len() >= MIN_DETECTED_ROWS
+                    && columns >= 2
+                    && records.iter().all(|record| record.len() == columns)
+            }
+            None => false,
+        })
+}
+
+fn parse_records(text: &str, delimiter: u8) -> Option<Vec<Vec<String>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes());
+    let records = reader
+        .records()
+        .map(|record| {
+            record
+                .ok()
+                .map(|record| record.iter().map(str::to_string).collect::<Vec<_>>())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let records: Vec<_> = records
+        .into_iter()
+        .filter(|record| !(record.len() == 1 && record[0].trim().is_empty()))
+        .collect();
+    (!records.is_empty()).then_some(records)
+}
+
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        // Numbers sort before text
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.to_lowercase().cmp(&b.to_lowercase()),
+    }
+}
+
+/// `text` cut to `width` display columns, ending in `…` when cut.
+fn fit(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return format!("{}{}", text, " ".repeat(width - text.width()));
+    }
+    let mut fitted = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width + 1 > width {
+            break;
+        }
+        fitted.push(c);
+        used += char_width;
+    }
+    fitted.push('…');
+    format!("{}{}", fitted, " ".repeat(width - used - 1))
+}
+
+impl TableView {
+    /// A table for blocks that are one ```` ```csv ```` or ```` ```tsv ```` code fence,
+    /// or whose lines all split into the same number of comma or tab separated fields.
+    pub fn from_lines(lines: &[String]) -> Option<Self> {
+        let fences = code_fences(lines);
+        if let [fence] = fences.as_slice() {
+            let outside_is_blank = lines[..fence.open]
+                .iter()
+                .chain(lines.get(fence.close + 1..).unwrap_or_default())
+                .all(|line| line.trim().is_empty());
+            let delimiter = match fence.language.as_deref() {
+                Some("csv") => Some(b','),
+                Some("tsv") => Some(b'\t'),
+                _ => None,
+            };
+            if let (true, Some(delimiter)) = (outside_is_blank, delimiter) {
+                return Self::parse(&fence.content(lines), delimiter);
+            }
+        }
+
+        let looks_like_markdown = lines.iter().any(|line| {
+            let line = line.trim_start();
+            line.starts_with('#') || line.starts_with("```") || line.starts_with("- ")
+        });
+        if looks_like_markdown {
+            return None;
+        }
+        let text = lines.join("\n");
+        Self::parse(&text, consistent_delimiter(&text)?)
+    }
+
+    /// Parses delimited text, the first record being the header. Short rows are padded.
+    pub fn parse(text: &str, delimiter: u8) -> Option<Self> {
+        let mut records = parse_records(text, delimiter)?;
+        let header = records.remove(0);
+        let columns = records
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(header.len());
+        let mut header = header;
+        header.resize(columns, String::new());
+        for record in &mut records {
+            record.resize(columns, String::new());
+        }
+
+        let widths = (0..columns)
+            .map(|column| {
+                records
+                    .iter()
+                    .map(|record| record[column].width())
+                    .chain(std::iter::once(header[column].width() + 2))
+                    .max()
+                    .unwrap_or(0)
+                    .clamp(1, MAX_COLUMN_WIDTH)
+            })
+            .collect();
+        Some(TableView {
+            header,
+            order: (0..records.len()).collect(),
+            rows: records,
+            sort: None,
+            widths,
+            selected_row: 0,
+            selected_column: 0,
+            scroll_row: 0,
+            scroll_column: 0,
+        })
+    }
+
+    /// Carries the sort and selection of `previous` over to the edited data.
+    pub fn restore(&mut self, previous: TableView) {
+        if let Some((column, order)) = previous.sort {
+            if column < self.header.len() {
+                self.sort = Some((column, order));
+                self.apply_sort();
+            }
+        }
+        self.selected_row = previous.selected_row;
+        self.selected_column = previous.selected_column;
+        self.scroll_row = previous.scroll_row;
+        self.scroll_column = previous.scroll_column;
+        self.clamp_selection();
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.header.len()
+    }
+
+    pub fn sort(&self) -> Option<(usize, SortOrder)> {
+        self.sort
+    }
+
+    fn clamp_selection(&mut self) {
+        self.selected_row = self.selected_row.min(self.rows.len().saturating_sub(1));
+        self.selected_column = self
+            .selected_column
+            .min(self.header.len().saturating_sub(1));
+    }
+
+    pub fn move_row(&mut self, delta: isize) {
+        self.selected_row = self.selected_row.saturating_add_signed(delta);
+        self.clamp_selection();
+    }
+
+    pub fn move_column(&mut self, delta: isize) {
+        self.selected_column = self.selected_column.saturating_add_signed(delta);
+        self.clamp_selection();
+    }
+
+    pub fn select_first_row(&mut self) {
+        self.selected_row = 0;
+    }
+
+    pub fn select_last_row(&mut self) {
+        self.selected_row = self.rows.len().saturating_sub(1);
+    }
+
+    /// Sorts by the selected column, ascending first, then descending, then back
+    /// to the original order.
+    pub fn cycle_sort(&mut self) {
+        let column = self.selected_column;
+        self.sort = match self.sort {
+            Some((sorted, SortOrder::Ascending)) if sorted == column => {
+                Some((column, SortOrder::Descending))
+            }
+            Some((sorted, SortOrder::Descending)) if sorted == column => None,
+            _ => Some((column, SortOrder::Ascending)),
+        };
+        self.apply_sort();
+    }
+
+    fn apply_sort(&mut self) {
+        self.order = (0..self.rows.len()).collect();
+        if let Some((column, order)) = self.sort {
+            let rows = &self.rows;
+            // A stable sort keeps equal cells in their original order
+            self.order.sort_by(|&a, &b| {
+                let ordering = compare_cells(&rows[a][column], &rows[b][column]);
+                match order {
+                    SortOrder::Ascending => ordering,
+                    SortOrder::Descending => ordering.reverse(),
+                }
+            });
+        }
+    }
+
+    /// The selected column's cells in display order, one per line, without the header.
+    pub fn selected_column_text(&self) -> String {
+        self.order
+            .iter()
+            .map(|&row| self.rows[row][self.selected_column].as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn selected_column_name(&self) -> &str {
+        self.header
+            .get(self.selected_column)
+            .map_or("", String::as_str)
+    }
+
+    /// Scrolls so the selected cell is inside `width` x `height` cells and returns
+    /// the lines to draw: the header, a rule, then the visible rows.
+    pub fn render_lines(
+        &mut self,
+        width: usize,
+        height: usize,
+        palette: &UiPalette,
+    ) -> Vec<Line<'static>> {
+        let body_height = height.saturating_sub(2);
+        if self.selected_row < self.scroll_row {
+            self.scroll_row = self.selected_row;
+        } else if body_height > 0 && self.selected_row >= self.scroll_row + body_height {
+            self.scroll_row = self.selected_row + 1 - body_height;
+        }
+        if self.selected_column < self.scroll_column {
+            self.scroll_column = self.selected_column;
+        }
+        while self.scroll_column < self.selected_column
+            && !self.visible_columns(width).contains(&self.selected_column)
+        {
+            self.scroll_column += 1;
+        }
+
+        let columns = self.visible_columns(width);
+        let header_style = Style::default()
+            .fg(palette.highlight)
+            .add_modifier(Modifier::BOLD);
+        let header_cells = columns.iter().map(|&column| {
+            let marker = match self.sort {
+                Some((sorted, SortOrder::Ascending)) if sorted == column => " ▲",
+                Some((sorted, SortOrder::Descending)) if sorted == column => " ▼",
+                _ => "",
+            };
+            let name = format!("{}{}", self.header[column], marker);
+            (fit(&name, self.widths[column]), header_style)
+        });
+        let mut lines = vec![self.join_cells(header_cells.collect(), palette)];
+        let rule = columns
+            .iter()
+            .map(|&column| "─".repeat(self.widths[column]))
+            .collect::<Vec<_>>()
+            .join("─┼─");
+        lines.push(Line::from(Span::styled(
+            rule,
+            Style::default().fg(palette.gutter),
+        )));
+
+        for (position, &row) in self
+            .order
+            .iter()
+            .enumerate()
+            .skip(self.scroll_row)
+            .take(body_height)
+        {
+            let selected = position == self.selected_row;
+            let cells = columns
+                .iter()
+                .map(|&column| {
+                    let mut style = Style::default().fg(palette.text_fg);
+                    if selected && column == self.selected_column {
+                        style = style.fg(palette.highlight).add_modifier(Modifier::BOLD);
+                    }
+                    (fit(&self.rows[row][column], self.widths[column]), style)
+                })
+                .collect();
+            let line = self.join_cells(cells, palette);
+            lines.push(if selected {
+                line.style(Style::default().bg(palette.selection_bg))
+            } else {
+                line
+            });
+        }
+        lines
+    }
+
+    /// Columns from `scroll_column` on that fit in `width`, at least one.
+    fn visible_columns(&self, width: usize) -> Vec<usize> {
+        let mut used = 0;
+        let mut columns = Vec::new();
+        for column in self.scroll_column..self.header.len() {
+            let needed = self.widths[column]
+                + if columns.is_empty() {
+                    0
+                } else {
+                    COLUMN_SEPARATOR.width()
+                };
+            if !columns.is_empty() && used + needed > width {
+                break;
+            }
+            used += needed;
+            columns.push(column);
+        }
+        columns
+    }
+
+    fn join_cells(&self, cells: Vec<(String, Style)>, palette: &UiPalette) -> Line<'static> {
+        let mut spans = Vec::with_capacity(cells.len() * 2);
+        for (i, (text, style)) in cells.into_iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::styled(
+                    COLUMN_SEPARATOR,
+                    Style::default().fg(palette.gutter),
+                ));
+            }
+            spans.push(Span::styled(text, style));
+        }
+        Line::from(spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn line_text(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn test_detects_csv_and_tsv() {
+        let csv = TableView::from_lines(&lines("name,age\nann,31\n\"b, c\",4")).unwrap();
+        assert_eq!(csv.column_count(), 2);
+        assert_eq!(csv.row_count(), 2);
+
+        let tsv = TableView::from_lines(&lines("a\tb\tc\n1\t2\t3\n4\t5\t6")).unwrap();
+        assert_eq!(tsv.column_count(), 3);
+
+        // Prose, ragged rows and markdown are not tables
+        assert!(TableView::from_lines(&lines("Hi, there\nHow are you")).is_none());
+        assert!(TableView::from_lines(&lines("a,b\n1,2,3\n4,5")).is_none());
+        assert!(TableView::from_lines(&lines("# a,b\n1,2\n3,4")).is_none());
+    }
+
+    #[test]
+    fn test_fenced_block_is_told_its_delimiter() {
+        // Two rows and ragged lengths are fine when the fence says it is CSV
+        let table = TableView::from_lines(&lines("\n```csv\na,b\n1\n```\n")).unwrap();
+        assert_eq!(table.column_count(), 2);
+        assert_eq!(table.row_count(), 1);
+        assert!(TableView::from_lines(&lines("intro\n```csv\na,b\n1,2\n```")).is_none());
+    }
+
+    #[test]
+    fn test_sort_cycles_and_compares_numbers() {
+        let mut table = TableView::parse("n,name\n10,b\n9,A\nx,c\n9,d", b',').unwrap();
+        table.cycle_sort();
+        assert_eq!(table.selected_column_text(), "9\n9\n10\nx");
+        table.cycle_sort();
+        assert_eq!(table.sort(), Some((0, SortOrder::Descending)));
+        assert_eq!(table.selected_column_text(), "x\n10\n9\n9");
+        table.cycle_sort();
+        assert_eq!(table.selected_column_text(), "10\n9\nx\n9");
+
+        table.move_column(1);
+        table.cycle_sort();
+        assert_eq!(table.selected_column_name(), "name");
+        assert_eq!(table.selected_column_text(), "A\nb\nc\nd");
+    }
+
+    #[test]
+    fn test_render_scrolls_to_selected_cell() {
+        let mut table = TableView::parse("a,b,c\n1,2,3\n4,5,6\n7,8,9", b',').unwrap();
+        let rendered = table.render_lines(30, 10, &UiPalette::dark());
+        assert_eq!(line_text(&rendered[0]), "a   │ b   │ c  ");
+        assert_eq!(line_text(&rendered[1]), "────┼─────┼────");
+        assert_eq!(line_text(&rendered[2]), "1   │ 2   │ 3  ");
+
+        table.move_column(2);
+        table.select_last_row();
+        let rendered = table.render_lines(9, 3, &UiPalette::dark());
+        assert_eq!(table.scroll_column, 1);
+        assert_eq!(table.scroll_row, 2);
+        assert_eq!(line_text(&rendered[2]), "8   │ 9  ");
+    }
+
+    #[test]
+    fn test_fit_truncates_wide_cells() {
+        assert_eq!(fit("abc", 5), "abc  ");
+        assert_eq!(fit("abcdef", 4), "abc…");
+        assert_eq!(fit("日本語", 4), "日… ");
+    }
+}
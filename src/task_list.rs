@@ -0,0 +1,170 @@
+use crate::code_fence::fence_after;
+use tui_textarea::{CursorMove, TextArea};
+
+pub const UNCHECKED_GLYPH: &str = "☐";
+pub const CHECKED_GLYPH: &str = "☑";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskItem {
+    pub checked: bool,
+    pub text: String,
+}
+
+/// Returns the byte offset of the `[ ]`/`[x]` marker when `line` is a task list item.
+fn marker_offset(line: &str) -> Option<usize> {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let mut chars = rest.chars();
+    let bullet = chars.next()?;
+    if !matches!(bullet, '-' | '*' | '+') || chars.next() != Some(' ') {
+        return None;
+    }
+    let marker = &rest[2..];
+    if marker.len() < 3 || !marker.is_char_boundary(3) {
+        return None;
+    }
+    match &marker[..3] {
+        "[ ]" | "[x]" | "[X]" => {
+            let after = &marker[3..];
+            if after.is_empty() || after.starts_with(' ') {
+                Some(indent + 2)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn parse_task(line: &str) -> Option<TaskItem> {
+    let offset = marker_offset(line)?;
+    let checked = &line[offset..offset + 3] != "[ ]";
+    Some(TaskItem {
+        checked,
+        text: line[offset + 3..].trim().to_string(),
+    })
+}
+
+pub fn toggle_task_line(line: &str) -> Option<String> {
+    let offset = marker_offset(line)?;
+    let replacement = if &line[offset..offset + 3] == "[ ]" {
+        "[x]"
+    } else {
+        "[ ]"
+    };
+    Some(format!(
+        "{}{}{}",
+        &line[..offset],
+        replacement,
+        &line[offset + 3..]
+    ))
+}
+
+/// Rows of the task items of a block, skipping anything inside fenced code blocks.
+pub fn task_rows<S: AsRef<str>>(lines: &[S]) -> Vec<usize> {
+    let mut fence = None;
+    let mut rows = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        let in_code = fence.is_some();
+        fence = fence_after(fence, line);
+        if in_code || fence.is_some() {
+            continue;
+        }
+        if marker_offset(line).is_some() {
+            rows.push(row);
+        }
+    }
+    rows
+}
+
+/// Task items of a block, skipping anything inside fenced code blocks.
+pub fn collect_tasks<S: AsRef<str>>(lines: &[S]) -> Vec<TaskItem> {
+    task_rows(lines)
+        .into_iter()
+        .filter_map(|row| parse_task(lines[row].as_ref()))
+        .collect()
+}
+
+/// Returns `(done, total)` for the task items of a block.
+pub fn task_progress<S: AsRef<str>>(lines: &[S]) -> (usize, usize) {
+    let tasks = collect_tasks(lines);
+    let done = tasks.iter().filter(|t| t.checked).count();
+    (done, tasks.len())
+}
+
+/// Toggles the task item on the cursor line. Returns false when the line is not a task.
+pub fn toggle_task_at_cursor(textarea: &mut TextArea) -> bool {
+    let (row, _) = textarea.cursor();
+    toggle_task_at(textarea, row)
+}
+
+/// Toggles the task item on `row`, leaving the cursor where it is. Returns false when
+/// the line is not a task.
+pub fn toggle_task_at(textarea: &mut TextArea, row: usize) -> bool {
+    let (cursor_row, cursor_col) = textarea.cursor();
+    let toggled = match textarea.lines().get(row).and_then(|l| toggle_task_line(l)) {
+        Some(toggled) => toggled,
+        None => return false,
+    };
+
+    textarea.cancel_selection();
+    textarea.move_cursor(CursorMove::Jump(row as u16, 0));
+    textarea.delete_line_by_end();
+    textarea.insert_str(&toggled);
+    textarea.move_cursor(CursorMove::Jump(cursor_row as u16, cursor_col as u16));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_task() {
+        assert_eq!(
+            parse_task("- [ ] write docs"),
+            Some(TaskItem {
+                checked: false,
+                text: "write docs".to_string()
+            })
+        );
+        assert!(parse_task("  * [x] done").unwrap().checked);
+        assert!(parse_task("+ [X] done").unwrap().checked);
+        assert_eq!(parse_task("- [] nope"), None);
+        assert_eq!(parse_task("-[ ] nope"), None);
+        assert_eq!(parse_task("- [ ]nope"), None);
+        assert_eq!(parse_task("plain text"), None);
+    }
+
+    #[test]
+    fn test_toggle_task_line() {
+        assert_eq!(toggle_task_line("- [ ] a").unwrap(), "- [x] a");
+        assert_eq!(toggle_task_line("  - [X] a").unwrap(), "  - [ ] a");
+        assert_eq!(toggle_task_line("- a"), None);
+    }
+
+    #[test]
+    fn test_task_progress_skips_code_blocks() {
+        let lines = vec![
+            "- [x] one",
+            "- [ ] two",
+            "```",
+            "- [ ] not a task",
+            "```",
+            "- [X] three",
+        ];
+        assert_eq!(task_progress(&lines), (2, 3));
+        assert_eq!(task_rows(&lines), [0, 1, 5]);
+    }
+
+    #[test]
+    fn test_toggle_task_at_cursor() {
+        let mut textarea = TextArea::new(vec!["intro".to_string(), "- [ ] task".to_string()]);
+        assert!(!toggle_task_at_cursor(&mut textarea));
+        textarea.move_cursor(CursorMove::Jump(1, 4));
+        assert!(toggle_task_at_cursor(&mut textarea));
+        assert_eq!(textarea.lines()[1], "- [x] task");
+        assert_eq!(textarea.cursor(), (1, 4));
+    }
+}
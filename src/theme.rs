@@ -0,0 +1,219 @@
+use std::path::Path;
+
+use ratatui::style::Color;
+use syntect::highlighting::{Theme, ThemeSet};
+
+use crate::{get_themes_dir, ORANGE};
+
+pub const DEFAULT_THEME: &str = "base16-mocha.dark";
+
+/// Colors used by the TUI chrome. Paired with a syntax theme so borders,
+/// popups and headers stay readable on the theme's background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UiPalette {
+    pub border: Color,
+    pub header_fg: Color,
+    pub header_bg: Color,
+    pub popup_bg: Color,
+    pub text_fg: Color,
+    pub focused_fg: Color,
+    pub focused_bg: Color,
+    pub edit_fg: Color,
+    pub edit_bg: Color,
+    pub cursor_fg: Color,
+    pub cursor_bg: Color,
+    pub selection_bg: Color,
+    pub highlight: Color,
+    pub error: Color,
+    pub gutter: Color,
+    pub task_open: Color,
+    pub task_done: Color,
+    pub link: Color,
+    pub headings: [Color; 6],
+}
+
+impl UiPalette {
+    pub fn dark() -> Self {
+        UiPalette {
+            border: ORANGE,
+            header_fg: ORANGE,
+            header_bg: Color::Black,
+            popup_bg: Color::Black,
+            text_fg: Color::White,
+            focused_fg: Color::Black,
+            focused_bg: Color::DarkGray,
+            edit_fg: Color::White,
+            edit_bg: Color::Black,
+            cursor_fg: Color::White,
+            cursor_bg: ORANGE,
+            selection_bg: Color::Red,
+            highlight: Color::Yellow,
+            error: Color::Red,
+            gutter: Color::White,
+            task_open: Color::Yellow,
+            task_done: Color::DarkGray,
+            link: Color::Cyan,
+            headings: [
+                Color::Red,
+                Color::Green,
+                Color::Yellow,
+                Color::Blue,
+                Color::Magenta,
+                Color::Cyan,
+            ],
+        }
+    }
+
+    pub fn light() -> Self {
+        let accent = Color::Rgb(196, 98, 0);
+        UiPalette {
+            border: accent,
+            header_fg: accent,
+            header_bg: Color::Rgb(238, 238, 238),
+            popup_bg: Color::White,
+            text_fg: Color::Black,
+            focused_fg: Color::Black,
+            focused_bg: Color::Rgb(225, 225, 225),
+            edit_fg: Color::Black,
+            edit_bg: Color::White,
+            cursor_fg: Color::White,
+            cursor_bg: accent,
+            selection_bg: Color::Rgb(255, 196, 140),
+            highlight: Color::Rgb(0, 95, 175),
+            error: Color::Rgb(175, 0, 0),
+            gutter: Color::DarkGray,
+            task_open: Color::Rgb(0, 95, 175),
+            task_done: Color::Gray,
+            link: Color::Rgb(0, 110, 110),
+            headings: [
+                Color::Rgb(175, 0, 0),
+                Color::Rgb(0, 135, 0),
+                Color::Rgb(175, 95, 0),
+                Color::Rgb(0, 0, 175),
+                Color::Rgb(135, 0, 135),
+                Color::Rgb(0, 135, 135),
+            ],
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    /// Picks the light or dark palette based on the theme's background luminance.
+    pub fn for_theme(theme: &Theme) -> Self {
+        match theme.settings.background {
+            Some(bg) if relative_luminance(bg.r, bg.g, bg.b) > 0.5 => Self::light(),
+            _ => Self::dark(),
+        }
+    }
+}
+
+impl Default for UiPalette {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+fn relative_luminance(r: u8, g: u8, b: u8) -> f32 {
+    (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32) / 255.0
+}
+
+/// Built-in syntect themes plus any `.tmTheme` files found in the themes directory,
+/// along with why the files that could not be loaded were skipped.
+pub fn load_theme_set() -> (ThemeSet, Option<String>) {
+    load_theme_set_from(&get_themes_dir())
+}
+
+/// Loads the files in `themes_dir` one by one, so a broken theme neither keeps thoth
+/// from starting nor hides the themes after it.
+pub fn load_theme_set_from(themes_dir: &Path) -> (ThemeSet, Option<String>) {
+    let mut theme_set = ThemeSet::load_defaults();
+    if !themes_dir.is_dir() {
+        return (theme_set, None);
+    }
+    let paths = match ThemeSet::discover_theme_paths(themes_dir) {
+        Ok(paths) => paths,
+        Err(e) => return (theme_set, Some(format!("{}: {}", themes_dir.display(), e))),
+    };
+    let mut errors = Vec::new();
+    for path in paths {
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        match ThemeSet::get_theme(&path) {
+            Ok(theme) => {
+                theme_set.themes.insert(name.to_string(), theme);
+            }
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    let error = (!errors.is_empty()).then(|| errors.join("; "));
+    (theme_set, error)
+}
+
+pub fn theme_names(theme_set: &ThemeSet) -> Vec<String> {
+    theme_set.themes.keys().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_from_name() {
+        assert_eq!(UiPalette::from_name("Light"), Some(UiPalette::light()));
+        assert_eq!(UiPalette::from_name("dark"), Some(UiPalette::dark()));
+        assert_eq!(UiPalette::from_name("sepia"), None);
+    }
+
+    #[test]
+    fn test_palette_for_builtin_themes() {
+        let theme_set = ThemeSet::load_defaults();
+        assert_eq!(
+            UiPalette::for_theme(&theme_set.themes["base16-ocean.light"]),
+            UiPalette::light()
+        );
+        assert_eq!(
+            UiPalette::for_theme(&theme_set.themes[DEFAULT_THEME]),
+            UiPalette::dark()
+        );
+    }
+
+    #[test]
+    fn test_theme_names_include_defaults() {
+        let names = theme_names(&load_theme_set().0);
+        assert!(names.contains(&DEFAULT_THEME.to_string()));
+        assert!(names.contains(&"InspiredGitHub".to_string()));
+    }
+
+    #[test]
+    fn test_broken_theme_is_reported_and_skipped() {
+        let themes = tempfile::tempdir().unwrap();
+        // Sorts before the good one, which used to stop loading right there
+        std::fs::write(themes.path().join("a-broken.tmTheme"), "not a plist").unwrap();
+        std::fs::write(
+            themes.path().join("b-paper.tmTheme"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0"><dict>
+<key>name</key><string>Paper</string>
+<key>settings</key><array><dict><key>settings</key><dict>
+<key>background</key><string>#FFFFFF</string>
+</dict></dict></array>
+</dict></plist>"#,
+        )
+        .unwrap();
+
+        let (theme_set, error) = load_theme_set_from(themes.path());
+        assert!(error.unwrap().contains("a-broken.tmTheme"));
+        assert_eq!(
+            UiPalette::for_theme(&theme_set.themes["b-paper"]),
+            UiPalette::light()
+        );
+        assert!(theme_set.themes.contains_key(DEFAULT_THEME));
+    }
+}
@@ -0,0 +1,68 @@
+use crate::theme::UiPalette;
+
+pub struct ThemePopup {
+    pub themes: Vec<String>,
+    pub selected_index: usize,
+    pub visible: bool,
+    /// Theme and palette active when the popup was opened, restored on cancel
+    pub original_theme: String,
+    pub original_palette: UiPalette,
+}
+
+impl ThemePopup {
+    pub fn new() -> Self {
+        ThemePopup {
+            themes: Vec::new(),
+            selected_index: 0,
+            visible: false,
+            original_theme: String::new(),
+            original_palette: UiPalette::dark(),
+        }
+    }
+
+    pub fn open(&mut self, themes: Vec<String>, current: &str, palette: UiPalette) {
+        self.selected_index = themes.iter().position(|t| t == current).unwrap_or(0);
+        self.themes = themes;
+        self.original_theme = current.to_string();
+        self.original_palette = palette;
+        self.visible = true;
+    }
+
+    pub fn selected_theme(&self) -> Option<&str> {
+        self.themes.get(self.selected_index).map(String::as_str)
+    }
+}
+
+impl Default for ThemePopup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_theme_popup() {
+        let popup = ThemePopup::new();
+        assert!(popup.themes.is_empty());
+        assert_eq!(popup.selected_theme(), None);
+        assert!(!popup.visible);
+    }
+
+    #[test]
+    fn test_open_selects_current_theme() {
+        let mut popup = ThemePopup::new();
+        popup.open(
+            vec!["a".to_string(), "b".to_string()],
+            "b",
+            UiPalette::light(),
+        );
+        assert!(popup.visible);
+        assert_eq!(popup.selected_index, 1);
+        assert_eq!(popup.selected_theme(), Some("b"));
+        assert_eq!(popup.original_theme, "b");
+        assert_eq!(popup.original_palette, UiPalette::light());
+    }
+}
@@ -1,7 +1,13 @@
-use crate::{TitlePopup, TitleSelectPopup, ORANGE};
+use crate::clip_history::entry_preview;
+use crate::render_cache::RenderCacheStats;
+use crate::theme::UiPalette;
+use crate::{
+    BacklinksPopup, ClipHistoryPopup, FormatPopup, JsonPopup, OutlinePopup, ThemePopup, TitlePopup,
+    TitleSelectPopup,
+};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Cell, Paragraph, Row, Table, Tabs},
     Frame,
@@ -52,18 +58,26 @@ impl Default for ErrorPopup {
     }
 }
 
-pub fn render_edit_commands_popup(f: &mut Frame) {
+pub fn render_edit_commands_popup(f: &mut Frame, palette: &UiPalette) {
     let area = centered_rect(80, 80, f.size());
     f.render_widget(ratatui::widgets::Clear, area);
 
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(ORANGE))
+        .border_style(Style::default().fg(palette.border))
         .title("Editing Commands");
 
     let header = Row::new(vec![
-        Cell::from("MAPPINGS").style(Style::default().fg(ORANGE).add_modifier(Modifier::BOLD)),
-        Cell::from("DESCRIPTIONS").style(Style::default().fg(ORANGE).add_modifier(Modifier::BOLD)),
+        Cell::from("MAPPINGS").style(
+            Style::default()
+                .fg(palette.header_fg)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Cell::from("DESCRIPTIONS").style(
+            Style::default()
+                .fg(palette.header_fg)
+                .add_modifier(Modifier::BOLD),
+        ),
     ])
     .height(2);
 
@@ -97,6 +111,17 @@ pub fn render_edit_commands_popup(f: &mut Frame) {
         ]),
         Row::new(vec!["Ctrl+K", "Format markdown block"]),
         Row::new(vec!["Ctrl+J", "Format JSON"]),
+        Row::new(vec!["Ctrl+L", "Format as YAML, TOML, XML, SQL, CSV..."]),
+        Row::new(vec![
+            "Alt+J",
+            "JSON tools: minify, sort keys, convert, query",
+        ]),
+        Row::new(vec!["Alt+G", "Follow the [[link]] under the cursor"]),
+        Row::new(vec!["Alt+←", "Go back to where the link was followed from"]),
+        Row::new(vec!["Alt+R", "Show blocks linking to this one"]),
+        Row::new(vec!["Alt+Y", "Clipboard history, paste an earlier copy"]),
+        Row::new(vec!["Alt+I", "Paste the primary selection"]),
+        Row::new(vec!["Ctrl+O", "Toggle task checkbox on cursor line"]),
     ];
 
     let table = Table::new(commands, [Constraint::Length(5), Constraint::Length(5)])
@@ -104,13 +129,14 @@ pub fn render_edit_commands_popup(f: &mut Frame) {
         .block(block)
         .widths([Constraint::Percentage(30), Constraint::Percentage(70)])
         .column_spacing(2)
-        .highlight_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg))
+        .highlight_style(Style::default().fg(palette.highlight))
         .highlight_symbol(">> ");
 
     f.render_widget(table, area);
 }
 
-pub fn render_header(f: &mut Frame, area: Rect, is_edit_mode: bool) {
+pub fn render_header(f: &mut Frame, area: Rect, is_edit_mode: bool, palette: &UiPalette) {
     let available_width = area.width as usize;
     let normal_commands = vec![
         "q:Quit",
@@ -125,6 +151,16 @@ pub fn render_header(f: &mut Frame, area: Rect, is_edit_mode: bool) {
         "^s:Select",
         "^j:Format JSON",
         "^k:Format Markdown",
+        "^l:Format As",
+        "M-j:JSON Tools",
+        "M-o:Outline",
+        "M-g:Follow Link",
+        "M-←:Back",
+        "M-r:Backlinks",
+        "M-y:Clip History",
+        "Tab:Select Task",
+        "^o:Toggle Task",
+        "^p:Theme",
     ];
     let edit_commands = vec![
         "Esc:Exit Edit",
@@ -135,6 +171,15 @@ pub fn render_header(f: &mut Frame, area: Rect, is_edit_mode: bool) {
         "^t:Title",
         "^s:Select",
         "^e:External Editor",
+        "^l:Format As",
+        "M-j:JSON Tools",
+        "M-o:Outline",
+        "M-g:Follow Link",
+        "M-←:Back",
+        "M-r:Backlinks",
+        "M-y:Clip History",
+        "M-i:Paste Primary",
+        "^o:Toggle Task",
         "^h:Help",
     ];
     let commands = if is_edit_mode {
@@ -154,7 +199,9 @@ pub fn render_header(f: &mut Frame, area: Rect, is_edit_mode: bool) {
 
     for cmd in commands {
         let cmd_width = cmd.width();
-        if current_width + cmd_width + separator_width > available_width - reserved_width {
+        if current_width + cmd_width + separator_width
+            > available_width.saturating_sub(reserved_width)
+        {
             break;
         }
         display_commands.push(*cmd);
@@ -164,37 +211,42 @@ pub fn render_header(f: &mut Frame, area: Rect, is_edit_mode: bool) {
     let command_string = display_commands.join(separator);
     let command_width = command_string.width();
 
-    let padding = " ".repeat(available_width - command_width - thoth_width - 2);
+    let padding = " ".repeat(
+        available_width
+            .saturating_sub(command_width)
+            .saturating_sub(thoth_width + 2),
+    );
 
+    let header_style = Style::default().fg(palette.header_fg);
     let header = Line::from(vec![
-        Span::styled(command_string, Style::default().fg(ORANGE)),
-        Span::styled(padding, Style::default().fg(ORANGE)),
-        Span::styled(format!(" {} ", thoth), Style::default().fg(ORANGE)),
+        Span::styled(command_string, header_style),
+        Span::styled(padding, header_style),
+        Span::styled(format!(" {} ", thoth), header_style),
     ]);
 
     let tabs = Tabs::new(vec![header])
-        .style(Style::default().bg(Color::Black))
-        .divider(Span::styled("|", Style::default().fg(ORANGE)));
+        .style(Style::default().bg(palette.header_bg))
+        .divider(Span::styled("|", header_style));
 
     f.render_widget(tabs, area);
 }
 
-pub fn render_title_popup(f: &mut Frame, popup: &TitlePopup) {
+pub fn render_title_popup(f: &mut Frame, popup: &TitlePopup, palette: &UiPalette) {
     let area = centered_rect(60, 20, f.size());
     f.render_widget(ratatui::widgets::Clear, area);
 
     let text = Paragraph::new(popup.title.as_str())
-        .style(Style::default().bg(Color::Black))
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg))
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(ORANGE))
+                .border_style(Style::default().fg(palette.border))
                 .title("Change Title"),
         );
     f.render_widget(text, area);
 }
 
-pub fn render_title_select_popup(f: &mut Frame, popup: &TitleSelectPopup) {
+pub fn render_title_select_popup(f: &mut Frame, popup: &TitleSelectPopup, palette: &UiPalette) {
     let area = centered_rect(80, 80, f.size());
     f.render_widget(ratatui::widgets::Clear, area);
 
@@ -206,7 +258,7 @@ pub fn render_title_select_popup(f: &mut Frame, popup: &TitleSelectPopup) {
             if i == popup.selected_index {
                 Line::from(vec![Span::styled(
                     format!("> {}", title),
-                    Style::default().fg(Color::Yellow),
+                    Style::default().fg(palette.highlight),
                 )])
             } else {
                 Line::from(vec![Span::raw(format!("  {}", title))])
@@ -216,17 +268,312 @@ pub fn render_title_select_popup(f: &mut Frame, popup: &TitleSelectPopup) {
 
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(ORANGE))
+        .border_style(Style::default().fg(palette.border))
         .title("Select Title");
 
     let paragraph = Paragraph::new(items)
         .block(block)
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg))
         .wrap(ratatui::widgets::Wrap { trim: true });
 
     f.render_widget(paragraph, area);
 }
 
-pub fn render_error_popup(f: &mut Frame, popup: &ErrorPopup) {
+pub fn render_theme_popup(f: &mut Frame, popup: &ThemePopup, palette: &UiPalette) {
+    let area = centered_rect(60, 60, f.size());
+    f.render_widget(ratatui::widgets::Clear, area);
+
+    let items: Vec<Line> = popup
+        .themes
+        .iter()
+        .enumerate()
+        .map(|(i, theme)| {
+            if i == popup.selected_index {
+                Line::from(vec![Span::styled(
+                    format!("> {}", theme),
+                    Style::default().fg(palette.highlight),
+                )])
+            } else {
+                Line::from(vec![Span::raw(format!("  {}", theme))])
+            }
+        })
+        .collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.border))
+        .title("Select Theme - Enter to apply, Esc to cancel");
+
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let scroll = popup
+        .selected_index
+        .saturating_sub(visible_rows.saturating_sub(1));
+
+    let paragraph = Paragraph::new(items)
+        .block(block)
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg))
+        .scroll((scroll as u16, 0));
+
+    f.render_widget(paragraph, area);
+}
+
+pub fn render_format_popup(f: &mut Frame, popup: &FormatPopup, palette: &UiPalette) {
+    let area = centered_rect(40, 50, f.size());
+    f.render_widget(ratatui::widgets::Clear, area);
+
+    let items: Vec<Line> = popup
+        .formats
+        .iter()
+        .enumerate()
+        .map(|(i, format)| {
+            let label = if popup.detected.as_deref() == Some(format.as_str()) {
+                format!("{} (detected)", format)
+            } else {
+                format.clone()
+            };
+            if i == popup.selected_index {
+                Line::from(vec![Span::styled(
+                    format!("> {}", label),
+                    Style::default().fg(palette.highlight),
+                )])
+            } else {
+                Line::from(vec![Span::raw(format!("  {}", label))])
+            }
+        })
+        .collect();
+
+    let title = if popup.code_block {
+        "Format Code Block As - Enter to format, Esc to cancel"
+    } else {
+        "Format As - Enter to format, Esc to cancel"
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.border))
+        .title(title);
+
+    let paragraph = Paragraph::new(items)
+        .block(block)
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg));
+
+    f.render_widget(paragraph, area);
+}
+
+pub fn render_json_popup(f: &mut Frame, popup: &JsonPopup, palette: &UiPalette) {
+    if let Some(query) = &popup.query {
+        let area = centered_rect(60, 20, f.size());
+        f.render_widget(ratatui::widgets::Clear, area);
+
+        let text = Paragraph::new(query.as_str())
+            .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(palette.border))
+                    .title("JSON Query, e.g. .items[].name - Enter to run, Esc to go back"),
+            );
+        f.render_widget(text, area);
+        return;
+    }
+
+    let area = centered_rect(40, 50, f.size());
+    f.render_widget(ratatui::widgets::Clear, area);
+
+    let items: Vec<Line> = popup
+        .labels()
+        .into_iter()
+        .enumerate()
+        .map(|(i, label)| {
+            if i == popup.selected_index {
+                Line::from(vec![Span::styled(
+                    format!("> {}", label),
+                    Style::default().fg(palette.highlight),
+                )])
+            } else {
+                Line::from(vec![Span::raw(format!("  {}", label))])
+            }
+        })
+        .collect();
+
+    let title = if popup.code_block {
+        "JSON Tools on Code Block - Enter to run, Esc to cancel"
+    } else {
+        "JSON Tools - Enter to run, Esc to cancel"
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.border))
+        .title(title);
+
+    let paragraph = Paragraph::new(items)
+        .block(block)
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg));
+
+    f.render_widget(paragraph, area);
+}
+
+pub fn render_outline_popup(f: &mut Frame, popup: &OutlinePopup, palette: &UiPalette) {
+    let area = centered_rect(60, 60, f.size());
+    f.render_widget(ratatui::widgets::Clear, area);
+
+    let items: Vec<Line> = if popup.entries.is_empty() {
+        vec![Line::from(Span::styled(
+            "  No headings",
+            Style::default().fg(palette.gutter),
+        ))]
+    } else {
+        popup
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let indent = "  ".repeat(entry.level.saturating_sub(1));
+                let style = if entry.level == 0 {
+                    Style::default().fg(palette.border)
+                } else {
+                    Style::default().fg(palette.headings[(entry.level - 1).min(5)])
+                };
+                if i == popup.selected_index {
+                    Line::from(vec![Span::styled(
+                        format!("> {}{}", indent, entry.text),
+                        Style::default().fg(palette.highlight),
+                    )])
+                } else {
+                    Line::from(vec![
+                        Span::raw(format!("  {}", indent)),
+                        Span::styled(entry.text.clone(), style),
+                    ])
+                }
+            })
+            .collect()
+    };
+
+    let title = if popup.whole_notebook {
+        "Outline (all blocks) - Enter to jump, Tab for this block, t to insert TOC, Esc to cancel"
+    } else {
+        "Outline - Enter to jump, Tab for all blocks, t to insert TOC, Esc to cancel"
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.border))
+        .title(title);
+
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let scroll = popup
+        .selected_index
+        .saturating_sub(visible_rows.saturating_sub(1));
+
+    let paragraph = Paragraph::new(items)
+        .block(block)
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg))
+        .scroll((scroll as u16, 0));
+
+    f.render_widget(paragraph, area);
+}
+
+pub fn render_backlinks_popup(f: &mut Frame, popup: &BacklinksPopup, palette: &UiPalette) {
+    let area = centered_rect(60, 60, f.size());
+    f.render_widget(ratatui::widgets::Clear, area);
+
+    let items: Vec<Line> = if popup.entries.is_empty() {
+        vec![Line::from(Span::styled(
+            "  No blocks link here",
+            Style::default().fg(palette.gutter),
+        ))]
+    } else {
+        popup
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let source = format!("{}:{}", entry.title, entry.line + 1);
+                if i == popup.selected_index {
+                    Line::from(vec![Span::styled(
+                        format!("> {}  {}", source, entry.text),
+                        Style::default().fg(palette.highlight),
+                    )])
+                } else {
+                    Line::from(vec![
+                        Span::styled(format!("  {}", source), Style::default().fg(palette.border)),
+                        Span::raw(format!("  {}", entry.text)),
+                    ])
+                }
+            })
+            .collect()
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.border))
+        .title(format!(
+            "Links to {} - Enter to jump, Esc to cancel",
+            popup.target
+        ));
+
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let scroll = popup
+        .selected_index
+        .saturating_sub(visible_rows.saturating_sub(1));
+
+    let paragraph = Paragraph::new(items)
+        .block(block)
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg))
+        .scroll((scroll as u16, 0));
+
+    f.render_widget(paragraph, area);
+}
+
+pub fn render_clip_history_popup(f: &mut Frame, popup: &ClipHistoryPopup, palette: &UiPalette) {
+    let area = centered_rect(60, 60, f.size());
+    f.render_widget(ratatui::widgets::Clear, area);
+
+    let preview_width = area.width.saturating_sub(16) as usize;
+    let items: Vec<Line> = if popup.entries.is_empty() {
+        vec![Line::from(Span::styled(
+            "  Nothing copied yet",
+            Style::default().fg(palette.gutter),
+        ))]
+    } else {
+        popup
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let preview = entry_preview(entry, preview_width);
+                if i == popup.selected_index {
+                    Line::from(vec![Span::styled(
+                        format!("> {:>2}  {}", i, preview),
+                        Style::default().fg(palette.highlight),
+                    )])
+                } else {
+                    Line::from(vec![
+                        Span::styled(format!("  {:>2}", i), Style::default().fg(palette.gutter)),
+                        Span::raw(format!("  {}", preview)),
+                    ])
+                }
+            })
+            .collect()
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(palette.border))
+        .title("Clipboard History - Enter to paste, c to copy again, Esc to cancel");
+
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let scroll = popup
+        .selected_index
+        .saturating_sub(visible_rows.saturating_sub(1));
+
+    let paragraph = Paragraph::new(items)
+        .block(block)
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg))
+        .scroll((scroll as u16, 0));
+
+    f.render_widget(paragraph, area);
+}
+
+pub fn render_error_popup(f: &mut Frame, popup: &ErrorPopup, palette: &UiPalette) {
     if !popup.visible {
         return;
     }
@@ -235,16 +582,53 @@ pub fn render_error_popup(f: &mut Frame, popup: &ErrorPopup) {
     f.render_widget(ratatui::widgets::Clear, area);
 
     let text = Paragraph::new(popup.message.as_str())
-        .style(Style::default().fg(Color::Red))
+        .style(Style::default().fg(palette.error).bg(palette.popup_bg))
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Red))
+                .border_style(Style::default().fg(palette.error))
                 .title("Error - Esc to exit"),
         );
     f.render_widget(text, area);
 }
 
+pub fn render_debug_overlay(f: &mut Frame, stats: &RenderCacheStats, palette: &UiPalette) {
+    let size = f.size();
+    let width = 32.min(size.width);
+    let height = 8.min(size.height);
+    let area = Rect::new(size.width - width, size.height - height, width, height);
+    f.render_widget(ratatui::widgets::Clear, area);
+
+    let lookups = stats.hits + stats.misses;
+    let hit_rate = if lookups == 0 {
+        0.0
+    } else {
+        stats.hits as f64 * 100.0 / lookups as f64
+    };
+    let lines = vec![
+        Line::from(format!("hits:      {}", stats.hits)),
+        Line::from(format!("misses:    {}", stats.misses)),
+        Line::from(format!("hit rate:  {:.1}%", hit_rate)),
+        Line::from(format!("evictions: {}", stats.evictions)),
+        Line::from(format!("entries:   {}", stats.entries)),
+        Line::from(format!(
+            "memory:    {}/{} KiB",
+            stats.bytes / 1024,
+            stats.max_bytes / 1024
+        )),
+    ];
+
+    let text = Paragraph::new(lines)
+        .style(Style::default().fg(palette.text_fg).bg(palette.popup_bg))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(palette.border))
+                .title("Render cache"),
+        );
+    f.render_widget(text, area);
+}
+
 pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -276,6 +660,7 @@ mod tests {
     use ratatui::{backend::TestBackend, Terminal};
 
     use super::*;
+    use crate::ORANGE;
 
     #[test]
     fn test_centered_rect() {
@@ -295,7 +680,7 @@ mod tests {
         terminal
             .draw(|f| {
                 let area = f.size();
-                render_header(f, area, false);
+                render_header(f, area, false, &UiPalette::dark());
             })
             .unwrap();
 
@@ -321,6 +706,31 @@ mod tests {
         assert!(buffer.content.iter().any(|cell| cell.fg == ORANGE));
     }
 
+    #[test]
+    fn test_render_header_narrow_terminal() {
+        let backend = TestBackend::new(5, 1);
+        let mut terminal = Terminal::new(backend).unwrap();
+
+        terminal
+            .draw(|f| {
+                let area = f.size();
+                render_header(f, area, true, &UiPalette::dark());
+            })
+            .unwrap();
+
+        // No command fits, so only the start of the name is shown
+        let row: String = terminal
+            .backend()
+            .buffer()
+            .content
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert_eq!(row.len(), 5);
+        assert!(!row.contains(':'), "{:?}", row);
+        assert!(row.contains("Th"), "{:?}", row);
+    }
+
     #[test]
     fn test_render_title_popup() {
         let backend = TestBackend::new(100, 30);
@@ -332,7 +742,7 @@ mod tests {
 
         terminal
             .draw(|f| {
-                render_title_popup(f, &popup);
+                render_title_popup(f, &popup, &UiPalette::dark());
             })
             .unwrap();
 
@@ -376,7 +786,7 @@ mod tests {
 
         terminal
             .draw(|f| {
-                render_title_select_popup(f, &popup);
+                render_title_select_popup(f, &popup, &UiPalette::dark());
             })
             .unwrap();
 
@@ -404,7 +814,7 @@ mod tests {
 
         terminal
             .draw(|f| {
-                render_edit_commands_popup(f);
+                render_edit_commands_popup(f, &UiPalette::dark());
             })
             .unwrap();
 
@@ -429,4 +839,76 @@ mod tests {
             .iter()
             .any(|cell| cell.symbol().contains("I") && cell.fg == ORANGE));
     }
+
+    #[test]
+    fn test_render_header_light_palette() {
+        let backend = TestBackend::new(100, 1);
+        let mut terminal = Terminal::new(backend).unwrap();
+        let palette = UiPalette::light();
+
+        terminal
+            .draw(|f| {
+                let area = f.size();
+                render_header(f, area, false, &palette);
+            })
+            .unwrap();
+
+        let buffer = terminal.backend().buffer();
+        assert!(buffer
+            .content
+            .iter()
+            .all(|cell| cell.bg == palette.header_bg));
+        assert!(!buffer.content.iter().any(|cell| cell.fg == ORANGE));
+    }
+
+    #[test]
+    fn test_render_theme_popup() {
+        let backend = TestBackend::new(100, 30);
+        let mut terminal = Terminal::new(backend).unwrap();
+        let mut popup = ThemePopup::new();
+        popup.open(
+            vec![
+                "InspiredGitHub".to_string(),
+                "base16-ocean.dark".to_string(),
+            ],
+            "base16-ocean.dark",
+            UiPalette::dark(),
+        );
+
+        terminal
+            .draw(|f| {
+                render_theme_popup(f, &popup, &UiPalette::dark());
+            })
+            .unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let rendered: String = buffer.content.iter().map(|cell| cell.symbol()).collect();
+        assert!(rendered.contains("> base16-ocean.dark"));
+        assert!(rendered.contains("  InspiredGitHub"));
+    }
+
+    #[test]
+    fn test_render_debug_overlay() {
+        let backend = TestBackend::new(100, 30);
+        let mut terminal = Terminal::new(backend).unwrap();
+        let stats = RenderCacheStats {
+            hits: 3,
+            misses: 1,
+            evictions: 0,
+            entries: 1,
+            bytes: 2048,
+            max_bytes: 4096,
+        };
+
+        terminal
+            .draw(|f| {
+                render_debug_overlay(f, &stats, &UiPalette::dark());
+            })
+            .unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let rendered: String = buffer.content.iter().map(|cell| cell.symbol()).collect();
+        assert!(rendered.contains("hit rate:  75.0%"));
+        assert!(rendered.contains("memory:    2/4 KiB"));
+    }
 }
@@ -1,7 +1,9 @@
-use crate::EditorClipboard;
 use anyhow::{bail, Result};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyModifiers},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -13,12 +15,30 @@ use std::{
 use tui_textarea::TextArea;
 
 use crate::{
-    format_json, format_markdown, get_save_file_path, load_textareas, save_textareas,
+    cli::appended_lines,
+    code_fence::{code_fence_at, CodeFence},
+    config::ThothConfig,
+    control::{blocks_version, ControlRequest, ControlResponse},
+    get_save_file_path,
+    json_tools::{query_json, JsonOperation},
+    json_tree::JsonTree,
+    load_textareas,
+    outline::{headings, insert_or_refresh_toc},
+    outline_popup::OutlineEntry,
+    save_textareas,
+    scrollable_textarea::StructuredView,
+    table_view::TableView,
+    theme::{UiPalette, DEFAULT_THEME},
     ui::{
-        render_edit_commands_popup, render_error_popup, render_header, render_title_popup,
+        render_backlinks_popup, render_clip_history_popup, render_debug_overlay,
+        render_edit_commands_popup, render_error_popup, render_format_popup, render_header,
+        render_json_popup, render_outline_popup, render_theme_popup, render_title_popup,
         render_title_select_popup, EditCommandsPopup, ErrorPopup,
     },
-    ScrollableTextArea, TitlePopup, TitleSelectPopup,
+    utils::{block_content, block_to_textarea},
+    wiki_links::backlinks,
+    BacklinksPopup, Block, ClipHistoryPopup, FormatPopup, FormatterRegistry, JsonPopup,
+    OutlinePopup, ScrollableTextArea, ThemePopup, TitlePopup, TitleSelectPopup,
 };
 
 use std::env;
@@ -30,10 +50,19 @@ pub struct UIState {
     pub scrollable_textarea: ScrollableTextArea,
     pub title_popup: TitlePopup,
     pub title_select_popup: TitleSelectPopup,
+    pub theme_popup: ThemePopup,
+    pub format_popup: FormatPopup,
+    pub json_popup: JsonPopup,
+    pub outline_popup: OutlinePopup,
+    pub backlinks_popup: BacklinksPopup,
+    pub clip_history_popup: ClipHistoryPopup,
+    pub formatters: FormatterRegistry,
     pub error_popup: ErrorPopup,
     pub edit_commands_popup: EditCommandsPopup,
-    pub clipboard: Option<EditorClipboard>,
     pub last_draw: Instant,
+    pub show_debug_overlay: bool,
+    /// The `palette` of config.toml, which every theme is shown with
+    pub palette_override: Option<UiPalette>,
 }
 
 impl UIState {
@@ -49,22 +78,72 @@ impl UIState {
         }
         scrollable_textarea.initialize_scroll();
 
+        let mut error_popup = ErrorPopup::new();
+        if let Some(e) = scrollable_textarea.syntax_error() {
+            error_popup.show(format!(
+                "Failed to load syntaxes, using the built-in ones: {}",
+                e
+            ));
+        }
+        if let Some(e) = scrollable_textarea.theme_error() {
+            error_popup.show(format!("Failed to load themes, skipping them: {}", e));
+        }
+        let mut formatters = FormatterRegistry::with_builtins();
+        let mut palette_override = None;
+        match ThothConfig::load() {
+            Ok(config) => {
+                scrollable_textarea.primary_selection = config.primary_selection;
+                let theme = config.theme.as_deref().unwrap_or(DEFAULT_THEME);
+                let applied = configured_palette(&config).and_then(|palette| {
+                    palette_override = palette;
+                    scrollable_textarea.set_theme(theme, palette)
+                });
+                if let Err(e) = applied {
+                    error_popup.show(format!("Failed to apply theme: {}", e));
+                }
+                match FormatterRegistry::from_config(&config) {
+                    Ok(registry) => formatters = registry,
+                    Err(e) => error_popup.show(format!("Failed to load formatters: {}", e)),
+                }
+            }
+            Err(e) => error_popup.show(format!("{:#}", e)),
+        }
+
         Ok(UIState {
             scrollable_textarea,
             title_popup: TitlePopup::new(),
             title_select_popup: TitleSelectPopup::new(),
-            error_popup: ErrorPopup::new(),
+            theme_popup: ThemePopup::new(),
+            format_popup: FormatPopup::new(),
+            json_popup: JsonPopup::new(),
+            outline_popup: OutlinePopup::new(),
+            backlinks_popup: BacklinksPopup::new(),
+            clip_history_popup: ClipHistoryPopup::new(),
+            formatters,
+            error_popup,
             edit_commands_popup: EditCommandsPopup::new(),
-            clipboard: EditorClipboard::try_new(),
             last_draw: Instant::now(),
+            show_debug_overlay: false,
+            palette_override,
         })
     }
 }
 
+fn configured_palette(config: &ThothConfig) -> Result<Option<UiPalette>> {
+    match config.palette.as_deref() {
+        Some(name) => match UiPalette::from_name(name) {
+            Some(palette) => Ok(Some(palette)),
+            None => bail!("Unknown palette '{}', expected 'dark' or 'light'", name),
+        },
+        None => Ok(None),
+    }
+}
+
 pub fn draw_ui(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     state: &mut UIState,
 ) -> Result<()> {
+    let palette = state.scrollable_textarea.palette;
     terminal.draw(|f| {
         let chunks = ratatui::layout::Layout::default()
             .direction(ratatui::layout::Direction::Vertical)
@@ -77,7 +156,7 @@ pub fn draw_ui(
             )
             .split(f.size());
 
-        render_header(f, chunks[0], state.scrollable_textarea.edit_mode);
+        render_header(f, chunks[0], state.scrollable_textarea.edit_mode, &palette);
         if state.scrollable_textarea.full_screen_mode {
             state.scrollable_textarea.render(f, f.size()).unwrap();
         } else {
@@ -85,39 +164,252 @@ pub fn draw_ui(
         }
 
         if state.title_popup.visible {
-            render_title_popup(f, &state.title_popup);
+            render_title_popup(f, &state.title_popup, &palette);
         } else if state.title_select_popup.visible {
-            render_title_select_popup(f, &state.title_select_popup);
+            render_title_select_popup(f, &state.title_select_popup, &palette);
+        } else if state.theme_popup.visible {
+            render_theme_popup(f, &state.theme_popup, &palette);
+        } else if state.format_popup.visible {
+            render_format_popup(f, &state.format_popup, &palette);
+        } else if state.json_popup.visible {
+            render_json_popup(f, &state.json_popup, &palette);
+        } else if state.outline_popup.visible {
+            render_outline_popup(f, &state.outline_popup, &palette);
+        } else if state.backlinks_popup.visible {
+            render_backlinks_popup(f, &state.backlinks_popup, &palette);
+        } else if state.clip_history_popup.visible {
+            render_clip_history_popup(f, &state.clip_history_popup, &palette);
         }
 
         if state.edit_commands_popup.visible {
-            render_edit_commands_popup(f);
+            render_edit_commands_popup(f, &palette);
+        }
+
+        if state.show_debug_overlay {
+            let stats = state.scrollable_textarea.render_cache_stats();
+            render_debug_overlay(f, &stats, &palette);
         }
 
         if state.error_popup.visible {
-            render_error_popup(f, &state.error_popup);
+            render_error_popup(f, &state.error_popup, &palette);
         }
     })?;
     Ok(())
 }
 
+/// Dispatches a terminal event. Returns true when thoth should quit.
+pub fn handle_event(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    state: &mut UIState,
+    event: Event,
+) -> Result<bool> {
+    match event {
+        Event::Key(key) => handle_input(terminal, state, key),
+        Event::Paste(text) => {
+            handle_paste_event(state, &text);
+            Ok(false)
+        }
+        Event::Mouse(mouse) => {
+            handle_mouse_event(state, mouse);
+            Ok(false)
+        }
+        Event::Resize(_, height) => {
+            // The header row is hidden in full screen mode
+            let viewport_height = if state.scrollable_textarea.full_screen_mode {
+                height
+            } else {
+                height.saturating_sub(1)
+            };
+            state.scrollable_textarea.handle_resize(viewport_height);
+            Ok(false)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Carries out a CLI command sent to this window over its control socket.
+pub fn handle_control_request(state: &mut UIState, request: ControlRequest) -> ControlResponse {
+    let sta = &mut state.scrollable_textarea;
+    let find = |titles: &[String], name: &str| titles.iter().position(|title| title == name);
+    let output = match request {
+        ControlRequest::Add { name, content } => {
+            if find(&sta.titles, &name).is_some() {
+                return ControlResponse::Error(format!(
+                    "Block '{}' already exists. Use `thoth append {}` to add to it",
+                    name, name
+                ));
+            }
+            let block = Block::new(name.as_str(), content.trim_end());
+            let title = sta.append_textarea(block_to_textarea(&block), name);
+            format!("Block '{}' added successfully.", title)
+        }
+        ControlRequest::Append { name, content } => {
+            let Some(index) = find(&sta.titles, &name) else {
+                return ControlResponse::Error(format!("Block '{}' not found", name));
+            };
+            let lines = appended_lines(sta.textareas[index].lines(), &content);
+            replace_content(sta, index, lines.join("\n"));
+            format!(
+                "Appended {} lines to block '{}'.",
+                content.lines().count(),
+                name
+            )
+        }
+        ControlRequest::View { name } => {
+            return match find(&sta.titles, &name) {
+                Some(index) => ControlResponse::Ok(block_content(sta.textareas[index].lines())),
+                None => ControlResponse::Error(format!("Block '{}' not found", name)),
+            };
+        }
+        ControlRequest::Blocks => {
+            let blocks = tui_blocks(sta);
+            return ControlResponse::Blocks {
+                version: blocks_version(&blocks),
+                blocks,
+            };
+        }
+        ControlRequest::Insert { version, .. }
+        | ControlRequest::Update { version, .. }
+        | ControlRequest::Delete { version, .. }
+            if version != blocks_version(&tui_blocks(sta)) =>
+        {
+            return ControlResponse::Error(
+                "The notes were changed in the open thoth since they were read".to_string(),
+            );
+        }
+        ControlRequest::Insert { block, .. } => {
+            if find(&sta.titles, &block.title).is_some() {
+                return ControlResponse::Error(format!("Block '{}' already exists", block.title));
+            }
+            let title = sta.append_textarea(block_to_textarea(&block), block.title);
+            format!("Block '{}' added.", title)
+        }
+        ControlRequest::Update {
+            name,
+            new_name,
+            content,
+            ..
+        } => {
+            let Some(index) = find(&sta.titles, &name) else {
+                return ControlResponse::Error(format!("Block '{}' not found", name));
+            };
+            // Renamed first, as the notes file does, so links in the new content stay as sent
+            if let Some(new_name) = new_name {
+                sta.rename_block(index, new_name);
+            }
+            if let Some(content) = content {
+                replace_content(sta, index, content);
+            }
+            format!("Block '{}' updated.", sta.titles[index])
+        }
+        ControlRequest::Delete { name, .. } => {
+            let Some(index) = find(&sta.titles, &name) else {
+                return ControlResponse::Error(format!("Block '{}' not found", name));
+            };
+            sta.remove_textarea(index);
+            format!("Block '{}' deleted.", name)
+        }
+    };
+    // Saved right away so the change is kept even if this window does not exit cleanly
+    match save_textareas(&sta.textareas, &sta.titles, get_save_file_path()) {
+        Ok(()) => ControlResponse::Ok(output),
+        Err(e) => ControlResponse::Error(format!("Failed to save the notes: {}", e)),
+    }
+}
+
+/// The blocks as they are in this window, saved or not.
+fn tui_blocks(sta: &ScrollableTextArea) -> Vec<Block> {
+    sta.titles
+        .iter()
+        .zip(&sta.textareas)
+        .map(|(title, textarea)| Block::new(title.as_str(), block_content(textarea.lines())))
+        .collect()
+}
+
+/// Gives block `index` new content without moving its cursor.
+fn replace_content(sta: &mut ScrollableTextArea, index: usize, content: String) {
+    let (row, col) = sta.textareas[index].cursor();
+    let mut textarea = block_to_textarea(&Block::new(sta.titles[index].as_str(), content));
+    textarea.move_cursor(tui_textarea::CursorMove::Jump(row as u16, col as u16));
+    sta.textareas[index] = textarea;
+}
+
+fn any_popup_visible(state: &UIState) -> bool {
+    state.title_popup.visible
+        || state.title_select_popup.visible
+        || state.theme_popup.visible
+        || state.format_popup.visible
+        || state.json_popup.visible
+        || state.outline_popup.visible
+        || state.backlinks_popup.visible
+        || state.clip_history_popup.visible
+        || state.error_popup.visible
+}
+
+fn handle_paste_event(state: &mut UIState, text: &str) {
+    if state.title_popup.visible {
+        let line = text.lines().collect::<Vec<_>>().join(" ");
+        state.title_popup.title.push_str(&line);
+    } else if let Some(query) = state.json_popup.query.as_mut() {
+        query.push_str(&text.lines().collect::<String>());
+    } else if state.scrollable_textarea.edit_mode && !any_popup_visible(state) {
+        state.scrollable_textarea.paste_text(text);
+        state.scrollable_textarea.start_sel = usize::MAX;
+    }
+}
+
+fn handle_mouse_event(state: &mut UIState, mouse: MouseEvent) {
+    if any_popup_visible(state) {
+        return;
+    }
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            state.scrollable_textarea.click(mouse.column, mouse.row);
+        }
+        MouseEventKind::Down(MouseButton::Middle) => paste_primary(state),
+        MouseEventKind::ScrollUp => state.scrollable_textarea.scroll_by(-1),
+        MouseEventKind::ScrollDown => state.scrollable_textarea.scroll_by(1),
+        _ => {}
+    }
+}
+
 pub fn handle_input(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     state: &mut UIState,
     key: event::KeyEvent,
 ) -> Result<bool> {
+    if key.code == KeyCode::F(12) {
+        state.show_debug_overlay = !state.show_debug_overlay;
+        return Ok(false);
+    }
+
     if state.scrollable_textarea.full_screen_mode {
         handle_full_screen_input(state, key)
     } else if state.title_popup.visible {
         handle_title_popup_input(state, key)
     } else if state.title_select_popup.visible {
         handle_title_select_popup_input(state, key)
+    } else if state.theme_popup.visible {
+        handle_theme_popup_input(state, key)
+    } else if state.format_popup.visible {
+        handle_format_popup_input(state, key)
+    } else if state.json_popup.visible {
+        handle_json_popup_input(state, key)
+    } else if state.outline_popup.visible {
+        handle_outline_popup_input(state, key)
+    } else if state.backlinks_popup.visible {
+        handle_backlinks_popup_input(state, key)
+    } else if state.clip_history_popup.visible {
+        handle_clip_history_popup_input(state, key)
     } else {
         handle_normal_input(terminal, state, key)
     }
 }
 
 fn handle_full_screen_input(state: &mut UIState, key: event::KeyEvent) -> Result<bool> {
+    if handle_structured_view_input(state, key) {
+        return Ok(false);
+    }
     match key.code {
         KeyCode::Esc => {
             if state.scrollable_textarea.edit_mode {
@@ -159,6 +451,22 @@ fn handle_full_screen_input(state: &mut UIState, key: event::KeyEvent) -> Result
                     .show(format!("Failed to copy to clipboard: {}", e));
             }
         }
+        KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            state.scrollable_textarea.toggle_task_on_focused();
+        }
+        // The task item Ctrl+O toggles in view mode
+        KeyCode::Tab if !state.scrollable_textarea.edit_mode => {
+            state.scrollable_textarea.move_task_selection(1);
+        }
+        KeyCode::BackTab if !state.scrollable_textarea.edit_mode => {
+            state.scrollable_textarea.move_task_selection(-1);
+        }
+        KeyCode::Char('i') if key.modifiers.contains(KeyModifiers::ALT) => paste_primary(state),
+        // Switch a JSON or CSV block between the tree or table and its text
+        KeyCode::Char('r') if !state.scrollable_textarea.edit_mode => {
+            state.scrollable_textarea.structured_view_enabled =
+                !state.scrollable_textarea.structured_view_enabled;
+        }
         _ => {
             if state.scrollable_textarea.edit_mode {
                 state.scrollable_textarea.textareas[state.scrollable_textarea.focused_index]
@@ -169,6 +477,75 @@ fn handle_full_screen_input(state: &mut UIState, key: event::KeyEvent) -> Result
     Ok(false)
 }
 
+/// What a key did in the full screen tree or table view.
+enum ViewKey {
+    /// Left to full screen mode
+    Ignored,
+    Handled,
+    Copy(String),
+}
+
+/// Keys of the JSON tree and table views. Returns false for keys they leave to full screen mode.
+fn handle_structured_view_input(state: &mut UIState, key: event::KeyEvent) -> bool {
+    let page = state
+        .scrollable_textarea
+        .viewport_height
+        .saturating_sub(2)
+        .max(1) as isize;
+    let result = match state.scrollable_textarea.active_structured_view() {
+        Some(StructuredView::Json(tree)) => handle_json_tree_key(tree, key, page),
+        Some(StructuredView::Table(table)) => handle_table_key(table, key, page),
+        None => ViewKey::Ignored,
+    };
+    match result {
+        ViewKey::Ignored => return false,
+        ViewKey::Handled => {}
+        ViewKey::Copy(text) => {
+            if let Err(e) = state.scrollable_textarea.copy_text(text) {
+                state
+                    .error_popup
+                    .show(format!("Failed to copy to clipboard: {}", e));
+            }
+        }
+    }
+    true
+}
+
+fn handle_json_tree_key(tree: &mut JsonTree, key: event::KeyEvent, page: isize) -> ViewKey {
+    match key.code {
+        KeyCode::Up => tree.move_selection(-1),
+        KeyCode::Down => tree.move_selection(1),
+        KeyCode::PageUp => tree.move_selection(-page),
+        KeyCode::PageDown => tree.move_selection(page),
+        KeyCode::Home => tree.select_first(),
+        KeyCode::End => tree.select_last(),
+        KeyCode::Right => tree.expand(),
+        KeyCode::Left => tree.collapse(),
+        KeyCode::Char(' ') => tree.toggle(),
+        KeyCode::Char('p') => return ViewKey::Copy(tree.selected_path()),
+        KeyCode::Char('c') => return ViewKey::Copy(tree.selected_value()),
+        _ => return ViewKey::Ignored,
+    }
+    ViewKey::Handled
+}
+
+fn handle_table_key(table: &mut TableView, key: event::KeyEvent, page: isize) -> ViewKey {
+    match key.code {
+        KeyCode::Up => table.move_row(-1),
+        KeyCode::Down => table.move_row(1),
+        KeyCode::PageUp => table.move_row(-page),
+        KeyCode::PageDown => table.move_row(page),
+        KeyCode::Home => table.select_first_row(),
+        KeyCode::End => table.select_last_row(),
+        KeyCode::Left => table.move_column(-1),
+        KeyCode::Right => table.move_column(1),
+        KeyCode::Char('s') => table.cycle_sort(),
+        KeyCode::Char('c') => return ViewKey::Copy(table.selected_column_text()),
+        _ => return ViewKey::Ignored,
+    }
+    ViewKey::Handled
+}
+
 fn handle_title_popup_input(state: &mut UIState, key: event::KeyEvent) -> Result<bool> {
     match key.code {
         KeyCode::Enter => {
@@ -225,6 +602,285 @@ fn handle_title_select_popup_input(state: &mut UIState, key: event::KeyEvent) ->
     Ok(false)
 }
 
+fn handle_theme_popup_input(state: &mut UIState, key: event::KeyEvent) -> Result<bool> {
+    let theme_count = state.theme_popup.themes.len();
+    match key.code {
+        KeyCode::Enter => {
+            state.theme_popup.visible = false;
+        }
+        KeyCode::Esc => {
+            state.theme_popup.visible = false;
+            let original = state.theme_popup.original_theme.clone();
+            let palette = state.theme_popup.original_palette;
+            if let Err(e) = state
+                .scrollable_textarea
+                .set_theme(&original, Some(palette))
+            {
+                state
+                    .error_popup
+                    .show(format!("Failed to restore theme: {}", e));
+            }
+            return Ok(false);
+        }
+        KeyCode::Up if theme_count > 0 => {
+            state.theme_popup.selected_index =
+                (state.theme_popup.selected_index + theme_count - 1) % theme_count;
+        }
+        KeyCode::Down if theme_count > 0 => {
+            state.theme_popup.selected_index = (state.theme_popup.selected_index + 1) % theme_count;
+        }
+        _ => return Ok(false),
+    }
+
+    // Preview the highlighted theme live
+    if let Some(theme) = state.theme_popup.selected_theme().map(str::to_string) {
+        let palette = state.palette_override;
+        if let Err(e) = state.scrollable_textarea.set_theme(&theme, palette) {
+            state
+                .error_popup
+                .show(format!("Failed to apply theme: {}", e));
+        }
+    }
+    Ok(false)
+}
+
+fn handle_format_popup_input(state: &mut UIState, key: event::KeyEvent) -> Result<bool> {
+    let format_count = state.format_popup.formats.len();
+    match key.code {
+        KeyCode::Enter => {
+            state.format_popup.visible = false;
+            if let Some(format) = state.format_popup.selected_format().map(str::to_string) {
+                format_current_textarea(state, &format)?;
+            }
+        }
+        KeyCode::Esc => {
+            state.format_popup.visible = false;
+        }
+        KeyCode::Up if format_count > 0 => {
+            state.format_popup.selected_index =
+                (state.format_popup.selected_index + format_count - 1) % format_count;
+        }
+        KeyCode::Down if format_count > 0 => {
+            state.format_popup.selected_index =
+                (state.format_popup.selected_index + 1) % format_count;
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
+fn handle_json_popup_input(state: &mut UIState, key: event::KeyEvent) -> Result<bool> {
+    if let Some(query) = state.json_popup.query.as_mut() {
+        match key.code {
+            KeyCode::Enter => {
+                let query = query.clone();
+                state.json_popup.close();
+                query_focused_json(state, &query);
+            }
+            KeyCode::Esc => state.json_popup.query = None,
+            KeyCode::Char(c) => query.push(c),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            _ => {}
+        }
+        return Ok(false);
+    }
+
+    let item_count = state.json_popup.labels().len();
+    match key.code {
+        KeyCode::Enter => match state.json_popup.selected_operation() {
+            Some(operation) => {
+                state.json_popup.close();
+                apply_json_operation(state, operation);
+            }
+            None => state.json_popup.query = Some(String::from(".")),
+        },
+        KeyCode::Esc => state.json_popup.close(),
+        KeyCode::Up => {
+            state.json_popup.selected_index =
+                (state.json_popup.selected_index + item_count - 1) % item_count;
+        }
+        KeyCode::Down => {
+            state.json_popup.selected_index = (state.json_popup.selected_index + 1) % item_count;
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
+fn outline_entries(state: &UIState, whole_notebook: bool) -> Vec<OutlineEntry> {
+    let textareas = &state.scrollable_textarea;
+    let blocks: Vec<usize> = if whole_notebook {
+        (0..textareas.textareas.len()).collect()
+    } else {
+        vec![textareas.focused_index]
+    };
+    let mut entries = Vec::new();
+    for block in blocks {
+        if whole_notebook {
+            entries.push(OutlineEntry {
+                block,
+                line: 0,
+                level: 0,
+                text: textareas.titles[block].clone(),
+            });
+        }
+        entries.extend(
+            headings(textareas.textareas[block].lines())
+                .into_iter()
+                .map(|heading| OutlineEntry {
+                    block,
+                    line: heading.line,
+                    level: heading.level,
+                    text: heading.text,
+                }),
+        );
+    }
+    entries
+}
+
+fn open_outline_popup(state: &mut UIState) {
+    let focused_index = state.scrollable_textarea.focused_index;
+    let (row, _) = state.scrollable_textarea.textareas[focused_index].cursor();
+    let entries = outline_entries(state, state.outline_popup.whole_notebook);
+    state.outline_popup.open(entries, focused_index, row);
+}
+
+fn handle_outline_popup_input(state: &mut UIState, key: event::KeyEvent) -> Result<bool> {
+    let entry_count = state.outline_popup.entries.len();
+    match key.code {
+        KeyCode::Enter => {
+            state.outline_popup.visible = false;
+            if let Some(entry) = state.outline_popup.selected_entry().cloned() {
+                jump_to_line(state, entry.block, entry.line);
+            }
+        }
+        KeyCode::Esc => {
+            state.outline_popup.visible = false;
+        }
+        KeyCode::Tab => {
+            state.outline_popup.whole_notebook = !state.outline_popup.whole_notebook;
+            open_outline_popup(state);
+        }
+        KeyCode::Char('t') => {
+            state.outline_popup.visible = false;
+            insert_toc(state);
+        }
+        KeyCode::Up if entry_count > 0 => {
+            state.outline_popup.selected_index =
+                (state.outline_popup.selected_index + entry_count - 1) % entry_count;
+        }
+        KeyCode::Down if entry_count > 0 => {
+            state.outline_popup.selected_index =
+                (state.outline_popup.selected_index + 1) % entry_count;
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
+fn open_backlinks_popup(state: &mut UIState) {
+    let textareas = &state.scrollable_textarea;
+    let blocks: Vec<&[String]> = textareas.textareas.iter().map(|t| t.lines()).collect();
+    let entries = backlinks(&textareas.titles, &blocks, textareas.focused_index);
+    let target = textareas.titles[textareas.focused_index].clone();
+    state.backlinks_popup.open(target, entries);
+}
+
+fn handle_backlinks_popup_input(state: &mut UIState, key: event::KeyEvent) -> Result<bool> {
+    let entry_count = state.backlinks_popup.entries.len();
+    match key.code {
+        KeyCode::Enter => {
+            state.backlinks_popup.visible = false;
+            if let Some(entry) = state.backlinks_popup.selected_entry().cloned() {
+                state.scrollable_textarea.push_link_history();
+                jump_to_line(state, entry.block, entry.line);
+            }
+        }
+        KeyCode::Esc => {
+            state.backlinks_popup.visible = false;
+        }
+        KeyCode::Up if entry_count > 0 => {
+            state.backlinks_popup.selected_index =
+                (state.backlinks_popup.selected_index + entry_count - 1) % entry_count;
+        }
+        KeyCode::Down if entry_count > 0 => {
+            state.backlinks_popup.selected_index =
+                (state.backlinks_popup.selected_index + 1) % entry_count;
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
+fn open_clip_history_popup(state: &mut UIState) {
+    match state.scrollable_textarea.clipboard_history() {
+        Ok(entries) => state.clip_history_popup.open(entries),
+        Err(e) => state.error_popup.show(format!("{}", e)),
+    }
+}
+
+fn handle_clip_history_popup_input(state: &mut UIState, key: event::KeyEvent) -> Result<bool> {
+    let entry_count = state.clip_history_popup.entries.len();
+    match key.code {
+        KeyCode::Enter => {
+            state.clip_history_popup.visible = false;
+            if let Some(entry) = state.clip_history_popup.selected_entry().cloned() {
+                state.scrollable_textarea.paste_text(&entry);
+            }
+        }
+        KeyCode::Char('c') => {
+            state.clip_history_popup.visible = false;
+            if let Some(entry) = state.clip_history_popup.selected_entry().cloned() {
+                if let Err(e) = state.scrollable_textarea.copy_text(entry) {
+                    state
+                        .error_popup
+                        .show(format!("Failed to copy to clipboard: {}", e));
+                }
+            }
+        }
+        KeyCode::Esc => {
+            state.clip_history_popup.visible = false;
+        }
+        KeyCode::Up if entry_count > 0 => {
+            state.clip_history_popup.selected_index =
+                (state.clip_history_popup.selected_index + entry_count - 1) % entry_count;
+        }
+        KeyCode::Down if entry_count > 0 => {
+            state.clip_history_popup.selected_index =
+                (state.clip_history_popup.selected_index + 1) % entry_count;
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
+/// Focuses `block` and puts the cursor at the start of `line` in edit mode, which
+/// scrolls the line into view.
+fn jump_to_line(state: &mut UIState, block: usize, line: usize) {
+    state.scrollable_textarea.jump_to_textarea(block);
+    state.scrollable_textarea.edit_mode = true;
+    state.scrollable_textarea.textareas[block]
+        .move_cursor(tui_textarea::CursorMove::Jump(line as u16, 0));
+}
+
+/// Refreshes the table of contents of the focused block, or inserts one at the cursor.
+fn insert_toc(state: &mut UIState) {
+    let focused_index = state.scrollable_textarea.focused_index;
+    let textarea = &state.scrollable_textarea.textareas[focused_index];
+    if headings(textarea.lines()).is_empty() {
+        state
+            .error_popup
+            .show("This block has no headings for a table of contents".to_string());
+        return;
+    }
+    let (lines, toc_end) = insert_or_refresh_toc(textarea.lines(), textarea.cursor().0);
+    let mut new_textarea = TextArea::new(lines);
+    new_textarea.move_cursor(tui_textarea::CursorMove::Jump(toc_end as u16, 0));
+    state.scrollable_textarea.textareas[focused_index] = new_textarea;
+}
+
 fn handle_normal_input(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     state: &mut UIState,
@@ -232,10 +888,53 @@ fn handle_normal_input(
 ) -> Result<bool> {
     match key.code {
         KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            format_current_textarea(state, format_markdown)?;
+            format_current_textarea(state, "markdown")?;
         }
         KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            format_current_textarea(state, format_json)?;
+            format_current_textarea(state, "json")?;
+        }
+        KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            let fence = focused_code_fence(state);
+            let detected = match &fence {
+                // Prefer the info string, then what the code itself looks like
+                Some(fence) => fence
+                    .language
+                    .as_deref()
+                    .and_then(|language| state.formatters.get(language))
+                    .or_else(|| state.formatters.detect(&fence_content(state, fence))),
+                None => state.formatters.detect(&focused_content(state)),
+            }
+            .map(|f| f.name().to_string());
+            state.format_popup.open(
+                state.formatters.names(),
+                detected.as_deref(),
+                fence.is_some(),
+            );
+        }
+        KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::ALT) => {
+            state.outline_popup.whole_notebook = false;
+            open_outline_popup(state);
+        }
+        KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::ALT) => {
+            if !state.scrollable_textarea.follow_link_at_cursor() {
+                state
+                    .error_popup
+                    .show("No [[link]] under the cursor".to_string());
+            }
+        }
+        KeyCode::Left if key.modifiers == KeyModifiers::ALT => {
+            state.scrollable_textarea.navigate_back();
+        }
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::ALT) => {
+            open_backlinks_popup(state);
+        }
+        KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::ALT) => {
+            open_clip_history_popup(state);
+        }
+        KeyCode::Char('i') if key.modifiers.contains(KeyModifiers::ALT) => paste_primary(state),
+        KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::ALT) => {
+            let code_block = focused_code_fence(state).is_some();
+            state.json_popup.open(code_block);
         }
         KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             if state.scrollable_textarea.edit_mode {
@@ -275,6 +974,16 @@ fn handle_normal_input(
                     .show(format!("Failed to copy to clipboard: {}", e));
             }
         }
+        KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            state.scrollable_textarea.toggle_task_on_focused();
+        }
+        // The task item Ctrl+O toggles in view mode
+        KeyCode::Tab if !state.scrollable_textarea.edit_mode => {
+            state.scrollable_textarea.move_task_selection(1);
+        }
+        KeyCode::BackTab if !state.scrollable_textarea.edit_mode => {
+            state.scrollable_textarea.move_task_selection(-1);
+        }
         KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             handle_paste(state)?;
         }
@@ -283,6 +992,17 @@ fn handle_normal_input(
                 state.scrollable_textarea.toggle_full_screen();
             }
         }
+        KeyCode::Char('p')
+            if key.modifiers.contains(KeyModifiers::CONTROL)
+                && !state.scrollable_textarea.edit_mode =>
+        {
+            let current = state.scrollable_textarea.theme_name();
+            state.theme_popup.open(
+                state.scrollable_textarea.theme_names(),
+                &current,
+                state.scrollable_textarea.palette,
+            );
+        }
         KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             if state.scrollable_textarea.edit_mode {
                 state.edit_commands_popup.visible = !state.edit_commands_popup.visible;
@@ -384,6 +1104,7 @@ fn handle_up_key(state: &mut UIState, key: event::KeyEvent) {
             if textarea.cursor().0 > 0 {
                 textarea.move_cursor(tui_textarea::CursorMove::Up);
             }
+            state.scrollable_textarea.mirror_selection_to_primary();
         } else {
             textarea.move_cursor(tui_textarea::CursorMove::Up);
             state.scrollable_textarea.start_sel = usize::MAX;
@@ -407,6 +1128,7 @@ fn handle_down_key(state: &mut UIState, key: event::KeyEvent) {
             if textarea.cursor().0 < textarea.lines().len() - 1 {
                 textarea.move_cursor(tui_textarea::CursorMove::Down);
             }
+            state.scrollable_textarea.mirror_selection_to_primary();
         } else {
             textarea.move_cursor(tui_textarea::CursorMove::Down);
             state.scrollable_textarea.start_sel = usize::MAX;
@@ -417,32 +1139,149 @@ fn handle_down_key(state: &mut UIState, key: event::KeyEvent) {
     }
 }
 
-fn format_current_textarea<F>(state: &mut UIState, formatter: F) -> Result<()>
-where
-    F: Fn(&str) -> Result<String>,
-{
-    let current_content = state.scrollable_textarea.textareas
-        [state.scrollable_textarea.focused_index]
+/// Inserts the primary selection at the cursor, like a middle-click paste.
+fn paste_primary(state: &mut UIState) {
+    if !state.scrollable_textarea.edit_mode {
+        return;
+    }
+    match state.scrollable_textarea.primary_contents() {
+        Ok(content) => {
+            state.scrollable_textarea.paste_text(&content);
+            state.scrollable_textarea.start_sel = usize::MAX;
+        }
+        Err(e) => state.error_popup.show(format!("{}", e)),
+    }
+}
+
+fn focused_content(state: &UIState) -> String {
+    state.scrollable_textarea.textareas[state.scrollable_textarea.focused_index]
         .lines()
-        .join("\n");
-    match formatter(&current_content) {
-        Ok(formatted) => {
-            let mut new_textarea = TextArea::default();
-            for line in formatted.lines() {
-                new_textarea.insert_str(line);
-                new_textarea.insert_newline();
-            }
-            state.scrollable_textarea.textareas[state.scrollable_textarea.focused_index] =
-                new_textarea;
-            Ok(())
+        .join("\n")
+}
+
+fn focused_code_fence(state: &UIState) -> Option<CodeFence> {
+    let textarea = &state.scrollable_textarea.textareas[state.scrollable_textarea.focused_index];
+    code_fence_at(textarea.lines(), textarea.cursor().0)
+}
+
+fn fence_content(state: &UIState, fence: &CodeFence) -> String {
+    fence.content(
+        state.scrollable_textarea.textareas[state.scrollable_textarea.focused_index].lines(),
+    )
+}
+
+/// Formats with the registered formatter for `format`, which may be an external
+/// command from the config. Inside a fenced code block only the code between the
+/// fences is formatted and the rest of the block is kept as is; markdown always
+/// formats the whole block. Nothing is replaced when formatting fails.
+fn format_current_textarea(state: &mut UIState, format: &str) -> Result<()> {
+    let fence = if format != "markdown" {
+        focused_code_fence(state)
+    } else {
+        None
+    };
+    let input = target_content(state, fence.as_ref());
+    let output = state.formatters.format(format, &input);
+    replace_target(state, fence.as_ref(), output, "format", None);
+    Ok(())
+}
+
+/// Runs a JSON toolbox operation on the code block under the cursor, or the whole block.
+fn apply_json_operation(state: &mut UIState, operation: JsonOperation) {
+    let fence = focused_code_fence(state);
+    let input = target_content(state, fence.as_ref());
+    let output = operation.apply(&input);
+    let action = format!("run '{}' on", operation.label());
+    replace_target(
+        state,
+        fence.as_ref(),
+        output,
+        &action,
+        operation.output_language(),
+    );
+}
+
+/// Opens the result of a path query in a new block after the focused one.
+fn query_focused_json(state: &mut UIState, query: &str) {
+    let fence = focused_code_fence(state);
+    let input = target_content(state, fence.as_ref());
+    match query_json(&input, query) {
+        Ok(result) => {
+            let title = format!(
+                "{} {}",
+                state.scrollable_textarea.titles[state.scrollable_textarea.focused_index],
+                query.trim()
+            );
+            let lines = result.lines().map(str::to_string).collect();
+            state
+                .scrollable_textarea
+                .add_textarea(TextArea::new(lines), title);
         }
+        Err(e) => state
+            .error_popup
+            .show(format!("Failed to query JSON: {}", e)),
+    }
+}
+
+fn target_content(state: &UIState, fence: Option<&CodeFence>) -> String {
+    match fence {
+        Some(fence) => fence_content(state, fence),
+        None => focused_content(state),
+    }
+}
+
+/// Replaces the code between the fences, or the whole focused block without a fence,
+/// with `output`. `language` relabels the fence when the output is in another language.
+fn replace_target(
+    state: &mut UIState,
+    fence: Option<&CodeFence>,
+    output: Result<String>,
+    action: &str,
+    language: Option<&str>,
+) {
+    let focused_index = state.scrollable_textarea.focused_index;
+    let output = match output {
+        Ok(output) => output,
         Err(e) => {
+            let target = if fence.is_some() {
+                "code block"
+            } else {
+                "block"
+            };
             state
                 .error_popup
-                .show(format!("Failed to format block: {}", e));
-            Ok(())
+                .show(format!("Failed to {} {}: {}", action, target, e));
+            return;
         }
-    }
+    };
+
+    let new_textarea = match fence {
+        Some(fence) => {
+            let textarea = &state.scrollable_textarea.textareas[focused_index];
+            let (row, col) = textarea.cursor();
+            let mut lines = fence.splice(textarea.lines(), &output);
+            if let Some(language) = language {
+                fence.relabel(&mut lines, language);
+            }
+            let mut new_textarea = TextArea::new(lines);
+            // Stay inside the code block, whose length may have changed
+            let last_code_row = fence.open + output.lines().count();
+            new_textarea.move_cursor(tui_textarea::CursorMove::Jump(
+                row.min(last_code_row) as u16,
+                col as u16,
+            ));
+            new_textarea
+        }
+        None => {
+            let mut new_textarea = TextArea::default();
+            for line in output.lines() {
+                new_textarea.insert_str(line);
+                new_textarea.insert_newline();
+            }
+            new_textarea
+        }
+    };
+    state.scrollable_textarea.textareas[focused_index] = new_textarea;
 }
 
 fn edit_with_external_editor(state: &mut UIState) -> Result<String> {
@@ -460,13 +1299,23 @@ fn edit_with_external_editor(state: &mut UIState) -> Result<String> {
 
     // suspend the TUI
     disable_raw_mode()?;
-    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )?;
 
     let status = Command::new(&editor).arg(temp_file.path()).status()?;
 
     // resume the TUI
     enable_raw_mode()?;
-    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        io::stdout(),
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
 
     if !status.success() {
         bail!(format!("Editor '{}' returned non-zero status", editor));
@@ -479,25 +1328,21 @@ fn edit_with_external_editor(state: &mut UIState) -> Result<String> {
 
 fn handle_paste(state: &mut UIState) -> Result<()> {
     if state.scrollable_textarea.edit_mode {
-        match &mut state.clipboard {
-            Some(clip) => {
-                if let Ok(content) = clip.get_content() {
-                    let textarea = &mut state.scrollable_textarea.textareas
-                        [state.scrollable_textarea.focused_index];
-                    for line in content.lines() {
-                        textarea.insert_str(line);
-                        textarea.insert_newline();
-                    }
-                    // Remove the last extra newline
-                    if content.ends_with('\n') {
-                        textarea.delete_char();
-                    }
+        match state.scrollable_textarea.clipboard_contents() {
+            Ok(content) => {
+                let textarea = &mut state.scrollable_textarea.textareas
+                    [state.scrollable_textarea.focused_index];
+                for line in content.lines() {
+                    textarea.insert_str(line);
+                    textarea.insert_newline();
+                }
+                // Remove the last extra newline
+                if content.ends_with('\n') {
+                    textarea.delete_char();
                 }
             }
-            None => {
-                state
-                    .error_popup
-                    .show("Failed to create clipboard".to_string());
+            Err(e) => {
+                state.error_popup.show(format!("{}", e));
             }
         }
     }
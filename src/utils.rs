@@ -1,74 +1,64 @@
 use crate::get_save_file_path;
-use anyhow::Result;
-use std::io::{BufRead, Write};
+use crate::notebook::{blocks_to_markdown, Block, Notebook};
+use anyhow::{Context, Result};
+use std::fs;
 use std::path::PathBuf;
-use std::{fs::File, io::BufReader};
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 
 pub fn save_textareas(textareas: &[TextArea], titles: &[String], file_path: PathBuf) -> Result<()> {
-    let mut file = File::create(file_path)?;
-    for (textarea, title) in textareas.iter().zip(titles.iter()) {
-        writeln!(file, "# {}", title)?;
-        let content = textarea.lines().join("\n");
-        let mut in_code_block = false;
-        for line in content.lines() {
-            if line.trim().starts_with("```") {
-                in_code_block = !in_code_block;
-            }
-            if in_code_block || !line.starts_with('#') {
-                writeln!(file, "{}", line)?;
-            } else {
-                writeln!(file, "\\{}", line)?;
-            }
-        }
-    }
-    Ok(())
+    let blocks: Vec<Block> = textareas
+        .iter()
+        .zip(titles)
+        .map(|(textarea, title)| Block::new(title.as_str(), block_content(textarea.lines())))
+        .collect();
+    fs::write(&file_path, blocks_to_markdown(&blocks))
+        .with_context(|| format!("Failed to write {}", file_path.display()))
 }
 
 pub fn load_textareas() -> Result<(Vec<TextArea<'static>>, Vec<String>)> {
-    let file = File::open(get_save_file_path())?;
-    let reader = BufReader::new(file);
-    let mut textareas = Vec::with_capacity(10);
-    let mut titles = Vec::with_capacity(10);
-    let mut current_textarea = TextArea::default();
-    let mut current_title = String::new();
-    let mut in_code_block = false;
-    let mut is_first_line = true;
+    load_textareas_from(get_save_file_path())
+}
 
-    for line in reader.lines() {
-        let line = line?;
-        if !in_code_block && line.starts_with("# ") && is_first_line {
-            current_title = line[2..].to_string();
-            is_first_line = false;
-        } else {
-            if line.trim().starts_with("```") {
-                in_code_block = !in_code_block;
-            }
-            if in_code_block {
-                current_textarea.insert_str(&line);
-            } else if let Some(strip) = line.strip_prefix('\\') {
-                current_textarea.insert_str(strip);
-            } else if line.starts_with("# ") && !is_first_line {
-                if !current_title.is_empty() {
-                    textareas.push(current_textarea);
-                    titles.push(current_title);
-                }
-                current_textarea = TextArea::default();
-                current_title = line[2..].to_string();
-                is_first_line = true;
-                continue;
-            } else {
-                current_textarea.insert_str(&line);
-            }
-            current_textarea.insert_newline();
-            is_first_line = false;
-        }
-    }
+pub fn load_textareas_from(file_path: PathBuf) -> Result<(Vec<TextArea<'static>>, Vec<String>)> {
+    let notebook = Notebook::open(file_path)?;
+    let textareas = notebook.blocks().iter().map(block_to_textarea).collect();
+    let titles = notebook
+        .blocks()
+        .iter()
+        .map(|block| block.title.clone())
+        .collect();
+    Ok((textareas, titles))
+}
 
-    if !current_title.is_empty() {
-        textareas.push(current_textarea);
-        titles.push(current_title);
-    }
+/// `block` as the TUI edits it, ending in an empty line with the cursor on it.
+pub fn block_to_textarea(block: &Block) -> TextArea<'static> {
+    let mut lines: Vec<String> = if block.content.is_empty() {
+        Vec::new()
+    } else {
+        block.content.split('\n').map(str::to_string).collect()
+    };
+    lines.push(String::new());
+    let mut textarea = TextArea::new(lines);
+    textarea.move_cursor(CursorMove::Bottom);
+    textarea
+}
 
-    Ok((textareas, titles))
+/// Text of a block edited in the TUI, without the empty line it ends in.
+pub fn block_content(lines: &[String]) -> String {
+    let lines = match lines.split_last() {
+        Some((last, rest)) if last.is_empty() => rest,
+        _ => lines,
+    };
+    lines.join("\n")
+}
+
+/// 64-bit FNV-1a hash, which unlike `DefaultHasher` is the same between runs and
+/// Rust versions.
+pub fn stable_hash(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
 }
@@ -0,0 +1,328 @@
+use std::ops::Range;
+
+use ratatui::{style::Style, text::Span};
+
+use crate::code_fence::code_fences;
+
+/// An Obsidian style `[[Target#Heading|Alias]]` link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WikiLink {
+    /// Byte range of the link in its line, brackets included
+    pub range: Range<usize>,
+    /// Title of the block linked to
+    pub target: String,
+    pub heading: Option<String>,
+    pub alias: Option<String>,
+}
+
+/// A line of one block that links to another block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backlink {
+    pub block: usize,
+    /// Title of the linking block
+    pub title: String,
+    pub line: usize,
+    pub text: String,
+}
+
+/// Every wiki link in `line`, in order.
+pub fn wiki_links(line: &str) -> Vec<WikiLink> {
+    let mut links = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = line[offset..].find("[[").map(|i| offset + i) {
+        let inner_start = start + 2;
+        let end = match line[inner_start..].find("]]") {
+            Some(i) => inner_start + i,
+            None => break,
+        };
+        let inner = &line[inner_start..end];
+        // `[[` inside the brackets means this one never closed
+        if let Some(nested) = inner.rfind("[[") {
+            offset = inner_start + nested;
+            continue;
+        }
+        let (reference, alias) = match inner.split_once('|') {
+            Some((reference, alias)) => (reference, Some(alias.trim().to_string())),
+            None => (inner, None),
+        };
+        let (target, heading) = match reference.split_once('#') {
+            Some((target, heading)) => (target, Some(heading.trim().to_string())),
+            None => (reference, None),
+        };
+        let target = target.trim();
+        if !target.is_empty() {
+            links.push(WikiLink {
+                range: start..end + 2,
+                target: target.to_string(),
+                heading,
+                alias,
+            });
+        }
+        offset = end + 2;
+    }
+    links
+}
+
+/// The link covering the character at `column` of `line`.
+pub fn wiki_link_at(line: &str, column: usize) -> Option<WikiLink> {
+    let byte = line
+        .char_indices()
+        .nth(column)
+        .map_or(line.len(), |(i, _)| i);
+    wiki_links(line)
+        .into_iter()
+        .find(|link| link.range.contains(&byte))
+}
+
+/// Index of the block titled `target`, preferring an exact match over one
+/// that differs only in case.
+pub fn resolve_link(titles: &[String], target: &str) -> Option<usize> {
+    titles.iter().position(|title| title == target).or_else(|| {
+        let target = target.to_lowercase();
+        titles
+            .iter()
+            .position(|title| title.to_lowercase() == target)
+    })
+}
+
+/// Lines outside fenced code blocks, with their index.
+fn prose_lines<S: AsRef<str>>(lines: &[S]) -> impl Iterator<Item = (usize, &str)> {
+    let fences = code_fences(lines);
+    lines
+        .iter()
+        .enumerate()
+        .filter(move |(row, _)| {
+            !fences
+                .iter()
+                .any(|fence| fence.open <= *row && *row <= fence.close)
+        })
+        .map(|(row, line)| (row, line.as_ref()))
+}
+
+/// Lines of other blocks with a link that resolves to block `target`.
+pub fn backlinks<S: AsRef<str>>(
+    titles: &[String],
+    blocks: &[&[S]],
+    target: usize,
+) -> Vec<Backlink> {
+    let mut found = Vec::new();
+    for (block, lines) in blocks.iter().enumerate() {
+        if block == target {
+            continue;
+        }
+        for (row, line) in prose_lines(lines) {
+            let links_here = wiki_links(line)
+                .iter()
+                .any(|link| resolve_link(titles, &link.target) == Some(target));
+            if links_here {
+                found.push(Backlink {
+                    block,
+                    title: titles[block].clone(),
+                    line: row,
+                    text: line.trim().to_string(),
+                });
+            }
+        }
+    }
+    found
+}
+
+/// `lines` with every link whose target `renamed` accepts pointed at `new_title`,
+/// keeping headings and aliases. `None` when nothing links there.
+pub fn rename_links<S: AsRef<str>>(
+    lines: &[S],
+    renamed: impl Fn(&str) -> bool,
+    new_title: &str,
+) -> Option<Vec<String>> {
+    let mut updated: Vec<String> = lines.iter().map(|l| l.as_ref().to_string()).collect();
+    let mut changed = false;
+    for (row, line) in prose_lines(lines) {
+        let links: Vec<_> = wiki_links(line)
+            .into_iter()
+            .filter(|link| renamed(&link.target))
+            .collect();
+        if links.is_empty() {
+            continue;
+        }
+        let mut rewritten = String::with_capacity(line.len());
+        let mut last = 0;
+        for link in links {
+            rewritten.push_str(&line[last..link.range.start]);
+            rewritten.push_str("[[");
+            rewritten.push_str(new_title);
+            if let Some(heading) = &link.heading {
+                rewritten.push('#');
+                rewritten.push_str(heading);
+            }
+            if let Some(alias) = &link.alias {
+                rewritten.push('|');
+                rewritten.push_str(alias);
+            }
+            rewritten.push_str("]]");
+            last = link.range.end;
+        }
+        rewritten.push_str(&line[last..]);
+        updated[row] = rewritten;
+        changed = true;
+    }
+    changed.then_some(updated)
+}
+
+/// Lines of every block with a link to block `renamed`, pointed at `new_title`
+/// instead, by block index. Shared by every way a block can be renamed.
+pub fn relink_blocks<S: AsRef<str>>(
+    titles: &[String],
+    blocks: &[&[S]],
+    renamed: usize,
+    new_title: &str,
+) -> Vec<(usize, Vec<String>)> {
+    let links_here = |target: &str| resolve_link(titles, target) == Some(renamed);
+    blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, lines)| {
+            rename_links(lines, links_here, new_title).map(|lines| (index, lines))
+        })
+        .collect()
+}
+
+/// Patches `style` onto the parts of `spans` that are wiki links.
+pub fn style_wiki_links(spans: Vec<Span<'static>>, style: Style) -> Vec<Span<'static>> {
+    let text: String = spans.iter().map(|span| span.content.as_ref()).collect();
+    if !text.contains("[[") {
+        return spans;
+    }
+    let links: Vec<Range<usize>> = wiki_links(&text).into_iter().map(|l| l.range).collect();
+    if links.is_empty() {
+        return spans;
+    }
+
+    let mut styled = Vec::with_capacity(spans.len() + links.len() * 2);
+    let mut offset = 0;
+    for span in spans {
+        let content = span.content.as_ref();
+        let span_range = offset..offset + content.len();
+        // Split points inside this span, where a link starts or ends
+        let mut cuts: Vec<usize> = links
+            .iter()
+            .flat_map(|link| [link.start, link.end])
+            .filter(|&cut| span_range.start < cut && cut < span_range.end)
+            .collect();
+        cuts.push(span_range.end);
+
+        let mut start = span_range.start;
+        for cut in cuts {
+            let piece = &content[start - offset..cut - offset];
+            let in_link = links.iter().any(|link| link.contains(&start));
+            let piece_style = if in_link {
+                span.style.patch(style)
+            } else {
+                span.style
+            };
+            styled.push(Span::styled(piece.to_string(), piece_style));
+            start = cut;
+        }
+        offset = span_range.end;
+    }
+    styled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::{Color, Modifier};
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_links() {
+        let links = wiki_links("see [[Daily Log]] and [[API#Auth|the auth]] [[ ]] [[unclosed");
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].target, "Daily Log");
+        assert_eq!(links[0].range, 4..17);
+        assert_eq!(links[1].target, "API");
+        assert_eq!(links[1].heading.as_deref(), Some("Auth"));
+        assert_eq!(links[1].alias.as_deref(), Some("the auth"));
+
+        let nested = wiki_links("[[a [[b]]");
+        assert_eq!(nested.len(), 1);
+        assert_eq!(nested[0].target, "b");
+    }
+
+    #[test]
+    fn test_link_at_column_counts_characters() {
+        let line = "日本 [[Tokyo]] x";
+        assert_eq!(wiki_link_at(line, 3).unwrap().target, "Tokyo");
+        assert_eq!(wiki_link_at(line, 11).unwrap().target, "Tokyo");
+        assert_eq!(wiki_link_at(line, 1), None);
+        assert_eq!(wiki_link_at(line, 12), None);
+    }
+
+    #[test]
+    fn test_resolve_prefers_exact_title() {
+        let titles = vec!["notes".to_string(), "Notes".to_string()];
+        assert_eq!(resolve_link(&titles, "Notes"), Some(1));
+        assert_eq!(resolve_link(&titles, "NOTES"), Some(0));
+        assert_eq!(resolve_link(&titles, "todo"), None);
+    }
+
+    #[test]
+    fn test_backlinks_skip_code_and_self() {
+        let titles = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let a = lines("[[B]] and [[b|again]]\n```\n[[B]]\n```");
+        let b = lines("[[B]] self");
+        let c = lines("nothing\n  - see [[B#Top]]");
+        let blocks = [a.as_slice(), b.as_slice(), c.as_slice()];
+        assert_eq!(
+            backlinks(&titles, &blocks, 1),
+            vec![
+                Backlink {
+                    block: 0,
+                    title: "A".to_string(),
+                    line: 0,
+                    text: "[[B]] and [[b|again]]".to_string()
+                },
+                Backlink {
+                    block: 2,
+                    title: "C".to_string(),
+                    line: 1,
+                    text: "- see [[B#Top]]".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rename_links_keeps_heading_and_alias() {
+        let note = lines("[[Old]], [[old#Top|x]] [[Other]]\n```\n[[Old]]\n```");
+        let renamed = rename_links(&note, |t| t.eq_ignore_ascii_case("old"), "New").unwrap();
+        assert_eq!(
+            renamed,
+            lines("[[New]], [[New#Top|x]] [[Other]]\n```\n[[Old]]\n```")
+        );
+        assert_eq!(rename_links(&note, |t| t == "Missing", "New"), None);
+    }
+
+    #[test]
+    fn test_style_wiki_links_splits_spans() {
+        let link = Style::default().add_modifier(Modifier::UNDERLINED);
+        let base = Style::default().fg(Color::Red);
+        let spans = vec![Span::styled("a [[B", base), Span::raw("]] c")];
+        let styled = style_wiki_links(spans, link);
+        let pieces: Vec<(&str, Style)> = styled
+            .iter()
+            .map(|s| (s.content.as_ref(), s.style))
+            .collect();
+        assert_eq!(
+            pieces,
+            vec![
+                ("a ", base),
+                ("[[B", base.patch(link)),
+                ("]]", link),
+                (" c", Style::default()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+#![cfg(unix)]
+
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use thoth_cli::control::{blocks_version, ControlRequest, ControlResponse, ControlServer};
+use thoth_cli::Block;
+
+/// Runs `thoth args` with `home` as its home directory while `control` stands in for
+/// a TUI editing `blocks`, answering writes with `answer`. Returns the output and
+/// what the TUI was asked.
+fn run_next_to_tui(
+    home: &Path,
+    args: &[&str],
+    control: &ControlServer,
+    blocks: &[Block],
+    answer: fn(&ControlRequest) -> ControlResponse,
+) -> (Output, Vec<ControlRequest>) {
+    let child = Command::new(env!("CARGO_BIN_EXE_thoth"))
+        .args(args)
+        .env("HOME", home)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let child = thread::spawn(move || child.wait_with_output().unwrap());
+    let mut received = Vec::new();
+    let output = loop {
+        control.serve_pending(|request| {
+            received.push(request.clone());
+            match request {
+                ControlRequest::Blocks => ControlResponse::Blocks {
+                    version: blocks_version(blocks),
+                    blocks: blocks.to_vec(),
+                },
+                request => answer(&request),
+            }
+        });
+        if child.is_finished() {
+            break child.join().unwrap();
+        }
+        thread::sleep(Duration::from_millis(2));
+    };
+    (output, received)
+}
+
+#[test]
+fn test_delete_goes_through_an_open_tui() {
+    let home = tempfile::tempdir().unwrap();
+    let notes = home.path().join("thoth_notes.md");
+    std::fs::write(&notes, "# Todo\nsaved\n").unwrap();
+    // The TUI has a block that is not saved yet
+    let blocks = vec![Block::new("Todo", "saved"), Block::new("Scratch", "draft")];
+    let control = ControlServer::bind(&notes).unwrap().unwrap();
+
+    let (output, received) = run_next_to_tui(
+        home.path(),
+        &["delete", "Scratch"],
+        &control,
+        &blocks,
+        |_| ControlResponse::Ok(String::new()),
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        received.last(),
+        Some(&ControlRequest::Delete {
+            version: blocks_version(&blocks),
+            name: "Scratch".to_string(),
+        })
+    );
+    // Left for the TUI to save
+    assert_eq!(std::fs::read_to_string(&notes).unwrap(), "# Todo\nsaved\n");
+
+    let (output, _) = run_next_to_tui(home.path(), &["delete", "Todo"], &control, &blocks, |_| {
+        ControlResponse::Error("The notes were changed".to_string())
+    });
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("The notes were changed"));
+    assert_eq!(std::fs::read_to_string(&notes).unwrap(), "# Todo\nsaved\n");
+}
@@ -1,6 +1,6 @@
 use thoth_cli::{
-    format_json, format_markdown, get_save_file_path, ScrollableTextArea, TitlePopup,
-    TitleSelectPopup,
+    clipboard::MemoryProvider, format_json, format_markdown, get_save_file_path, EditorClipboard,
+    ScrollableTextArea, TitlePopup, TitleSelectPopup,
 };
 use tui_textarea::TextArea;
 
@@ -29,9 +29,12 @@ fn test_full_application_flow() {
     sta.change_title("Updated Note 1".to_string());
     assert_eq!(sta.titles[0], "Updated Note 1");
 
-    // Test copy functionality (note: this should return an error)
-    // since the display is not connected in github actions
-    assert!(sta.copy_textarea_contents().is_err());
+    // Test copy functionality, in memory rather than through the system clipboard
+    // or the clipboard history, which tests should not write to
+    let memory = MemoryProvider::new();
+    sta.set_clipboard(EditorClipboard::with_provider(Box::new(memory.clone())));
+    sta.copy_textarea_contents().unwrap();
+    assert_eq!(memory.contents(), "This is the content of Note 1");
 
     // Test remove textarea
     sta.remove_textarea(1);
@@ -94,12 +97,10 @@ fn test_scrollable_textarea_scroll_behavior() {
 
 #[test]
 fn test_markdown_renderer_with_code_blocks() {
-    let mut renderer = thoth_cli::MarkdownRenderer::new();
+    let renderer = thoth_cli::MarkdownRenderer::new();
     let markdown =
         "# Header\n\n```rust\nfn main() {\n    println!(\"Hello, world!\");\n}\n```".to_string();
-    let rendered = renderer
-        .render_markdown(markdown, "".to_string(), 40)
-        .unwrap();
+    let rendered = renderer.render_markdown(markdown, 40).unwrap();
 
     assert!(rendered.lines.len() > 5);
     assert!(rendered.lines[0]
@@ -0,0 +1,375 @@
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpStream};
+use std::path::Path;
+use std::thread;
+
+use serde_json::{json, Value};
+use thoth_cli::control::{blocks_version, ControlRequest, ControlResponse, ControlServer};
+use thoth_cli::server::{ServeConfig, Server};
+use thoth_cli::Block;
+
+struct HttpResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl HttpResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+/// Starts a server on a free port for the notes file at `path`.
+fn start_server(path: &Path, token: Option<&str>) -> u16 {
+    let server = Server::bind(ServeConfig {
+        host: Ipv4Addr::LOCALHOST.into(),
+        port: 0,
+        token: token.map(str::to_string),
+        notes_path: path.to_path_buf(),
+    })
+    .unwrap();
+    let port = server.port();
+    thread::spawn(move || server.run());
+    port
+}
+
+/// Sends a request with `Host: localhost:<port>`, and a JSON content type when there
+/// is a body, unless `headers` sets them.
+fn request(
+    port: u16,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<&Value>,
+) -> HttpResponse {
+    let body = body.map(Value::to_string).unwrap_or_default();
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nConnection: close\r\nContent-Length: {}\r\n",
+        method,
+        path,
+        body.len()
+    );
+    let has = |name: &str| {
+        headers
+            .iter()
+            .any(|(key, _)| key.eq_ignore_ascii_case(name))
+    };
+    if !has("Host") {
+        head.push_str(&format!("Host: localhost:{}\r\n", port));
+    }
+    if !body.is_empty() && !has("Content-Type") {
+        head.push_str("Content-Type: application/json\r\n");
+    }
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).unwrap();
+    stream.write_all(body.as_bytes()).unwrap();
+
+    let mut raw = String::new();
+    stream.read_to_string(&mut raw).unwrap();
+    let (head, body) = raw.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.lines();
+    let status = lines
+        .next()
+        .unwrap()
+        .split(' ')
+        .nth(1)
+        .unwrap()
+        .parse()
+        .unwrap();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+    HttpResponse {
+        status,
+        headers,
+        body: body.to_string(),
+    }
+}
+
+#[test]
+fn test_crud_over_http() {
+    let dir = tempfile::tempdir().unwrap();
+    let notes = dir.path().join("notes.md");
+    std::fs::write(&notes, "# Existing\nhello\n\\# not a title\n").unwrap();
+    let port = start_server(&notes, None);
+
+    let listed = request(port, "GET", "/blocks", &[], None);
+    assert_eq!(listed.status, 200);
+    assert_eq!(listed.json()[0]["title"], "Existing");
+
+    let existing = request(port, "GET", "/blocks/Existing", &[], None);
+    assert_eq!(existing.json()["content"], "hello\n# not a title");
+
+    let created = request(
+        port,
+        "POST",
+        "/blocks",
+        &[],
+        Some(&json!({ "title": "Daily log", "content": "- [ ] write tests" })),
+    );
+    assert_eq!(created.status, 201);
+    assert_eq!(created.header("Location"), Some("/blocks/Daily%20log"));
+    let duplicate = request(
+        port,
+        "POST",
+        "/blocks",
+        &[],
+        Some(&json!({ "title": "Daily log", "content": "" })),
+    );
+    assert_eq!(duplicate.status, 409);
+
+    let updated = request(
+        port,
+        "PUT",
+        "/blocks/Daily%20log",
+        &[],
+        Some(&json!({ "content": "- [x] write tests" })),
+    );
+    assert_eq!(updated.status, 200);
+    assert_eq!(updated.json()["title"], "Daily log");
+
+    let found = request(port, "GET", "/search?q=WRITE+tests", &[], None);
+    assert_eq!(
+        found.json(),
+        json!([{ "title": "Daily log", "line": 0, "text": "- [x] write tests" }])
+    );
+
+    let deleted = request(port, "DELETE", "/blocks/Existing", &[], None);
+    assert_eq!(deleted.status, 204);
+    assert_eq!(
+        request(port, "GET", "/blocks/Existing", &[], None).status,
+        404
+    );
+    assert_eq!(
+        std::fs::read_to_string(&notes).unwrap(),
+        "# Daily log\n- [x] write tests\n"
+    );
+}
+
+#[test]
+fn test_etags_detect_concurrent_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let notes = dir.path().join("notes.md");
+    std::fs::write(&notes, "# Todo\nfirst\n").unwrap();
+    let port = start_server(&notes, None);
+
+    let read = request(port, "GET", "/blocks/Todo", &[], None);
+    let etag = read.header("ETag").unwrap().to_string();
+    let unchanged = request(
+        port,
+        "GET",
+        "/blocks/Todo",
+        &[("If-None-Match", &etag)],
+        None,
+    );
+    assert_eq!(unchanged.status, 304);
+
+    // Someone else, such as the TUI, saves the notes file in the meantime
+    std::fs::write(&notes, "# Todo\nfirst\nsecond\n").unwrap();
+    let stale = request(
+        port,
+        "PUT",
+        "/blocks/Todo",
+        &[("If-Match", &etag)],
+        Some(&json!({ "content": "mine" })),
+    );
+    assert_eq!(stale.status, 412);
+    assert!(std::fs::read_to_string(&notes).unwrap().contains("second"));
+
+    let fresh = request(port, "GET", "/blocks/Todo", &[], None);
+    let etag = fresh.header("ETag").unwrap().to_string();
+    let renamed = request(
+        port,
+        "PUT",
+        "/blocks/Todo",
+        &[("If-Match", &etag)],
+        Some(&json!({ "title": "Done", "content": "mine" })),
+    );
+    assert_eq!(renamed.status, 200);
+    assert_ne!(renamed.header("ETag").unwrap(), etag);
+    assert_eq!(
+        request(port, "DELETE", "/blocks/Done", &[("If-Match", &etag)], None).status,
+        412
+    );
+}
+
+#[test]
+fn test_token_and_bad_requests() {
+    let dir = tempfile::tempdir().unwrap();
+    let port = start_server(&dir.path().join("notes.md"), Some("secret"));
+
+    assert_eq!(request(port, "GET", "/blocks", &[], None).status, 401);
+    let wrong = [("Authorization", "Bearer nope")];
+    assert_eq!(request(port, "GET", "/blocks", &wrong, None).status, 401);
+
+    let auth = [("Authorization", "Bearer secret")];
+    let empty = request(port, "GET", "/blocks", &auth, None);
+    assert_eq!(empty.status, 200);
+    assert_eq!(empty.json(), json!([]));
+
+    let bad_body = request(
+        port,
+        "POST",
+        "/blocks",
+        &auth,
+        Some(&json!({ "name": "x" })),
+    );
+    assert_eq!(bad_body.status, 400);
+    assert!(bad_body.json()["error"].is_string());
+    let bad_title = request(
+        port,
+        "POST",
+        "/blocks",
+        &auth,
+        Some(&json!({ "title": "two\nlines", "content": "" })),
+    );
+    assert_eq!(bad_title.status, 400);
+    assert_eq!(request(port, "GET", "/search", &auth, None).status, 400);
+    assert_eq!(request(port, "PATCH", "/blocks", &auth, None).status, 405);
+    assert_eq!(request(port, "GET", "/nowhere", &auth, None).status, 404);
+}
+
+#[test]
+fn test_requests_from_web_pages_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let notes = dir.path().join("notes.md");
+    let port = start_server(&notes, None);
+
+    // A page on a domain rebound to 127.0.0.1 sends its own name as the host
+    let rebound = request(port, "GET", "/blocks", &[("Host", "evil.example:80")], None);
+    assert_eq!(rebound.status, 403);
+    let loopback_host = format!("127.0.0.1:{}", port);
+    let direct = request(port, "GET", "/blocks", &[("Host", &loopback_host)], None);
+    assert_eq!(direct.status, 200);
+
+    // Cross-site forms can only send simple content types such as text/plain
+    let form = request(
+        port,
+        "POST",
+        "/blocks",
+        &[("Content-Type", "text/plain")],
+        Some(&json!({ "title": "pwned", "content": "" })),
+    );
+    assert_eq!(form.status, 415);
+    assert!(!notes.exists());
+
+    let open = Server::bind(ServeConfig {
+        host: Ipv4Addr::UNSPECIFIED.into(),
+        port: 0,
+        token: None,
+        notes_path: notes,
+    });
+    assert!(open.is_err());
+}
+
+/// Stands in for a TUI editing `notes`, holding `blocks` and answering with `answer`.
+/// Returns every request it got once `stop` is set.
+#[cfg(unix)]
+fn fake_tui(
+    notes: &Path,
+    blocks: Vec<Block>,
+    answer: fn(&[Block], ControlRequest) -> ControlResponse,
+) -> (
+    std::sync::Arc<std::sync::atomic::AtomicBool>,
+    thread::JoinHandle<Vec<ControlRequest>>,
+) {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    let control = ControlServer::bind(notes).unwrap().unwrap();
+    let stop = std::sync::Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    let handle = thread::spawn(move || {
+        let mut received = Vec::new();
+        while !stopped.load(Ordering::SeqCst) {
+            control.serve_pending(|request| {
+                received.push(request.clone());
+                answer(&blocks, request)
+            });
+            thread::sleep(std::time::Duration::from_millis(2));
+        }
+        received
+    });
+    (stop, handle)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_writes_go_through_an_open_tui() {
+    let dir = tempfile::tempdir().unwrap();
+    let notes = dir.path().join("notes.md");
+    std::fs::write(&notes, "# Todo\nsaved\n").unwrap();
+    let unsaved = vec![Block::new("Todo", "saved\nnot saved yet")];
+    let (stop, tui) = fake_tui(&notes, unsaved.clone(), |blocks, request| match request {
+        ControlRequest::Blocks => ControlResponse::Blocks {
+            version: blocks_version(blocks),
+            blocks: blocks.to_vec(),
+        },
+        _ => ControlResponse::Ok(String::new()),
+    });
+    let port = start_server(&notes, None);
+
+    let read = request(port, "GET", "/blocks/Todo", &[], None);
+    assert_eq!(read.json()["content"], "saved\nnot saved yet");
+    let etag = read.header("ETag").unwrap().to_string();
+    let updated = request(
+        port,
+        "PUT",
+        "/blocks/Todo",
+        &[("If-Match", &etag)],
+        Some(&json!({ "title": "Done" })),
+    );
+    assert_eq!(updated.status, 200);
+    assert_eq!(updated.json()["title"], "Done");
+    let missing = request(port, "DELETE", "/blocks/Nope", &[], None);
+    assert_eq!(missing.status, 404);
+
+    stop.store(true, std::sync::atomic::Ordering::SeqCst);
+    let received = tui.join().unwrap();
+    assert!(received.contains(&ControlRequest::Update {
+        version: blocks_version(&unsaved),
+        name: "Todo".to_string(),
+        new_name: Some("Done".to_string()),
+        content: None,
+    }));
+    // The TUI saves the change itself
+    assert_eq!(std::fs::read_to_string(&notes).unwrap(), "# Todo\nsaved\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_writes_are_refused_when_the_tui_cannot_take_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let notes = dir.path().join("notes.md");
+    std::fs::write(&notes, "# Todo\nsaved\n").unwrap();
+    let (stop, tui) = fake_tui(&notes, Vec::new(), |_, _| {
+        ControlResponse::Error("Invalid request".to_string())
+    });
+    let port = start_server(&notes, None);
+
+    let read = request(port, "GET", "/blocks/Todo", &[], None);
+    assert_eq!(read.json()["content"], "saved");
+    let created = request(
+        port,
+        "POST",
+        "/blocks",
+        &[],
+        Some(&json!({ "title": "New", "content": "" })),
+    );
+    assert_eq!(created.status, 409);
+    assert_eq!(std::fs::read_to_string(&notes).unwrap(), "# Todo\nsaved\n");
+
+    stop.store(true, std::sync::atomic::Ordering::SeqCst);
+    tui.join().unwrap();
+}